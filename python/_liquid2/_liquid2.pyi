@@ -1,6 +1,11 @@
 from __future__ import annotations
 
 from enum import Enum
+from os import PathLike
+from typing import BinaryIO
+from typing import Callable
+from typing import Iterator
+from typing import Mapping
 from typing import TypeAlias
 
 class Whitespace(Enum):
@@ -9,25 +14,93 @@ class Whitespace(Enum):
     Smart = ...
     Default = ...
 
+class ExpressionType(Enum):
+    Logical = ...
+    Nodes = ...
+    Value = ...
+
+class FunctionSignature:
+    def __init__(
+        self, param_types: list[ExpressionType], return_type: ExpressionType
+    ) -> None: ...
+    @property
+    def param_types(self) -> list[ExpressionType]: ...
+    @property
+    def return_type(self) -> ExpressionType: ...
+
+class Lexer:
+    def __init__(
+        self,
+        *,
+        functions: dict[str, FunctionSignature] | None = None,
+        standard_functions: bool = True,
+        max_index: int = 2**53 - 1,
+        max_int: int = 2**53 - 1,
+    ) -> None: ...
+    def tokenize(self, source: str, *, trim: Whitespace | None = None) -> list[Markup]: ...
+    def tokenize_many(
+        self, sources: list[str], *, trim: Whitespace | None = None
+    ) -> list[list[Markup] | PyLiquidError]: ...
+    def tokenize_paths(
+        self,
+        paths: list[str | PathLike[str]],
+        *,
+        trim: Whitespace | None = None,
+    ) -> list[list[Markup] | PyLiquidError | OSError]: ...
+    def parse_query(self, path: str) -> Query: ...
+    def parse_jsonpath_query(self, path: str) -> Query: ...
+    def function_names(self) -> list[str]: ...
+
 class Markup:
+    def __eq__(self, other: object) -> bool: ...
+    def __hash__(self) -> int: ...
+    def equals(self, other: object, *, spans: bool = True) -> bool: ...
+    def without_spans(self) -> Markup: ...
+    def normalize(self, *, spans: bool = False) -> Markup: ...
+    def validate(self, *, lexer: Lexer | None = None) -> None: ...
+    def to_bytes(self) -> bytes: ...
+    @classmethod
+    def from_bytes(cls, data: bytes) -> Markup: ...
+
     class Content:
-        __match_args__ = ("text", "span")
+        __match_args__ = ("text", "span", "trimmed_span")
+        def __init__(
+            self,
+            text: str,
+            span: tuple[int, int] | None = None,
+            trimmed_span: tuple[int, int] | None = None,
+        ) -> None: ...
         @property
         def text(self) -> str: ...
         @property
-        def span(self) -> tuple[int, int]: ...
+        def span(self) -> tuple[int, int] | None: ...
+        @property
+        def trimmed_span(self) -> tuple[int, int] | None: ...
 
     class Raw:
         __match_args__ = ("wc", "text", "span")
+        def __init__(
+            self,
+            wc: tuple[Whitespace, Whitespace, Whitespace, Whitespace],
+            text: str,
+            span: tuple[int, int] | None = None,
+        ) -> None: ...
         @property
         def wc(self) -> tuple[Whitespace, Whitespace, Whitespace, Whitespace]: ...
         @property
         def text(self) -> str: ...
         @property
-        def span(self) -> tuple[int, int]: ...
+        def span(self) -> tuple[int, int] | None: ...
 
     class Comment:
         __match_args__ = ("wc", "hashes", "text", "span")
+        def __init__(
+            self,
+            wc: tuple[Whitespace, Whitespace],
+            hashes: str,
+            text: str,
+            span: tuple[int, int] | None = None,
+        ) -> None: ...
         @property
         def wc(self) -> tuple[Whitespace, Whitespace]: ...
         @property
@@ -35,19 +108,32 @@ class Markup:
         @property
         def text(self) -> str: ...
         @property
-        def span(self) -> tuple[int, int]: ...
+        def span(self) -> tuple[int, int] | None: ...
 
     class Output:
         __match_args__ = ("wc", "expression", "span")
+        def __init__(
+            self,
+            wc: tuple[Whitespace, Whitespace],
+            expression: list[Token],
+            span: tuple[int, int] | None = None,
+        ) -> None: ...
         @property
         def wc(self) -> tuple[Whitespace, Whitespace]: ...
         @property
         def expression(self) -> list[Token]: ...
         @property
-        def span(self) -> tuple[int, int]: ...
+        def span(self) -> tuple[int, int] | None: ...
 
     class Tag:
         __match_args__ = ("wc", "name", "expression", "span")
+        def __init__(
+            self,
+            wc: tuple[Whitespace, Whitespace],
+            name: str,
+            expression: list[Token] | None,
+            span: tuple[int, int] | None = None,
+        ) -> None: ...
         @property
         def wc(self) -> tuple[Whitespace, Whitespace]: ...
         @property
@@ -55,10 +141,17 @@ class Markup:
         @property
         def expression(self) -> list[Token]: ...
         @property
-        def span(self) -> tuple[int, int]: ...
+        def span(self) -> tuple[int, int] | None: ...
 
     class Lines:
         __match_args__ = ("wc", "name", "statements", "span")
+        def __init__(
+            self,
+            wc: tuple[Whitespace, Whitespace],
+            name: str,
+            statements: list[Markup.Tag],
+            span: tuple[int, int] | None = None,
+        ) -> None: ...
         @property
         def wc(self) -> tuple[Whitespace, Whitespace]: ...
         @property
@@ -66,219 +159,292 @@ class Markup:
         @property
         def statements(self) -> list[Markup.Tag]: ...
         @property
-        def span(self) -> tuple[int, int]: ...
+        def span(self) -> tuple[int, int] | None: ...
 
     class EOI:
         pass
 
 class Token:
+    def __eq__(self, other: object) -> bool: ...
+    def __hash__(self) -> int: ...
+    def equals(self, other: object, *, spans: bool = True) -> bool: ...
+    def without_spans(self) -> Token: ...
+    def normalize(self, *, spans: bool = False) -> Token: ...
+    def validate(self, *, lexer: Lexer | None = None) -> None: ...
+    def to_bytes(self) -> bytes: ...
+    @classmethod
+    def from_bytes(cls, data: bytes) -> Token: ...
+
     class True_:  # noqa: N801
         __match_args__ = ("span",)
+        def __init__(self, span: tuple[int, int] | None = None) -> None: ...
         @property
-        def span(self) -> tuple[int, int]: ...
+        def span(self) -> tuple[int, int] | None: ...
 
     class False_:  # noqa: N801
         __match_args__ = ("span",)
+        def __init__(self, span: tuple[int, int] | None = None) -> None: ...
         @property
-        def span(self) -> tuple[int, int]: ...
+        def span(self) -> tuple[int, int] | None: ...
 
     class And:
         __match_args__ = ("span",)
+        def __init__(self, span: tuple[int, int] | None = None) -> None: ...
         @property
-        def span(self) -> tuple[int, int]: ...
+        def span(self) -> tuple[int, int] | None: ...
 
     class Or:
         __match_args__ = ("span",)
+        def __init__(self, span: tuple[int, int] | None = None) -> None: ...
         @property
-        def span(self) -> tuple[int, int]: ...
+        def span(self) -> tuple[int, int] | None: ...
 
     class In:
         __match_args__ = ("span",)
+        def __init__(self, span: tuple[int, int] | None = None) -> None: ...
         @property
-        def span(self) -> tuple[int, int]: ...
+        def span(self) -> tuple[int, int] | None: ...
 
     class Not:
         __match_args__ = ("span",)
+        def __init__(self, span: tuple[int, int] | None = None) -> None: ...
         @property
-        def span(self) -> tuple[int, int]: ...
+        def span(self) -> tuple[int, int] | None: ...
 
     class Contains:
         __match_args__ = ("span",)
+        def __init__(self, span: tuple[int, int] | None = None) -> None: ...
         @property
-        def span(self) -> tuple[int, int]: ...
+        def span(self) -> tuple[int, int] | None: ...
 
     class Null:
         __match_args__ = ("span",)
+        def __init__(self, span: tuple[int, int] | None = None) -> None: ...
         @property
-        def span(self) -> tuple[int, int]: ...
+        def span(self) -> tuple[int, int] | None: ...
 
     class If:
         __match_args__ = ("span",)
+        def __init__(self, span: tuple[int, int] | None = None) -> None: ...
         @property
-        def span(self) -> tuple[int, int]: ...
+        def span(self) -> tuple[int, int] | None: ...
 
     class Else:
         __match_args__ = ("span",)
+        def __init__(self, span: tuple[int, int] | None = None) -> None: ...
         @property
-        def span(self) -> tuple[int, int]: ...
+        def span(self) -> tuple[int, int] | None: ...
 
     class With:
         __match_args__ = ("span",)
+        def __init__(self, span: tuple[int, int] | None = None) -> None: ...
         @property
-        def span(self) -> tuple[int, int]: ...
+        def span(self) -> tuple[int, int] | None: ...
 
     class Required:
         __match_args__ = ("span",)
+        def __init__(self, span: tuple[int, int] | None = None) -> None: ...
         @property
-        def span(self) -> tuple[int, int]: ...
+        def span(self) -> tuple[int, int] | None: ...
 
     class As:
         __match_args__ = ("span",)
+        def __init__(self, span: tuple[int, int] | None = None) -> None: ...
         @property
-        def span(self) -> tuple[int, int]: ...
+        def span(self) -> tuple[int, int] | None: ...
 
     class For:
         __match_args__ = ("span",)
+        def __init__(self, span: tuple[int, int] | None = None) -> None: ...
         @property
-        def span(self) -> tuple[int, int]: ...
+        def span(self) -> tuple[int, int] | None: ...
 
     class Eq:
         __match_args__ = ("span",)
+        def __init__(self, span: tuple[int, int] | None = None) -> None: ...
         @property
-        def span(self) -> tuple[int, int]: ...
+        def span(self) -> tuple[int, int] | None: ...
 
     class Ne:
         __match_args__ = ("span",)
+        def __init__(self, span: tuple[int, int] | None = None) -> None: ...
         @property
-        def span(self) -> tuple[int, int]: ...
+        def span(self) -> tuple[int, int] | None: ...
 
     class Ge:
         __match_args__ = ("span",)
+        def __init__(self, span: tuple[int, int] | None = None) -> None: ...
         @property
-        def span(self) -> tuple[int, int]: ...
+        def span(self) -> tuple[int, int] | None: ...
 
     class Gt:
         __match_args__ = ("span",)
+        def __init__(self, span: tuple[int, int] | None = None) -> None: ...
         @property
-        def span(self) -> tuple[int, int]: ...
+        def span(self) -> tuple[int, int] | None: ...
 
     class Le:
         __match_args__ = ("span",)
+        def __init__(self, span: tuple[int, int] | None = None) -> None: ...
         @property
-        def span(self) -> tuple[int, int]: ...
+        def span(self) -> tuple[int, int] | None: ...
 
     class Lt:
         __match_args__ = ("span",)
+        def __init__(self, span: tuple[int, int] | None = None) -> None: ...
         @property
-        def span(self) -> tuple[int, int]: ...
+        def span(self) -> tuple[int, int] | None: ...
 
     class Colon:
         __match_args__ = ("span",)
+        def __init__(self, span: tuple[int, int] | None = None) -> None: ...
         @property
-        def span(self) -> tuple[int, int]: ...
+        def span(self) -> tuple[int, int] | None: ...
 
     class Pipe:
         __match_args__ = ("span",)
+        def __init__(self, span: tuple[int, int] | None = None) -> None: ...
         @property
-        def span(self) -> tuple[int, int]: ...
+        def span(self) -> tuple[int, int] | None: ...
 
     class DoublePipe:
         __match_args__ = ("span",)
+        def __init__(self, span: tuple[int, int] | None = None) -> None: ...
         @property
-        def span(self) -> tuple[int, int]: ...
+        def span(self) -> tuple[int, int] | None: ...
 
     class Comma:
         __match_args__ = ("span",)
+        def __init__(self, span: tuple[int, int] | None = None) -> None: ...
         @property
-        def span(self) -> tuple[int, int]: ...
+        def span(self) -> tuple[int, int] | None: ...
 
     class LeftParen:
         __match_args__ = ("span",)
+        def __init__(self, span: tuple[int, int] | None = None) -> None: ...
         @property
-        def span(self) -> tuple[int, int]: ...
+        def span(self) -> tuple[int, int] | None: ...
 
     class RightParen:
         __match_args__ = ("span",)
+        def __init__(self, span: tuple[int, int] | None = None) -> None: ...
         @property
-        def span(self) -> tuple[int, int]: ...
+        def span(self) -> tuple[int, int] | None: ...
 
     class Assign:
         __match_args__ = ("span",)
+        def __init__(self, span: tuple[int, int] | None = None) -> None: ...
         @property
-        def span(self) -> tuple[int, int]: ...
+        def span(self) -> tuple[int, int] | None: ...
 
     class Word:
         __match_args__ = ("value", "span")
+        def __init__(self, value: str, span: tuple[int, int] | None = None) -> None: ...
         @property
         def value(self) -> str: ...
         @property
-        def span(self) -> tuple[int, int]: ...
+        def span(self) -> tuple[int, int] | None: ...
 
     class StringLiteral:
         __match_args__ = ("value", "span")
+        def __init__(self, value: str, span: tuple[int, int] | None = None) -> None: ...
         @property
         def value(self) -> str: ...
         @property
-        def span(self) -> tuple[int, int]: ...
+        def span(self) -> tuple[int, int] | None: ...
 
     class IntegerLiteral:
         __match_args__ = ("value", "span")
+        def __init__(self, value: int, span: tuple[int, int] | None = None) -> None: ...
         @property
         def value(self) -> int: ...
         @property
-        def span(self) -> tuple[int, int]: ...
+        def span(self) -> tuple[int, int] | None: ...
 
     class FloatLiteral:
         __match_args__ = ("value", "span")
+        def __init__(self, value: float, span: tuple[int, int] | None = None) -> None: ...
         @property
         def value(self) -> float: ...
         @property
-        def span(self) -> tuple[int, int]: ...
+        def span(self) -> tuple[int, int] | None: ...
 
     class RangeLiteral:
         __match_args__ = ("start", "stop", "span")
+        def __init__(
+            self,
+            start: RangeArgument,
+            stop: RangeArgument,
+            span: tuple[int, int] | None = None,
+        ) -> None: ...
         @property
         def start(self) -> RangeArgument: ...
         @property
         def stop(self) -> RangeArgument: ...
         @property
-        def span(self) -> tuple[int, int]: ...
+        def span(self) -> tuple[int, int] | None: ...
 
     class Query:
         __match_args__ = ("path", "span")
+        def __init__(self, path: Query, span: tuple[int, int] | None = None) -> None: ...
         @property
         def path(self) -> Query: ...
         @property
-        def span(self) -> tuple[int, int]: ...
+        def span(self) -> tuple[int, int] | None: ...
 
 class RangeArgument:
+    def __eq__(self, other: object) -> bool: ...
+    def __hash__(self) -> int: ...
+    def equals(self, other: object, *, spans: bool = True) -> bool: ...
+    def without_spans(self) -> RangeArgument: ...
+    def normalize(self, *, spans: bool = False) -> RangeArgument: ...
+    def validate(self, *, lexer: Lexer | None = None) -> None: ...
+    def to_bytes(self) -> bytes: ...
+    @classmethod
+    def from_bytes(cls, data: bytes) -> RangeArgument: ...
+
     class StringLiteral:
         __match_args__ = ("value", "span")
+        def __init__(self, value: str, span: tuple[int, int] | None = None) -> None: ...
         @property
         def value(self) -> str: ...
         @property
-        def span(self) -> tuple[int, int]: ...
+        def span(self) -> tuple[int, int] | None: ...
 
     class IntegerLiteral:
         __match_args__ = ("value", "span")
+        def __init__(self, value: int, span: tuple[int, int] | None = None) -> None: ...
         @property
         def value(self) -> int: ...
         @property
-        def span(self) -> tuple[int, int]: ...
+        def span(self) -> tuple[int, int] | None: ...
 
     class FloatLiteral:
         __match_args__ = ("value", "span")
+        def __init__(self, value: float, span: tuple[int, int] | None = None) -> None: ...
         @property
         def value(self) -> float: ...
         @property
-        def span(self) -> tuple[int, int]: ...
+        def span(self) -> tuple[int, int] | None: ...
 
     class Query:
         __match_args__ = ("path", "span")
+        def __init__(self, path: Query, span: tuple[int, int] | None = None) -> None: ...
         @property
         def path(self) -> Query: ...
         @property
-        def span(self) -> tuple[int, int]: ...
+        def span(self) -> tuple[int, int] | None: ...
+
+class Range:
+    def __init__(self, token: Token) -> None: ...
+    @property
+    def start(self) -> RangeArgument: ...
+    @property
+    def stop(self) -> RangeArgument: ...
+    @property
+    def span(self) -> tuple[int, int] | None: ...
+    def resolve(self, resolver: Callable[[Query], object]) -> tuple[int, int]: ...
+    def evaluate(self, resolver: Callable[[Query], object]) -> range: ...
 
 class ComparisonOperator(Enum):
     Eq = ...
@@ -293,51 +459,77 @@ class LogicalOperator(Enum):
     Or = ...
 
 class FilterExpression:
+    def __eq__(self, other: object) -> bool: ...
+    def __hash__(self) -> int: ...
+    def equals(self, other: object, *, spans: bool = True) -> bool: ...
+    def without_spans(self) -> FilterExpression: ...
+    def normalize(self, *, spans: bool = False) -> FilterExpression: ...
+    def validate(self, *, lexer: Lexer | None = None) -> None: ...
+    def to_bytes(self) -> bytes: ...
+    @classmethod
+    def from_bytes(cls, data: bytes) -> FilterExpression: ...
+
     class True_:  # noqa: N801
         __match_args__ = ("span",)
+        def __init__(self, span: tuple[int, int] | None = None) -> None: ...
         @property
-        def span(self) -> tuple[int, int]: ...
+        def span(self) -> tuple[int, int] | None: ...
 
     class False_:  # noqa: N801
         __match_args__ = ("span",)
+        def __init__(self, span: tuple[int, int] | None = None) -> None: ...
         @property
-        def span(self) -> tuple[int, int]: ...
+        def span(self) -> tuple[int, int] | None: ...
 
     class Null:
         __match_args__ = ("span",)
+        def __init__(self, span: tuple[int, int] | None = None) -> None: ...
         @property
-        def span(self) -> tuple[int, int]: ...
+        def span(self) -> tuple[int, int] | None: ...
 
     class StringLiteral:
         __match_args__ = ("value", "span")
+        def __init__(self, value: str, span: tuple[int, int] | None = None) -> None: ...
         @property
         def value(self) -> str: ...
         @property
-        def span(self) -> tuple[int, int]: ...
+        def span(self) -> tuple[int, int] | None: ...
 
     class Int:
         __match_args__ = ("value", "span")
+        def __init__(self, value: int, span: tuple[int, int] | None = None) -> None: ...
         @property
         def value(self) -> int: ...
         @property
-        def span(self) -> tuple[int, int]: ...
+        def span(self) -> tuple[int, int] | None: ...
 
     class Float:
         __match_args__ = ("value", "span")
+        def __init__(self, value: float, span: tuple[int, int] | None = None) -> None: ...
         @property
         def value(self) -> float: ...
         @property
-        def span(self) -> tuple[int, int]: ...
+        def span(self) -> tuple[int, int] | None: ...
 
     class Not:
         __match_args__ = ("expression", "span")
+        def __init__(
+            self, expression: FilterExpression, span: tuple[int, int] | None = None
+        ) -> None: ...
         @property
         def expression(self) -> FilterExpression: ...
         @property
-        def span(self) -> tuple[int, int]: ...
+        def span(self) -> tuple[int, int] | None: ...
 
     class Logical:
         __match_args__ = ("left", "operator", "right", "span")
+        def __init__(
+            self,
+            left: FilterExpression,
+            operator: LogicalOperator,
+            right: FilterExpression,
+            span: tuple[int, int] | None = None,
+        ) -> None: ...
         @property
         def left(self) -> FilterExpression: ...
         @property
@@ -345,10 +537,17 @@ class FilterExpression:
         @property
         def right(self) -> FilterExpression: ...
         @property
-        def span(self) -> tuple[int, int]: ...
+        def span(self) -> tuple[int, int] | None: ...
 
     class Comparison:
         __match_args__ = ("left", "operator", "right", "span")
+        def __init__(
+            self,
+            left: FilterExpression,
+            operator: ComparisonOperator,
+            right: FilterExpression,
+            span: tuple[int, int] | None = None,
+        ) -> None: ...
         @property
         def left(self) -> FilterExpression: ...
         @property
@@ -356,48 +555,75 @@ class FilterExpression:
         @property
         def right(self) -> FilterExpression: ...
         @property
-        def span(self) -> tuple[int, int]: ...
+        def span(self) -> tuple[int, int] | None: ...
 
     class RelativeQuery:
         __match_args__ = ("query", "span")
+        def __init__(self, query: Query, span: tuple[int, int] | None = None) -> None: ...
         @property
         def query(self) -> Query: ...
         @property
-        def span(self) -> tuple[int, int]: ...
+        def span(self) -> tuple[int, int] | None: ...
 
     class RootQuery:
         __match_args__ = ("query", "span")
+        def __init__(self, query: Query, span: tuple[int, int] | None = None) -> None: ...
         @property
         def query(self) -> Query: ...
         @property
-        def span(self) -> tuple[int, int]: ...
+        def span(self) -> tuple[int, int] | None: ...
 
     class Function:
         __match_args__ = ("name", "args", "span")
+        def __init__(
+            self,
+            name: str,
+            args: list[FilterExpression],
+            span: tuple[int, int] | None = None,
+        ) -> None: ...
         @property
         def name(self) -> str: ...
         @property
         def args(self) -> list[FilterExpression]: ...
         @property
-        def span(self) -> tuple[int, int]: ...
+        def span(self) -> tuple[int, int] | None: ...
 
 class Selector:
+    def __eq__(self, other: object) -> bool: ...
+    def __hash__(self) -> int: ...
+    def equals(self, other: object, *, spans: bool = True) -> bool: ...
+    def without_spans(self) -> Selector: ...
+    def normalize(self, *, spans: bool = False) -> Selector: ...
+    def validate(self, *, lexer: Lexer | None = None) -> None: ...
+    def to_bytes(self) -> bytes: ...
+    @classmethod
+    def from_bytes(cls, data: bytes) -> Selector: ...
+
     class Name:
         __match_args__ = ("name", "span")
+        def __init__(self, name: str, span: tuple[int, int] | None = None) -> None: ...
         @property
         def name(self) -> str: ...
         @property
-        def span(self) -> tuple[int, int]: ...
+        def span(self) -> tuple[int, int] | None: ...
 
     class Index:
         __match_args__ = ("index", "span")
+        def __init__(self, index: int, span: tuple[int, int] | None = None) -> None: ...
         @property
         def index(self) -> int: ...
         @property
-        def span(self) -> tuple[int, int]: ...
+        def span(self) -> tuple[int, int] | None: ...
 
     class Slice:
         __match_args__ = ("start", "stop", "step", "span")
+        def __init__(
+            self,
+            start: int | None = None,
+            stop: int | None = None,
+            step: int | None = None,
+            span: tuple[int, int] | None = None,
+        ) -> None: ...
         @property
         def start(self) -> int | None: ...
         @property
@@ -405,61 +631,298 @@ class Selector:
         @property
         def step(self) -> int | None: ...
         @property
-        def span(self) -> tuple[int, int]: ...
+        def span(self) -> tuple[int, int] | None: ...
 
     class Wild:
         __match_args__ = ("span",)
+        def __init__(self, span: tuple[int, int] | None = None) -> None: ...
         @property
-        def span(self) -> tuple[int, int]: ...
+        def span(self) -> tuple[int, int] | None: ...
 
     class Filter:
         __match_args__ = ("expression", "span")
+        def __init__(
+            self, expression: FilterExpression, span: tuple[int, int] | None = None
+        ) -> None: ...
         @property
         def expression(self) -> FilterExpression: ...
         @property
-        def span(self) -> tuple[int, int]: ...
+        def span(self) -> tuple[int, int] | None: ...
 
     class SingularQuery:
         __match_args__ = ("query", "span")
+        def __init__(self, query: Query, span: tuple[int, int] | None = None) -> None: ...
         @property
         def query(self) -> Query: ...
         @property
-        def span(self) -> tuple[int, int]: ...
+        def span(self) -> tuple[int, int] | None: ...
 
 SelectorList = list[Selector]
 
 class Segment:
+    def __eq__(self, other: object) -> bool: ...
+    def __hash__(self) -> int: ...
+    def equals(self, other: object, *, spans: bool = True) -> bool: ...
+    def without_spans(self) -> Segment: ...
+    def normalize(self, *, spans: bool = False) -> Segment: ...
+    def validate(self, *, lexer: Lexer | None = None) -> None: ...
+    def to_bytes(self) -> bytes: ...
+    @classmethod
+    def from_bytes(cls, data: bytes) -> Segment: ...
+
     class Child:
         __match_args__ = ("selectors", "span")
+        def __init__(
+            self, selectors: SelectorList, span: tuple[int, int] | None = None
+        ) -> None: ...
         @property
         def selectors(self) -> SelectorList: ...
         @property
-        def span(self) -> tuple[int, int]: ...
+        def span(self) -> tuple[int, int] | None: ...
 
     class Recursive:
         __match_args__ = ("selectors", "span")
+        def __init__(
+            self, selectors: SelectorList, span: tuple[int, int] | None = None
+        ) -> None: ...
         @property
         def selectors(self) -> SelectorList: ...
         @property
-        def span(self) -> tuple[int, int]: ...
+        def span(self) -> tuple[int, int] | None: ...
 
 class Query:
+    __match_args__ = ("segments",)
+    def __init__(self, segments: list[Segment]) -> None: ...
+    def __eq__(self, other: object) -> bool: ...
+    def __hash__(self) -> int: ...
+    def equals(self, other: object, *, spans: bool = True) -> bool: ...
+    def without_spans(self) -> Query: ...
+    def normalize(self, *, spans: bool = False) -> Query: ...
+    def validate(self, *, lexer: Lexer | None = None) -> None: ...
+    def to_bytes(self) -> bytes: ...
+    @classmethod
+    def from_bytes(cls, data: bytes) -> Query: ...
     @property
     def segments(self) -> list[Segment]: ...
     def as_word(self) -> None | str: ...
+    def shorthand(self) -> str: ...
+    def is_singular(self) -> bool: ...
+    def parent(self) -> Query | None: ...
+    def starts_with(self, prefix: Query) -> bool: ...
+    def strip_prefix(self, prefix: Query) -> Query | None: ...
+    def join(self, other: Query) -> Query: ...
+    def to_singular(self) -> Query | None: ...
+    def to_pointer(self) -> str: ...
+    @staticmethod
+    def from_pointer(pointer: str, *, lexer: Lexer | None = None) -> Query: ...
+    def set(self, data: object, value: object) -> list[Query]: ...
+    def delete(self, data: object) -> list[Query]: ...
+    def upsert(self, data: object, value: object) -> list[Query]: ...
+    def __str__(self) -> str: ...
+
+class Branch:
+    @property
+    def tag(self) -> Markup.Tag: ...
+    @property
+    def nodes(self) -> list[BlockNode]: ...
+
+class BlockNode:
+    class Markup:
+        __match_args__ = ("markup",)
+        @property
+        def markup(self) -> Markup: ...
+
+    class Block:
+        __match_args__ = ("name", "branches", "end", "span")
+        @property
+        def name(self) -> str: ...
+        @property
+        def branches(self) -> list[Branch]: ...
+        @property
+        def end(self) -> Markup.Tag: ...
+        @property
+        def span(self) -> tuple[int, int] | None: ...
+
+    class Lines:
+        __match_args__ = ("wc", "name", "statements", "span")
+        @property
+        def wc(self) -> tuple[Whitespace, Whitespace]: ...
+        @property
+        def name(self) -> str: ...
+        @property
+        def statements(self) -> list[BlockNode]: ...
+        @property
+        def span(self) -> tuple[int, int] | None: ...
+
+class Variable:
+    @property
+    def path(self) -> Query: ...
+    @property
+    def span(self) -> tuple[int, int] | None: ...
+
+class TemplateAnalysis:
+    @property
+    def variables(self) -> list[Variable]: ...
+    @property
+    def globals(self) -> list[Variable]: ...
+    @property
+    def locals(self) -> list[Variable]: ...
+    @property
+    def filters(self) -> list[tuple[str, tuple[int, int] | None]]: ...
+    @property
+    def tags(self) -> list[tuple[str, tuple[int, int] | None]]: ...
+    def group_variables(
+        self, *, globals: bool = False
+    ) -> list[tuple[Query, list[tuple[int, int] | None]]]: ...
+
+class Dependency:
+    @property
+    def tag(self) -> str: ...
+    @property
+    def name(self) -> str | None: ...
+    @property
+    def span(self) -> tuple[int, int] | None: ...
+
+class DependencyGraph:
+    def __init__(self, *, extension: str | None = None) -> None: ...
+    @staticmethod
+    def from_directory(
+        path: str, *, extension: str | None = None, lexer: Lexer | None = None
+    ) -> DependencyGraph: ...
+    def add_template(self, name: str, tokens: list[Markup]) -> None: ...
+    def remove_template(self, name: str) -> bool: ...
+    def templates(self) -> list[str]: ...
+    def dependencies(self, name: str) -> list[Dependency]: ...
+    def errors(self) -> list[tuple[str, str]]: ...
+    def dynamic(self) -> list[tuple[str, Dependency]]: ...
+    def missing(self) -> list[tuple[str, Dependency]]: ...
+    def cycles(self) -> list[list[str]]: ...
+    def affected(self, name: str) -> list[str]: ...
+
+class TemplateSource:
+    @property
+    def name(self) -> str: ...
+    @property
+    def filename(self) -> str: ...
+    @property
+    def source(self) -> str: ...
+    def uptodate(self) -> bool: ...
+
+class Loader:
+    def get_source(self, name: str) -> TemplateSource: ...
+    def load(self, name: str) -> tuple[TemplateSource, list[Markup]]: ...
+
+class FileSystemLoader(Loader):
+    def __init__(
+        self,
+        search_path: str | PathLike[str] | list[str | PathLike[str]],
+        *,
+        default_extension: str | None = None,
+    ) -> None: ...
+
+class MemoryLoader(Loader):
+    def __init__(self, templates: Mapping[str, str]) -> None: ...
 
-def tokenize(source: str) -> list[Markup]: ...
+class ChoiceLoader(Loader):
+    def __init__(self, loaders: list[Loader]) -> None: ...
+
+class CacheStats:
+    @property
+    def hits(self) -> int: ...
+    @property
+    def misses(self) -> int: ...
+    @property
+    def evictions(self) -> int: ...
+    @property
+    def entries(self) -> int: ...
+    @property
+    def bytes(self) -> int: ...
+
+class TemplateCache:
+    def __init__(self, capacity: int = 300, *, max_bytes: int | None = None) -> None: ...
+    def get(
+        self, name: str, source: str, *, lexer: Lexer | None = None
+    ) -> list[Markup] | None: ...
+    def tokenize(
+        self, name: str, source: str, *, lexer: Lexer | None = None
+    ) -> list[Markup]: ...
+    def invalidate(self, name: str) -> bool: ...
+    def clear(self) -> None: ...
+    def stats(self) -> CacheStats: ...
+    def __len__(self) -> int: ...
+    def __contains__(self, name: str) -> bool: ...
+
+def tokenize(source: str, *, trim: Whitespace | None = None) -> list[Markup]: ...
+def tokenize_many(
+    sources: list[str], *, trim: Whitespace | None = None
+) -> list[list[Markup] | PyLiquidError]: ...
+def tokenize_paths(
+    paths: list[str | PathLike[str]], *, trim: Whitespace | None = None
+) -> list[list[Markup] | PyLiquidError | OSError]: ...
+def to_source(
+    tokens: list[Markup], *, validate: bool = True, lexer: Lexer | None = None
+) -> str: ...
+
+Node: TypeAlias = (
+    Markup | Token | RangeArgument | Query | Segment | Selector | FilterExpression
+)
+
+class NodeIterator:
+    def __iter__(self) -> NodeIterator: ...
+    def __next__(self) -> tuple[Node, Node | None, tuple[int, int] | None]: ...
+
+def walk(nodes: Node | list[Node] | tuple[Node, ...]) -> NodeIterator: ...
+
+class QueryMatch:
+    @property
+    def value(self) -> object: ...
+    @property
+    def path(self) -> Query: ...
+
+class QueryMatchIterator:
+    def __iter__(self) -> QueryMatchIterator: ...
+    def __next__(self) -> QueryMatch: ...
+
+class StreamIterator:
+    def __iter__(self) -> StreamIterator: ...
+    def __next__(self) -> QueryMatch: ...
+
+class CompiledQuery:
+    def __init__(self, query: Query) -> None: ...
+    @property
+    def query(self) -> Query: ...
+    def findall(self, data: object) -> list[object]: ...
+    def finditer(self, data: object) -> QueryMatchIterator: ...
+    def first(self, data: object) -> QueryMatch | None: ...
+    def match(self, data: object) -> bool: ...
+    def stream(self, source: str | PathLike[str] | BinaryIO) -> StreamIterator: ...
+    def find_file(
+        self, path: str | PathLike[str], *, format: str | None = None  # noqa: A002
+    ) -> list[QueryMatch]: ...
+
+def compile(path: str) -> CompiledQuery: ...  # noqa: A001
+def query_cache_stats() -> CacheStats: ...
+def clear_query_cache() -> None: ...
 def dump(source: str) -> None: ...
 def dump_query(path: str) -> None: ...
+def parse_blocks(tokens: list[Markup]) -> list[BlockNode]: ...
+def analyze(tokens: list[Markup]) -> TemplateAnalysis: ...
 def parse_query(path: str) -> Query: ...
 def parse_jsonpath_query(path: str) -> Query: ...
 def unescape_string(s: str) -> str: ...
+def dumps(tokens: list[Markup], source: str) -> bytes: ...
+def loads(data: bytes, *, source: str | None = None) -> list[Markup]: ...
+def is_current(data: bytes, source: str) -> bool: ...
+
+class PyLiquidError(Exception):
+    span: tuple[int, int] | None
 
-class PyLiquidError(Exception): ...
 class LiquidTypeError(PyLiquidError): ...
 class LiquidSyntaxError(PyLiquidError): ...
 class LiquidNameError(PyLiquidError): ...
 class LiquidExtensionError(PyLiquidError): ...
+class TemplateNotFoundError(PyLiquidError): ...
+class LiquidSerializationError(PyLiquidError): ...
 
 TokenT: TypeAlias = (
     Markup
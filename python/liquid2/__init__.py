@@ -5,7 +5,9 @@ from _liquid2 import Markup
 from _liquid2 import Token
 from _liquid2 import Whitespace
 from .ast import Node
+from .builtin import ChoiceLoader
 from .builtin import DictLoader
+from .builtin import FileSystemLoader
 from .context import RenderContext
 from .environment import Environment
 from .template import Template
@@ -14,8 +16,10 @@ from .undefined import StrictUndefined
 from .undefined import Undefined
 
 __all__ = [
+    "ChoiceLoader",
     "DictLoader",
     "Environment",
+    "FileSystemLoader",
     "Markup",
     "Node",
     "RenderContext",
@@ -87,7 +87,9 @@ from .filters.string import truncatewords
 from .filters.string import upcase
 from .filters.string import url_decode
 from .filters.string import url_encode
+from .loaders.choice_loader import ChoiceLoader
 from .loaders.dict_loader import DictLoader
+from .loaders.file_system_loader import FileSystemLoader
 from .output import Output
 from .tags.assign_tag import AssignTag
 from .tags.capture_tag import CaptureTag
@@ -120,6 +122,7 @@ __all__ = (
     "BreakTag",
     "CaseTag",
     "CaptureTag",
+    "ChoiceLoader",
     "ContinueTag",
     "Comment",
     "Content",
@@ -131,6 +134,7 @@ __all__ = (
     "Empty",
     "EqExpression",
     "FalseLiteral",
+    "FileSystemLoader",
     "abs_",
     "at_least",
     "at_most",
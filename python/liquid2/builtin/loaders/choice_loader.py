@@ -0,0 +1,18 @@
+"""A template loader that tries a list of loaders in turn."""
+
+from __future__ import annotations
+
+from _liquid2 import ChoiceLoader as _ChoiceLoader
+
+from liquid2.loader import NativeLoader
+
+
+class ChoiceLoader(NativeLoader):
+    """A loader that returns the first template found by one of _loaders_.
+
+    Args:
+        loaders: Loaders to try, in order.
+    """
+
+    def __init__(self, loaders: list[NativeLoader]):
+        super().__init__(_ChoiceLoader([loader.loader for loader in loaders]))
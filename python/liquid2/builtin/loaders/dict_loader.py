@@ -2,40 +2,31 @@
 
 from __future__ import annotations
 
-from typing import TYPE_CHECKING
+from _liquid2 import MemoryLoader
 
-from liquid2.exceptions import TemplateNotFound
-from liquid2.loader import BaseLoader
-from liquid2.loader import TemplateSource
+from liquid2.loader import NativeLoader
 
-if TYPE_CHECKING:
-    from liquid2 import Environment
-    from liquid2.context import RenderContext
 
-
-class DictLoader(BaseLoader):
+class DictLoader(NativeLoader):
     """A loader that loads templates from a dictionary.
 
+    The dictionary is not copied. Templates added to or removed from
+    _templates_ after the loader is created are seen by the loader.
+
     Args:
         templates: A dictionary mapping template names to template source strings.
     """
 
     def __init__(self, templates: dict[str, str]):
-        super().__init__()
-        self.templates = templates
-
-    def get_source(
-        self,
-        env: Environment,  # noqa: ARG002
-        template_name: str,
-        *,
-        context: RenderContext | None = None,  # noqa: ARG002
-        **kwargs: object,  # noqa: ARG002
-    ) -> TemplateSource:
-        """Get the template source, filename and reload helper for a template."""
-        try:
-            source = self.templates[template_name]
-        except KeyError as err:
-            raise TemplateNotFound(template_name) from err
-
-        return TemplateSource(source, template_name, None)
+        self._templates = templates
+        super().__init__(MemoryLoader(templates))
+
+    @property
+    def templates(self) -> dict[str, str]:
+        """The dictionary of template source strings used by this loader."""
+        return self._templates
+
+    @templates.setter
+    def templates(self, templates: dict[str, str]) -> None:
+        self._templates = templates
+        self.loader = MemoryLoader(templates)
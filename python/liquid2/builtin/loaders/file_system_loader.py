@@ -0,0 +1,29 @@
+"""A template loader that reads templates from the file system."""
+
+from __future__ import annotations
+
+from os import PathLike
+
+from _liquid2 import FileSystemLoader as _FileSystemLoader
+
+from liquid2.loader import NativeLoader
+
+
+class FileSystemLoader(NativeLoader):
+    """A loader that reads templates from one or more directories.
+
+    Args:
+        search_path: A directory, or a list of directories searched in order.
+        default_extension: An extension to append to template names that don't
+            have one, like `".liquid"`.
+    """
+
+    def __init__(
+        self,
+        search_path: str | PathLike[str] | list[str | PathLike[str]],
+        *,
+        default_extension: str | None = None,
+    ):
+        super().__init__(
+            _FileSystemLoader(search_path, default_extension=default_extension)
+        )
@@ -12,7 +12,13 @@ from typing import Mapping
 from typing import NamedTuple
 from typing import TypeAlias
 
+from _liquid2 import TemplateNotFoundError
+
+from .exceptions import TemplateNotFound
+
 if TYPE_CHECKING:
+    from _liquid2 import Loader
+
     from .context import RenderContext
     from .environment import Environment
     from .template import Template
@@ -117,6 +123,35 @@ class BaseLoader(ABC):
         return template
 
 
+class NativeLoader(BaseLoader):
+    """A loader that delegates to a template loader implemented in Rust.
+
+    Args:
+        loader: A `FileSystemLoader`, `MemoryLoader` or `ChoiceLoader` from the
+            `_liquid2` extension module.
+    """
+
+    def __init__(self, loader: Loader):
+        super().__init__()
+        self.loader = loader
+
+    def get_source(
+        self,
+        env: Environment,  # noqa: ARG002
+        template_name: str,
+        *,
+        context: RenderContext | None = None,  # noqa: ARG002
+        **kwargs: object,  # noqa: ARG002
+    ) -> TemplateSource:
+        """Get the template source, filename and reload helper for a template."""
+        try:
+            source = self.loader.get_source(template_name)
+        except TemplateNotFoundError as err:
+            raise TemplateNotFound(template_name) from err
+
+        return TemplateSource(source.source, source.filename, source.uptodate)
+
+
 UpToDate: TypeAlias = Callable[[], bool] | Callable[[], Awaitable[bool]] | None
 
 
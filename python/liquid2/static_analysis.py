@@ -51,8 +51,13 @@ class Span:
 
     @staticmethod
     def from_token(template_name: str, token: TokenT) -> Span:
-        """Return a new span taking start and end positions from _token_."""
-        return Span(template_name, token.span[0], token.span[1])  # type: ignore
+        """Return a new span taking start and end positions from _token_.
+
+        Tokens built without a span, like `Token.Word("x")`, have a start and
+        end of `-1`.
+        """
+        start, end = token.span or (-1, -1)  # type: ignore
+        return Span(template_name, start, end)
 
     def __eq__(self, other: object) -> bool:
         return (
@@ -41,7 +41,7 @@ class TokenStream(peekable):  # type: ignore
     def _index(self, token: TokenT | None) -> int:
         if hasattr(token, "index"):
             return token.index  # type: ignore
-        if hasattr(token, "span"):
+        if getattr(token, "span", None) is not None:
             return token.span[0]  # type: ignore
         return -1
 
@@ -0,0 +1,103 @@
+"""Test static analysis of template tokens."""
+
+from _liquid2 import TemplateAnalysis
+from _liquid2 import analyze
+from _liquid2 import tokenize
+
+
+def _analyze(source: str) -> TemplateAnalysis:
+    return analyze(tokenize(source))
+
+
+def _variables(
+    variables: list,  # type: ignore
+) -> list[tuple[str, tuple[int, int]]]:
+    return [(str(v.path), v.span) for v in variables]
+
+
+def test_analyze_output() -> None:
+    """Test that we find variables and filters in output statements."""
+    analysis = _analyze(r"{{ x | default: y, allow_false: z }}")
+    assert _variables(analysis.variables) == [
+        ("$['x']", (3, 4)),
+        ("$['y']", (16, 17)),
+        ("$['z']", (32, 33)),
+    ]
+    assert _variables(analysis.globals) == _variables(analysis.variables)
+    assert analysis.locals == []
+    assert analysis.filters == [("default", (7, 14))]
+
+
+def test_analyze_nested_queries() -> None:
+    """Test that we find queries nested in bracketed selections."""
+    analysis = _analyze(r"{{ x[y.z].title }}")
+    assert _variables(analysis.globals) == [
+        ("$['x'][$['y']['z']]['title']", (3, 15)),
+        ("$['y']['z']", (5, 8)),
+    ]
+
+
+def test_analyze_assign() -> None:
+    """Test that assigned names are locals after they are assigned."""
+    analysis = _analyze(r"{% assign x = x | plus: y %}{{ x }}")
+    assert _variables(analysis.variables) == [
+        ("$['x']", (14, 15)),
+        ("$['y']", (24, 25)),
+        ("$['x']", (31, 32)),
+    ]
+    assert _variables(analysis.globals) == [
+        ("$['x']", (14, 15)),
+        ("$['y']", (24, 25)),
+    ]
+    assert _variables(analysis.locals) == [("$['x']", (10, 11))]
+    assert analysis.filters == [("plus", (18, 22))]
+    assert analysis.tags == [("assign", (0, 28))]
+
+
+def test_analyze_for_loop_scope() -> None:
+    """Test that loop variables are in scope inside the loop only."""
+    source = (
+        r"{% for x in (1..y) reversed %}{{ x }}{{ forloop.index }}"
+        r"{% else %}{{ x }}{% endfor %}"
+    )
+    analysis = _analyze(source)
+    assert _variables(analysis.globals) == [
+        ("$['y']", (16, 17)),
+        ("$['x']", (69, 70)),
+    ]
+    assert _variables(analysis.locals) == [("$['x']", (7, 8))]
+    assert analysis.tags == [("for", (0, 30))]
+
+
+def test_analyze_capture_and_liquid_tag() -> None:
+    """Test that we analyze line statements and capture blocks."""
+    source = "{% liquid capture x\necho y\nendcapture\necho x %}"
+    analysis = _analyze(source)
+    assert _variables(analysis.globals) == [("$['y']", (25, 26))]
+    assert _variables(analysis.locals) == [("$['x']", (18, 19))]
+    assert [name for name, _ in analysis.tags] == ["liquid", "capture", "echo", "echo"]
+
+
+def test_analyze_include_arguments() -> None:
+    """Test that argument names and aliases are not variables."""
+    analysis = _analyze(r"{% include 'a' with b as c, d: e %}")
+    assert _variables(analysis.globals) == [
+        ("$['b']", (20, 21)),
+        ("$['e']", (31, 32)),
+    ]
+
+
+def test_group_variables() -> None:
+    """Test that we group references to the same path, ignoring notation."""
+    source = "{{ a.b[0:] }}{{ x }}{{ a['b'][::1] }}{% assign x = 1 %}{{ $.x }}"
+    analysis = _analyze(source)
+    groups = [(str(path), spans) for path, spans in analysis.group_variables()]
+    assert groups == [
+        ("$['a']['b'][0:]", [(3, 10), (23, 34)]),
+        ("$['x']", [(16, 17), (58, 61)]),
+    ]
+    groups = analysis.group_variables(globals=True)
+    assert [(str(path), spans) for path, spans in groups] == [
+        ("$['a']['b'][0:]", [(3, 10), (23, 34)]),
+        ("$['x']", [(16, 17)]),
+    ]
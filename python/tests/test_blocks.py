@@ -0,0 +1,103 @@
+"""Test grouping markup into blocks."""
+
+import operator
+from dataclasses import dataclass
+
+import pytest
+from _liquid2 import BlockNode
+from _liquid2 import LiquidSyntaxError
+from _liquid2 import parse_blocks
+from _liquid2 import tokenize
+
+
+@dataclass
+class Case:
+    """Test case helper."""
+
+    name: str
+    source: str
+    want: str
+    span: tuple[int, int]
+
+
+def test_nested_blocks() -> None:
+    """Test that block tags and their branches are grouped."""
+    source = (
+        "a{% if x %}b{% for i in y %}{{ i }}{% else %}c{% endfor %}"
+        "{% elsif z %}d{% endif %}"
+    )
+
+    nodes = parse_blocks(tokenize(source))
+    assert len(nodes) == 3  # noqa: PLR2004
+
+    block = nodes[1]
+    assert isinstance(block, BlockNode.Block)
+    assert block.name == "if"
+    assert block.span == (1, len(source))
+    assert [str(branch.tag) for branch in block.branches] == [
+        "{% if x %}",
+        "{% elsif z %}",
+    ]
+    assert str(block.end) == "{% endif %}"
+
+    inner = block.branches[0].nodes[1]
+    assert isinstance(inner, BlockNode.Block)
+    assert inner.name == "for"
+    assert len(inner.branches) == 2  # noqa: PLR2004
+
+
+def test_line_statement_blocks() -> None:
+    """Test that line statements are grouped into blocks."""
+    source = "{% liquid for x in y\nif x\necho x\nendif\nendfor %}"
+    nodes = parse_blocks(tokenize(source))
+    lines = nodes[0]
+    assert isinstance(lines, BlockNode.Lines)
+    assert len(lines.statements) == 1
+
+    block = lines.statements[0]
+    assert isinstance(block, BlockNode.Block)
+    assert block.name == "for"
+    assert isinstance(block.branches[0].nodes[0], BlockNode.Block)
+
+
+INVALID_CASES = [
+    Case(
+        name="mismatched end tag",
+        source="{% if x %}{% endfor %}",
+        want="unexpected tag 'endfor', expected 'endif'",
+        span=(10, 22),
+    ),
+    Case(
+        name="unclosed block",
+        source="{% if x %}{% for y in z %}{% endfor %}",
+        want="tag 'if' was never closed, expected 'endif'",
+        span=(0, 10),
+    ),
+    Case(
+        name="end tag without block",
+        source="a{% endif %}",
+        want="unexpected tag 'endif'",
+        span=(1, 12),
+    ),
+    Case(
+        name="branch from another block",
+        source="{% for x in y %}{% when 1 %}{% endfor %}",
+        want="unexpected tag 'when', expected 'endfor'",
+        span=(16, 28),
+    ),
+    Case(
+        name="unclosed block in liquid tag",
+        source="{% if x %}{% liquid if a\necho b %}{% endif %}",
+        want="tag 'if' was never closed, expected 'endif'",
+        span=(20, 24),
+    ),
+]
+
+
+@pytest.mark.parametrize("case", INVALID_CASES, ids=operator.attrgetter("name"))
+def test_unbalanced_blocks(case: Case) -> None:
+    """Test that structural mistakes are reported with a span."""
+    with pytest.raises(LiquidSyntaxError, match=case.want) as err:
+        parse_blocks(tokenize(case.source))
+
+    assert err.value.span == case.span
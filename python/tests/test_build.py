@@ -0,0 +1,160 @@
+"""Test programmatic construction of syntax tree nodes and source generation."""
+
+import pytest
+from _liquid2 import ComparisonOperator
+from _liquid2 import FilterExpression
+from _liquid2 import LiquidNameError
+from _liquid2 import LiquidSyntaxError
+from _liquid2 import LiquidTypeError
+from _liquid2 import Markup
+from _liquid2 import Query
+from _liquid2 import RangeArgument
+from _liquid2 import Segment
+from _liquid2 import Selector
+from _liquid2 import Token
+from _liquid2 import Whitespace
+from _liquid2 import parse_query
+from _liquid2 import to_source
+from _liquid2 import tokenize
+from liquid2.tokens import TokenStream
+
+WC = (Whitespace.Default, Whitespace.Default)
+
+
+def _query(*names: str) -> Query:
+    return Query([Segment.Child([Selector.Name(name)]) for name in names])
+
+
+def _without_spans(tokens: list[Markup]) -> list[Markup]:
+    return [t.without_spans() for t in tokens]
+
+
+def test_spans_are_optional() -> None:
+    """Test that we can construct nodes without spans."""
+    assert Token.Word("x").span is None
+    assert Token.Word("x", (1, 2)).span == (1, 2)
+    assert Markup.Content("hello").trimmed_span is None
+    assert Selector.Slice(stop=2) == Selector.Slice(None, 2, None)
+
+
+def test_constructed_nodes_equal_parsed_nodes() -> None:
+    """Test that constructed nodes equal lexed nodes, ignoring spans."""
+    output = Markup.Output(WC, [Token.Word("x"), Token.Pipe(), Token.Word("upcase")])
+    assert output.equals(tokenize("{{ x | upcase }}")[0], spans=False)
+    assert _query("a", "b").equals(parse_query("a.b"), spans=False)
+
+
+def test_to_source() -> None:
+    """Test that we can generate template source from constructed markup."""
+    tokens = [
+        Markup.Content("Hello, "),
+        Markup.Output(
+            WC,
+            [
+                Token.Query(_query("user", "first name")),
+                Token.Pipe(),
+                Token.Word("default"),
+                Token.Colon(),
+                Token.StringLiteral("it's"),
+            ],
+        ),
+        Markup.Tag(
+            WC,
+            "for",
+            [
+                Token.Word("x"),
+                Token.In(),
+                Token.RangeLiteral(
+                    RangeArgument.IntegerLiteral(1),
+                    RangeArgument.Query(_query("y", "size")),
+                ),
+            ],
+        ),
+        Markup.Tag(WC, "endfor", None),
+        Markup.Output(WC, [Token.FloatLiteral(1500.0)]),
+    ]
+
+    source = to_source(tokens)
+    assert source == (
+        "Hello, {{ user['first name'] | default : 'it\\'s' }}"
+        "{% for x in (1..y.size) %}{% endfor %}{{ 1500.0 }}"
+    )
+    assert _without_spans(tokenize(source)[:-1]) == _without_spans(tokens)
+
+
+def test_filter_expressions() -> None:
+    """Test that constructed filter expressions are written as valid source."""
+    expression = FilterExpression.Not(
+        FilterExpression.Comparison(
+            FilterExpression.Function(
+                "length", [FilterExpression.RelativeQuery(_query("b"))]
+            ),
+            ComparisonOperator.Gt,
+            FilterExpression.Int(1),
+        )
+    )
+    query = Query([Segment.Child([Selector.Filter(expression)])])
+    query.validate()
+    assert str(query) == "$[?!(length(@['b']) > 1)]"
+
+
+def test_validate_function_arity() -> None:
+    """Test that we check function calls against their signatures."""
+    with pytest.raises(LiquidTypeError, match="takes 1 argument"):
+        FilterExpression.Function("length", [])
+
+
+def test_custom_function() -> None:
+    """Test that calls to non-standard functions are checked by validate."""
+    expression = FilterExpression.Function("nosuchthing", [], (1, 2))
+    assert expression.span == (1, 2)  # noqa: PLR2004
+    with pytest.raises(LiquidNameError, match="unknown function"):
+        expression.validate()
+
+
+def test_empty_content() -> None:
+    """Test that empty content is written as nothing."""
+    assert to_source([Markup.Content(""), Markup.Content("a")]) == "a"
+
+
+def test_validate_markup() -> None:
+    """Test that we reject markup that can't be written as source text."""
+    with pytest.raises(LiquidSyntaxError):
+        Markup.Content("a {{ b").validate()
+
+    with pytest.raises(LiquidSyntaxError):
+        Markup.Tag(WC, "If", None).validate()
+
+    with pytest.raises(LiquidSyntaxError, match="does not read back"):
+        Markup.Output(WC, [Token.Word("if")]).validate()
+
+    with pytest.raises(LiquidSyntaxError):
+        to_source([Markup.Output(WC, [Token.Word("if")])])
+
+    assert to_source([Markup.Content("{{")], validate=False) == "{{"
+
+
+def test_validate_joined_markup() -> None:
+    """Test that we reject markup that is only valid on its own."""
+    with pytest.raises(LiquidSyntaxError, match="does not read back"):
+        to_source([Markup.Content("a {"), Markup.Output(WC, [Token.Word("x")])])
+
+    with pytest.raises(LiquidSyntaxError, match="does not read back"):
+        to_source([Markup.Content("a{"), Markup.Comment(WC, "#", " c ")])
+
+    with pytest.raises(LiquidSyntaxError, match="does not read back"):
+        to_source([Markup.Content("{"), Markup.Content("{ x }}")])
+
+    assert to_source([Markup.Content("a"), Markup.Content("b")]) == "ab"
+
+
+def test_validate_index() -> None:
+    """Test that we reject out of range indices."""
+    with pytest.raises(LiquidSyntaxError, match="out of range"):
+        Selector.Index(2**60).validate()
+
+
+def test_token_stream_without_spans() -> None:
+    """Test that token streams can describe tokens without spans."""
+    stream = TokenStream([Token.Word("x"), Token.Word("y", (3, 4))])
+    assert str(stream) == "current: 'x' at -1, next: 'y' at 3"
@@ -0,0 +1,103 @@
+"""Test the tokenized template cache."""
+
+from _liquid2 import Lexer
+from _liquid2 import TemplateCache
+
+
+def test_hits_and_misses() -> None:
+    """Test that we count cache hits and misses."""
+    cache = TemplateCache()
+    assert cache.get("a", "Hello, {{ you }}!") is None
+
+    tokens = cache.tokenize("a", "Hello, {{ you }}!")
+    assert [str(t) for t in cache.tokenize("a", "Hello, {{ you }}!")] == [
+        str(t) for t in tokens
+    ]
+    assert cache.get("a", "Hello, {{ you }}!") is not None
+
+    stats = cache.stats()
+    assert stats.hits == 2  # noqa: PLR2004
+    assert stats.misses == 2  # noqa: PLR2004
+    assert stats.entries == 1
+    assert stats.bytes == len("Hello, {{ you }}!")
+
+
+def test_changed_source_is_a_miss() -> None:
+    """Test that a template with new source text replaces the old entry."""
+    cache = TemplateCache()
+    cache.tokenize("a", "a")
+    assert cache.get("a", "b") is None
+
+    cache.tokenize("a", "bb")
+    assert len(cache) == 1
+    assert cache.stats().bytes == 2  # noqa: PLR2004
+    assert cache.get("a", "bb") is not None
+
+
+def test_capacity() -> None:
+    """Test that the least recently used template is evicted."""
+    cache = TemplateCache(2)
+    cache.tokenize("a", "a")
+    cache.tokenize("b", "b")
+    cache.get("a", "a")
+    cache.tokenize("c", "c")
+
+    assert "a" in cache
+    assert "b" not in cache
+    assert "c" in cache
+    assert cache.stats().evictions == 1
+
+
+def test_max_bytes() -> None:
+    """Test that templates are evicted to stay within the byte limit."""
+    cache = TemplateCache(10, max_bytes=5)
+    cache.tokenize("a", "aa")
+    cache.tokenize("b", "bb")
+    cache.tokenize("c", "cc")
+    assert "a" not in cache
+    assert cache.stats().bytes == 4  # noqa: PLR2004
+
+    cache.tokenize("d", "dddddd")
+    assert "d" not in cache
+    assert len(cache) == 2  # noqa: PLR2004
+
+
+def test_invalidate() -> None:
+    """Test that we can remove templates from the cache."""
+    cache = TemplateCache()
+    cache.tokenize("a", "a")
+    cache.tokenize("b", "b")
+    assert cache.invalidate("a")
+    assert not cache.invalidate("a")
+    assert "a" not in cache
+
+    cache.clear()
+    assert len(cache) == 0
+    assert cache.stats().bytes == 0
+
+
+def test_lexer_options_are_part_of_the_key() -> None:
+    """Test that tokens from a differently configured lexer are a miss."""
+    cache = TemplateCache()
+    source = "{{ a[?length(@) > 1] }}"
+    cache.tokenize("a", source)
+    assert cache.get("a", source) is not None
+    assert cache.get("a", source, lexer=Lexer(max_index=10)) is None
+    assert cache.get("a", source, lexer=Lexer(standard_functions=False)) is None
+    assert cache.get("a", source, lexer=Lexer()) is not None
+
+
+def test_hits_share_tokens() -> None:
+    """Test that cache hits return new lists of the same markup objects."""
+    cache = TemplateCache()
+    tokens = cache.tokenize("a", "Hello, {{ you }}!")
+    assert isinstance(tokens, list)
+
+    hit = cache.get("a", "Hello, {{ you }}!")
+    assert hit is not None
+    assert hit is not tokens
+    assert all(a is b for a, b in zip(hit, tokens, strict=True))
+
+    # Changing a returned list doesn't change the cache.
+    tokens.clear()
+    assert len(cache.tokenize("a", "Hello, {{ you }}!")) == 4  # noqa: PLR2004
@@ -0,0 +1,189 @@
+"""Test evaluating compiled queries against Python data."""
+
+import datetime
+from collections.abc import Iterator
+from collections.abc import Mapping
+
+import pytest
+from _liquid2 import CompiledQuery
+from _liquid2 import LiquidSyntaxError
+from _liquid2 import LiquidTypeError
+from _liquid2 import clear_query_cache
+from _liquid2 import compile  # noqa: A004
+from _liquid2 import parse_jsonpath_query
+from _liquid2 import query_cache_stats
+
+STORE = {
+    "store": {
+        "book": [
+            {"author": "Nigel Rees", "title": "Sayings", "price": 8.95},
+            {"author": "Evelyn Waugh", "title": "Sword", "price": 12.99},
+            {"author": "Herman Melville", "title": "Moby Dick", "price": 8.99},
+            {"author": "J. R. R. Tolkien", "title": "LOTR", "price": 22.99},
+        ],
+        "bicycle": {"color": "red", "price": 399},
+    }
+}
+
+CASES = [
+    ("$.store.book[*].title", ["Sayings", "Sword", "Moby Dick", "LOTR"]),
+    ("$.store..price", [8.95, 12.99, 8.99, 22.99, 399]),
+    ("$..book[-1].title", ["LOTR"]),
+    ("$..book[::-2].title", ["LOTR", "Sword"]),
+    ("$..book[?@.price < 10].title", ["Sayings", "Moby Dick"]),
+    ("$..book[?length(@.title) == 4].title", ["LOTR"]),
+    ("$..book[?match(@.author, 'N.*')].title", ["Sayings"]),
+    ("$..book[?search(@.title, 'o')].title", ["Sword", "Moby Dick"]),
+    ("$..[?@.price == $.store.bicycle.price].color", ["red"]),
+    ("$.store[?count(@.*) == 2].color", ["red"]),
+    ("$.store.nosuchthing", []),
+]
+
+
+@pytest.mark.parametrize(("path", "want"), CASES)
+def test_findall(path: str, want: list[object]) -> None:
+    """Test that we find values in document order."""
+    assert compile(path).findall(STORE) == want
+
+
+def test_values_are_not_copied() -> None:
+    """Test that we return the matched objects themselves."""
+    (book,) = compile("$.store.book[0]").findall(STORE)
+    assert book is STORE["store"]["book"][0]
+
+
+def test_finditer() -> None:
+    """Test that matches have a value and a normalized path."""
+    matches = list(compile("$..book[1:3].price").finditer(STORE))
+    assert [m.value for m in matches] == [12.99, 8.99]
+    assert [str(m.path) for m in matches] == [
+        "$['store']['book'][1]['price']",
+        "$['store']['book'][2]['price']",
+    ]
+
+
+class _Counting(Mapping[str, object]):
+    lookups = 0
+
+    def __init__(self, data: dict[str, object]) -> None:
+        self.data = data
+
+    def __getitem__(self, key: str) -> object:
+        _Counting.lookups += 1
+        return self.data[key]
+
+    def __iter__(self) -> Iterator[str]:
+        return iter(self.data)
+
+    def __len__(self) -> int:
+        return len(self.data)
+
+
+def test_finditer_is_lazy() -> None:
+    """Test that we stop looking once we've found enough matches."""
+    data = [_Counting({"a": i}) for i in range(10)]
+    _Counting.lookups = 0
+    it = compile("$[*].a").finditer(data)
+    assert next(it).value == 0
+    assert _Counting.lookups == 1
+    assert compile("$[*].a").first(data) is not None
+    assert _Counting.lookups == 2  # noqa: PLR2004
+
+
+def test_finditer_descendants_are_lazy() -> None:
+    """Test that we don't visit a whole subtree before the first match."""
+    data = [_Counting({"a": i}) for i in range(10)]
+    _Counting.lookups = 0
+    assert next(compile("$..a").finditer(data)).value == 0
+    assert _Counting.lookups < 10  # noqa: PLR2004
+
+
+@pytest.mark.parametrize(("path", "want"), CASES)
+def test_finditer_order(path: str, want: list[object]) -> None:
+    """Test that finditer finds the same values as findall, in order."""
+    assert [m.value for m in compile(path).finditer(STORE)] == want
+
+
+def test_first_and_match() -> None:
+    """Test that we can find the first match or check for any match."""
+    match = compile("$..price").first(STORE)
+    assert match is not None
+    assert match.value == 8.95  # noqa: PLR2004
+    assert compile("$.nosuchthing").first(STORE) is None
+    assert compile("$..[?@ == null]").first({"a": None}) is not None
+    assert compile("$..bicycle").match(STORE)
+    assert not compile("$..car").match(STORE)
+
+
+def test_python_types() -> None:
+    """Test that tuples are arrays, and bools are not numbers."""
+    data = {"a": (1, 2), "b": [True, 1, 1.0, "1", None]}
+    assert compile("$.a[-1]").findall(data) == [2]
+    assert compile("$.b[?@ == 1]").findall(data) == [1, 1.0]
+    assert compile("$.b[?@ == true]").findall(data) == [True]
+    assert compile("$.b[?@ == null]").findall(data) == [None]
+    assert compile("$.*[?length(@) == 1]").findall({"x": ["a", "bc"]}) == ["a"]
+
+
+def test_bytes() -> None:
+    """Test that bytes are only equal to the same bytes."""
+    data = [{"a": b"x", "b": b"x"}, {"a": b"x", "b": b"y"}, {"a": b"x", "b": "x"}]
+    query = compile("$[?@.a == @.b]")
+    assert query.findall(data) == [data[0]]
+    assert [m.value for m in query.finditer(data)] == [data[0]]
+
+
+def test_dates_and_times() -> None:
+    """Test that Python dates and times compare like RFC 3339 text."""
+    day = datetime.date(2024, 1, 31)
+    noon = datetime.datetime(2024, 1, 31, 12, tzinfo=datetime.timezone.utc)
+    opens = datetime.time(7, 30)
+    assert compile('$[?@ == "2024-01-31"]').findall([day]) == [day]
+    assert compile('$[?@ > "2024-01-31T11:00:00+00:00"]').findall([noon]) == [noon]
+    assert compile('$[?@ < "08:00:00"]').findall([opens, day]) == [opens]
+    assert compile("$[?@.a < @.b]").findall([{"a": day, "b": noon}]) == []
+
+def test_cyclic_data() -> None:
+    """Test that descending into or comparing cyclic data is an error."""
+    data: list[object] = [1]
+    data.append(data)
+    message = r"the value at `\$\[1\]` contains itself"
+    with pytest.raises(LiquidTypeError, match=message):
+        compile("$..*").findall(data)
+    with pytest.raises(LiquidTypeError, match="contains itself"):
+        list(compile("$..*").finditer(data))
+    with pytest.raises(LiquidTypeError, match="can't compare cyclic data"):
+        compile("$[?@ == $]").findall(data)
+    with pytest.raises(LiquidTypeError, match="contains itself"):
+        parse_jsonpath_query("$..x").delete(data)
+
+    shared = {"a": 1}
+    assert compile("$..a").findall([shared, {"b": shared}]) == [1, 1]
+    assert compile("$[?@ == $[0]]").findall([shared, shared]) == [shared, shared]
+
+def test_compiled_query_from_query() -> None:
+    """Test that we can compile an existing query."""
+    query = parse_jsonpath_query("$.a")
+    assert CompiledQuery(query).findall({"a": 1}) == [1]
+    assert CompiledQuery(query).query == query
+
+
+def test_cache() -> None:
+    """Test that parsed queries are cached by path."""
+    clear_query_cache()
+    before = query_cache_stats()
+    compile("$.some.path")
+    compile("$.some.path")
+    after = query_cache_stats()
+    assert after.hits - before.hits == 1
+    assert after.misses - before.misses == 1
+    assert after.entries == 1
+
+
+def test_syntax_errors_are_not_cached() -> None:
+    """Test that invalid paths raise every time."""
+    clear_query_cache()
+    for _ in range(2):
+        with pytest.raises(LiquidSyntaxError):
+            compile("$.a[")
+    assert query_cache_stats().entries == 0
@@ -0,0 +1,134 @@
+"""Test equality, hashing, repr and pickling of syntax tree nodes."""
+
+import copy
+import pickle
+
+from _liquid2 import ComparisonOperator
+from _liquid2 import Markup
+from _liquid2 import Query
+from _liquid2 import Segment
+from _liquid2 import Selector
+from _liquid2 import Token
+from _liquid2 import Whitespace
+from _liquid2 import parse_jsonpath_query
+from _liquid2 import parse_query
+from _liquid2 import tokenize
+
+SOURCE = (
+    "{{ x | upcase }}{% for a in (1..b.c) %}{% endfor %}"
+    "{{ d[?@.e > -0.0 && length(@.f) == 1] }}"
+)
+
+
+def test_structural_equality() -> None:
+    """Test that nodes with the same variant and fields are equal."""
+    assert tokenize(SOURCE) == tokenize(SOURCE)
+    assert parse_query("a.b[0]") == parse_query("a.b[0]")
+    assert parse_query("a.b[0]") != parse_query("a.b[1]")
+    assert tokenize("{{ a }}")[0] != tokenize("{{ b }}")[0]
+
+
+def test_equality_includes_spans() -> None:
+    """Test that nodes at different positions are not equal by default."""
+    first = tokenize("{{ x }}")[0]
+    second = tokenize(" {{ x }}")[1]
+    assert first != second
+    assert not first.equals(second)
+    assert first.equals(second, spans=False)
+    assert first.without_spans() == second.without_spans()
+
+
+def test_hash() -> None:
+    """Test that equal nodes have equal hashes."""
+    tokens = tokenize(SOURCE)
+    assert hash(tokens[0]) == hash(tokenize(SOURCE)[0])
+    queries = {parse_query("a.b"), parse_query("a.b"), parse_query("a.c")}
+    assert len(queries) == 2  # noqa: PLR2004
+
+    first = tokenize(SOURCE)[3]
+    second = tokenize(" " + SOURCE.replace("-0.0", "0.0"))[4]
+    assert first.equals(second, spans=False)
+    assert hash(first.without_spans()) == hash(second.without_spans())
+
+
+def test_equals_other_types() -> None:
+    """Test that nodes are not equal to other types."""
+    query = parse_query("a")
+    assert query != "a"
+    assert not query.equals("a")
+    assert not query.equals(tokenize("{{ a }}")[0])
+
+
+def test_repr() -> None:
+    """Test that repr shows variant and fields."""
+    token = tokenize("{{ x }}")[0]
+    assert repr(token) == (
+        "Markup.Output(wc=(Whitespace.Default, Whitespace.Default), "
+        "expression=[Token.Word(value='x', span=(3, 4))], span=(0, 7))"
+    )
+    assert repr(parse_query("a")) == (
+        "Query(segments=[Segment.Child(selectors="
+        "[Selector.Name(name='a', span=(0, 1))], span=(0, 1))])"
+    )
+
+
+def test_pickle() -> None:
+    """Test that nodes survive a round trip through pickle."""
+    tokens = tokenize(SOURCE)
+    assert pickle.loads(pickle.dumps(tokens)) == tokens  # noqa: S301
+    query = parse_query("a[?@.b == 'c'].d")
+    assert pickle.loads(pickle.dumps(query)) == query  # noqa: S301
+    assert pickle.loads(pickle.dumps(query.segments)) == query.segments  # noqa: S301
+
+    for ws in (Whitespace.Plus, Whitespace.Minus, Whitespace.Default):
+        assert pickle.loads(pickle.dumps(ws)) == ws  # noqa: S301
+
+    op = ComparisonOperator.Gt
+    assert pickle.loads(pickle.dumps(op)) == op  # noqa: S301
+
+
+def test_copy() -> None:
+    """Test that nodes can be copied."""
+    tokens = tokenize(SOURCE)
+    assert copy.deepcopy(tokens) == tokens
+    assert copy.copy(tokens[1]) == tokens[1]
+
+
+def test_match_args() -> None:
+    """Test that nodes can be destructured with match statements."""
+    match tokenize("{{ x }}")[0]:
+        case Markup.Output(_, [Token.Word(value)], _):
+            assert value == "x"
+        case _:
+            raise AssertionError("expected output markup")
+
+    match parse_query("a"):
+        case Query([Segment.Child([Selector.Name(name)])]):
+            assert name == "a"
+        case _:
+            raise AssertionError("expected a single name selector")
+
+
+def test_equivalent_forms() -> None:
+    """Test that equivalent queries compare equal by structure."""
+    assert parse_query("a.b").equals(parse_jsonpath_query("$['a']['b']"), spans=False)
+    assert parse_query("a[0:]").equals(parse_query("a[::1]"), spans=False)
+    assert parse_query("a[:2:1]").equals(parse_query("a[0:2]"), spans=False)
+    assert not parse_query("a[0::-1]").equals(parse_query("a[::-1]"), spans=False)
+
+    left = parse_query("a[?(@.b || @.c) && (@.d)]")
+    right = parse_query("a[?(@.b || @.c) && @.d]")
+    assert left.equals(right, spans=False)
+
+    assert hash(parse_query("a[0:]").normalize()) == hash(
+        parse_query("a[::1]").normalize()
+    )
+
+
+def test_normalize() -> None:
+    """Test that we can rewrite nodes to a canonical form."""
+    query = parse_query("a[0::1]")
+    assert str(query.normalize()) == "$['a'][:]"
+    assert query.normalize().segments[0].span is None
+    assert query.normalize(spans=True).segments[1].span == (1, 7)
+    assert parse_jsonpath_query("$.a").normalize() == parse_query("a").normalize()
@@ -0,0 +1,194 @@
+"""Test the template dependency graph."""
+
+import os
+import tempfile
+from pathlib import Path
+
+import pytest
+from _liquid2 import DependencyGraph
+from _liquid2 import Lexer
+from _liquid2 import Markup
+from _liquid2 import Token
+from _liquid2 import Whitespace
+from _liquid2 import tokenize
+
+
+def _graph(templates: dict[str, str]) -> DependencyGraph:
+    graph = DependencyGraph(extension=".liquid")
+    for name, source in templates.items():
+        graph.add_template(name, tokenize(source))
+    return graph
+
+
+def test_static_dependencies() -> None:
+    """Test that we find string literal template names."""
+    graph = _graph(
+        {
+            "page.liquid": (
+                "{% extends 'base' %}{% include 'header' %}"
+                "{% liquid render 'footer' %}"
+            ),
+        }
+    )
+    deps = graph.dependencies("page")
+    assert [(d.tag, d.name, d.span) for d in deps] == [
+        ("extends", "base", (12, 16)),
+        ("include", "header", (32, 38)),
+        ("render", "footer", (60, 66)),
+    ]
+
+
+def test_dynamic_dependencies() -> None:
+    """Test that we flag template names that are not string literals."""
+    graph = _graph({"a.liquid": "{% include x %}{% include 'b' %}{% render %}"})
+    assert [(n, d.tag, d.name, d.span) for n, d in graph.dynamic()] == [
+        ("a.liquid", "include", None, (11, 12)),
+        ("a.liquid", "render", None, (32, 44)),
+    ]
+    assert [(n, d.name) for n, d in graph.missing()] == [("a.liquid", "b")]
+
+
+def test_cycles() -> None:
+    """Test that we detect templates that depend on each other."""
+    graph = _graph(
+        {
+            "a.liquid": "{% include 'b' %}",
+            "b.liquid": "{% include 'c' %}",
+            "c.liquid": "{% include 'a' %}",
+            "d.liquid": "{% include 'd' %}",
+            "e.liquid": "{% include 'a' %}",
+        }
+    )
+    assert graph.cycles() == [
+        ["a.liquid", "b.liquid", "c.liquid"],
+        ["d.liquid"],
+    ]
+
+
+
+def test_long_cycle() -> None:
+    """Test that a long chain of templates doesn't overflow the stack."""
+    count = 100_000
+    wc = (Whitespace.Default, Whitespace.Default)
+    graph = DependencyGraph()
+    for i in range(count):
+        name = Token.StringLiteral(f"t{(i + 1) % count}")
+        graph.add_template(f"t{i}", [Markup.Tag(wc, "include", [name])])
+    cycles = graph.cycles()
+    assert len(cycles) == 1
+    assert len(cycles[0]) == count
+
+def test_affected() -> None:
+    """Test that we find templates that load a partial indirectly."""
+    graph = _graph(
+        {
+            "index.liquid": "{% extends 'layout' %}",
+            "about.liquid": "{% extends 'layout' %}",
+            "layout.liquid": "{% render 'nav' %}",
+            "nav.liquid": "",
+            "other.liquid": "{% include 'footer' %}",
+        }
+    )
+    assert graph.affected("nav") == [
+        "about.liquid",
+        "index.liquid",
+        "layout.liquid",
+    ]
+    assert graph.affected("index") == []
+
+
+def test_from_directory() -> None:
+    """Test that we can build a graph from a directory of templates."""
+    with tempfile.TemporaryDirectory() as tmp:
+        root = Path(tmp)
+        (root / "partials").mkdir()
+        (root / "index.liquid").write_text("{% include 'partials/nav' %}")
+        (root / "partials" / "nav.liquid").write_text("nav")
+        (root / "notes.txt").write_text("{% include 'x' %}")
+
+        graph = DependencyGraph.from_directory(tmp, extension=".liquid")
+        assert graph.templates() == ["index.liquid", "partials/nav.liquid"]
+        assert graph.affected("partials/nav") == ["index.liquid"]
+
+
+def test_from_directory_matches_whole_extensions() -> None:
+    """Test that the extension must follow a dot in the file name."""
+    with tempfile.TemporaryDirectory() as tmp:
+        root = Path(tmp)
+        (root / "index.html").write_text("")
+        (root / "foohtml").write_text("")
+
+        graph = DependencyGraph.from_directory(tmp, extension=".html")
+        assert graph.templates() == ["index.html"]
+
+
+def test_extension_without_dot() -> None:
+    """Test that an extension without a leading dot is appended with one."""
+    graph = DependencyGraph(extension="liquid")
+    graph.add_template("index.liquid", tokenize("{% include 'part' %}"))
+    graph.add_template("part.liquid", tokenize("part"))
+    assert graph.missing() == []
+    assert graph.affected("part") == ["index.liquid"]
+
+    with tempfile.TemporaryDirectory() as tmp:
+        (Path(tmp) / "index.liquid").write_text("")
+        (Path(tmp) / "foo.liquidx").write_text("")
+        graph = DependencyGraph.from_directory(tmp, extension="liquid")
+        assert graph.templates() == ["index.liquid"]
+
+
+def test_from_directory_reports_bad_files() -> None:
+    """Test that files we can't read or tokenize don't stop the walk."""
+    with tempfile.TemporaryDirectory() as tmp:
+        root = Path(tmp)
+        (root / "index.liquid").write_text("{% include 'nav' %}")
+        (root / "broken.liquid").write_text("{{ a")
+        (root / "image.liquid").write_bytes(b"\xff\xfe")
+
+        graph = DependencyGraph.from_directory(tmp, extension=".liquid")
+        assert graph.templates() == ["index.liquid"]
+        assert [name for name, _ in graph.errors()] == [
+            "broken.liquid",
+            "image.liquid",
+        ]
+
+
+
+def test_from_directory_with_lexer() -> None:
+    """Test that we tokenize files with the given lexer."""
+    with tempfile.TemporaryDirectory() as tmp:
+        (Path(tmp) / "index.liquid").write_text("{% include 'nav' %}{{ 1000 }}")
+
+        graph = DependencyGraph.from_directory(tmp, lexer=Lexer(max_int=100))
+        assert graph.templates() == []
+        assert [name for name, _ in graph.errors()] == ["index.liquid"]
+
+        graph = DependencyGraph.from_directory(tmp)
+        assert graph.templates() == ["index.liquid"]
+
+def test_from_directory_skips_hidden_files() -> None:
+    """Test that we don't read hidden files and directories."""
+    with tempfile.TemporaryDirectory() as tmp:
+        root = Path(tmp)
+        (root / ".git").mkdir()
+        (root / ".git" / "index").write_bytes(b"\xff")
+        (root / ".env").write_text("")
+        (root / "index").write_text("")
+
+        graph = DependencyGraph.from_directory(tmp)
+        assert graph.templates() == ["index"]
+        assert graph.errors() == []
+
+
+@pytest.mark.skipif(not hasattr(os, "symlink"), reason="no symbolic links")
+def test_from_directory_does_not_follow_directory_links() -> None:
+    """Test that a symbolic link loop doesn't recurse forever."""
+    with tempfile.TemporaryDirectory() as tmp:
+        root = Path(tmp)
+        (root / "partials").mkdir()
+        (root / "partials" / "nav.liquid").write_text("nav")
+        (root / "partials" / "loop").symlink_to(root, target_is_directory=True)
+        (root / "index.liquid").symlink_to(root / "partials" / "nav.liquid")
+
+        graph = DependencyGraph.from_directory(tmp, extension=".liquid")
+        assert graph.templates() == ["index.liquid", "partials/nav.liquid"]
@@ -0,0 +1,244 @@
+"""Test evaluating compiled queries against YAML, TOML, CBOR and MessagePack."""
+
+import datetime
+import os
+import struct
+import tempfile
+
+import pytest
+from _liquid2 import LiquidSyntaxError
+from _liquid2 import LiquidTypeError
+from _liquid2 import compile  # noqa: A004
+
+YAML = """\
+site:
+  title: "Blog"
+  posts: 2
+  rating: 4.0
+  published: 2024-01-31
+  updated: 2024-02-01 09:30:00.5 +1
+  quoted: "2024-01-31"
+  tags: &tags [a, b]
+  more: *tags
+  1: not a name
+events:
+  - {name: launch, start: 2024-03-01, end: 2024-03-02}
+  - {name: party, start: 2024-05-01, end: 2024-04-01}
+"""
+
+TOML = """\
+title = "Blog"
+posts = 2
+rating = 4.0
+published = 2024-01-31
+updated = 2024-02-01T09:30:00Z
+opens = 07:30:00
+
+[owner]
+name = "Sue"
+"""
+
+# {"name": "Sue", "score": 1.5, "n": 2, "when": <timestamp>, "blob": b"\x00"}
+MESSAGEPACK = (
+    b"\x85"
+    b"\xa4name\xa3Sue"
+    b"\xa5score\xcb" + struct.pack(">d", 1.5) + b"\xa1n\x02"
+    b"\xa4when\xd6\xff" + struct.pack(">I", 86400 * 365) + b"\xa4blob\xc4\x01\x00"
+)
+
+# {"a": 1, "b": 0("2024-01-31T12:30:00Z"), "c": 1(0), "d": 100(1)}
+CBOR = (
+    b"\xa4"
+    b"\x61a\x01"
+    b"\x61b\xc0\x742024-01-31T12:30:00Z"
+    b"\x61c\xc1\x00"
+    b"\x61d\xd8\x64\x01"
+)
+
+
+def _find(
+    path: str, filename: str, data: str | bytes, **kwargs: str
+) -> list[tuple[str, object]]:
+    with tempfile.TemporaryDirectory() as tmp:
+        file_path = os.path.join(tmp, filename)
+        with open(file_path, "wb") as fd:
+            fd.write(data.encode() if isinstance(data, str) else data)
+        matches = compile(path).find_file(file_path, **kwargs)
+    return [(str(m.path), m.value) for m in matches]
+
+
+def test_yaml_member_order() -> None:
+    """Test that YAML mappings keep their order."""
+    assert [path for path, _ in _find("$.site.*", "data.yaml", YAML)] == [
+        "$['site']['title']",
+        "$['site']['posts']",
+        "$['site']['rating']",
+        "$['site']['published']",
+        "$['site']['updated']",
+        "$['site']['quoted']",
+        "$['site']['tags']",
+        "$['site']['more']",
+    ]
+
+
+def test_yaml_scalars() -> None:
+    """Test that YAML scalars keep their types."""
+    values = dict(_find("$.site.*", "data.yml", YAML))
+    assert values["$['site']['posts']"] == 2  # noqa: PLR2004
+    assert isinstance(values["$['site']['rating']"], float)
+    assert values["$['site']['published']"] == datetime.date(2024, 1, 31)
+    assert values["$['site']['updated']"] == datetime.datetime(
+        2024,
+        2,
+        1,
+        9,
+        30,
+        0,
+        500000,
+        tzinfo=datetime.timezone(datetime.timedelta(hours=1)),
+    )
+    assert values["$['site']['quoted']"] == "2024-01-31"
+    assert values["$['site']['more']"] == ["a", "b"]
+
+
+def test_yaml_date_filters() -> None:
+    """Test that we can compare dates in filters."""
+    assert _find("$.events[?@.start < @.end].name", "data.yaml", YAML) == [
+        ("$['events'][0]['name']", "launch")
+    ]
+
+
+def test_yaml_dates_and_strings() -> None:
+    """Test that dates compare with strings as RFC 3339 text."""
+    assert _find("$.events[?@.start > '2024-04-01'].name", "data.yaml", YAML) == [
+        ("$['events'][1]['name']", "party")
+    ]
+    matches = _find("$.site[?@ == '2024-01-31']", "data.yaml", YAML)
+    assert [path for path, _ in matches] == [
+        "$['site']['published']",
+        "$['site']['quoted']",
+    ]
+
+
+def test_yaml_alias_limit() -> None:
+    """Test that aliases can't expand to an enormous document."""
+    lines = ["a0: &a0 [x, x, x, x, x, x, x, x, x, x]"]
+    lines += [f"a{i}: &a{i} [{', '.join([f'*a{i - 1}'] * 10)}]" for i in range(1, 9)]
+    with pytest.raises(LiquidSyntaxError, match="aliases expand"):
+        _find("$.a0", "data.yaml", "\n".join(lines))
+
+
+def test_yaml_core_tags() -> None:
+    """Test that explicit core tags decide a scalar's type."""
+    data = "a: !!float 1\nb: !!int '1'\nc: !!bool 'true'\nd: !!null ''\ne: !!str 1\n"
+    assert _find("$.*", "data.yaml", data) == [
+        ("$['a']", 1.0),
+        ("$['b']", 1),
+        ("$['c']", True),
+        ("$['d']", None),
+        ("$['e']", "1"),
+    ]
+    assert isinstance(_find("$.a", "data.yaml", data)[0][1], float)
+
+    with pytest.raises(LiquidSyntaxError, match=r"`x` is not a !!int"):
+        _find("$.a", "data.yaml", "a: !!int x")
+
+
+def test_yaml_big_integers() -> None:
+    """Test that integers too big for 64 bits are an error, not floats."""
+    with pytest.raises(LiquidSyntaxError, match="signed 64 bit integer"):
+        _find("$.a", "data.yaml", "a: 1234567890123456789012345678901")
+
+
+def test_toml() -> None:
+    """Test that TOML tables keep their order and dates."""
+    assert _find("$.*", "data.toml", TOML) == [
+        ("$['title']", "Blog"),
+        ("$['posts']", 2),
+        ("$['rating']", 4.0),
+        ("$['published']", datetime.date(2024, 1, 31)),
+        (
+            "$['updated']",
+            datetime.datetime(2024, 2, 1, 9, 30, tzinfo=datetime.timezone.utc),
+        ),
+        ("$['opens']", datetime.time(7, 30)),
+        ("$['owner']", {"name": "Sue"}),
+    ]
+    assert isinstance(_find("$.rating", "data.toml", TOML)[0][1], float)
+
+
+
+def test_toml_offset_and_local_times() -> None:
+    """Test that offset date-times compare as instants and times only with times."""
+    data = "[[e]]\nat = 1979-05-27T07:32:00-08:00\nopens = 07:32:00\n"
+    assert _find("$.e[?@.at > '1979-05-27T10:00:00Z'].at", "data.toml", data)
+    assert _find("$.e[?@.at == '1979-05-27T15:32:00Z'].at", "data.toml", data)
+    assert _find("$.e[?@.opens < '08:00:00'].opens", "data.toml", data)
+    assert not _find("$.e[?@.opens < '1980'].opens", "data.toml", data)
+    assert not _find("$.e[?@.opens > '1980'].opens", "data.toml", data)
+    assert not _find("$.e[?@.opens < @.at].opens", "data.toml", data)
+
+
+def test_messagepack() -> None:
+    """Test that we read MessagePack maps, timestamps and binary data."""
+    assert _find("$.*", "data.msgpack", MESSAGEPACK) == [
+        ("$['name']", "Sue"),
+        ("$['score']", 1.5),
+        ("$['n']", 2),
+        (
+            "$['when']",
+            datetime.datetime(1971, 1, 1, tzinfo=datetime.timezone.utc),
+        ),
+        ("$['blob']", b"\x00"),
+    ]
+
+
+def test_cbor() -> None:
+    """Test that we read CBOR maps and date tags."""
+    assert _find("$.*", "data.cbor", CBOR) == [
+        ("$['a']", 1),
+        (
+            "$['b']",
+            datetime.datetime(2024, 1, 31, 12, 30, tzinfo=datetime.timezone.utc),
+        ),
+        (
+            "$['c']",
+            datetime.datetime(1970, 1, 1, tzinfo=datetime.timezone.utc),
+        ),
+        ("$['d']", datetime.date(1970, 1, 2)),
+    ]
+
+
+def test_json() -> None:
+    """Test that we read JSON files too."""
+    assert _find("$.a[1]", "data.json", '{"a": [1, 2.0]}') == [("$['a'][1]", 2.0)]
+
+
+def test_explicit_format() -> None:
+    """Test that a format argument overrides the file's extension."""
+    assert _find("$.a", "data.txt", "a: 1", format="yaml") == [("$['a']", 1)]
+
+
+def test_unknown_format() -> None:
+    """Test that we complain about formats we don't know."""
+    with pytest.raises(LiquidTypeError, match="can't guess the format"):
+        _find("$.a", "data.txt", "a: 1")
+
+    with pytest.raises(LiquidTypeError, match="unknown data format `ini`"):
+        _find("$.a", "data.txt", "a: 1", format="ini")
+
+
+@pytest.mark.parametrize(
+    ("filename", "data"),
+    [
+        ("data.yaml", "a: [1"),
+        ("data.toml", "a = "),
+        ("data.json", "{"),
+        ("data.cbor", b"\xa1"),
+        ("data.msgpack", b"\x81"),
+    ],
+)
+def test_invalid_data(filename: str, data: str | bytes) -> None:
+    """Test that invalid data is a syntax error."""
+    with pytest.raises(LiquidSyntaxError, match="invalid"):
+        _find("$.a", filename, data)
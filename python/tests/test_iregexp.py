@@ -0,0 +1,92 @@
+"""Test I-Regexp patterns in the `match` and `search` filter functions."""
+
+import pytest
+from _liquid2 import LiquidSyntaxError
+from _liquid2 import compile  # noqa: A004
+from _liquid2 import parse_jsonpath_query
+
+DATA = ["ab", "a.b", "a\nb", "a\rb", "^a$", "A", "é", "-", "1", "abc"]
+
+MATCH = [
+    ("a.b", ["ab", "a.b"][1:] + ["a b"][:0]),
+    ("a\\\\.b", ["a.b"]),
+    ("^a$", ["^a$"]),
+    ("[a-c]+", ["ab", "abc"]),
+    ("[^a-z]", ["A", "é", "-", "1"]),
+    ("[a-]", ["-"]),
+    ("[\\\\-1]", ["-", "1"]),
+    ("\\\\p{Lu}", ["A"]),
+    ("\\\\P{L}", ["-", "1"]),
+    ("(ab|é)c?", ["ab", "é", "abc"]),
+    ("a{1,2}b{1}c{0,}", ["ab", "abc"]),
+]
+
+
+@pytest.mark.parametrize(("pattern", "want"), MATCH)
+def test_match(pattern: str, want: list[str]) -> None:
+    """Test that `match` must match the whole string."""
+    assert compile(f'$[?match(@, "{pattern}")]').findall(DATA) == want
+
+
+def test_dot() -> None:
+    """Test that `.` matches anything but line feeds and carriage returns."""
+    assert compile('$[?match(@, "a.b")]').findall(DATA) == ["a.b"]
+    assert compile('$[?search(@, "a.b")]').findall(DATA) == ["a.b"]
+    assert compile('$[?match(@, "a\\\\nb")]').findall(DATA) == ["a\nb"]
+
+
+def test_search() -> None:
+    """Test that `search` can match anywhere in a string."""
+    assert compile("$[?search(@, 'b')]").findall(DATA) == [
+        "ab",
+        "a.b",
+        "a\nb",
+        "a\rb",
+        "abc",
+    ]
+    assert compile("$[?search(@, '$')]").findall(DATA) == ["^a$"]
+
+
+@pytest.mark.parametrize(
+    "pattern",
+    [
+        "(a",
+        "a)",
+        "[a",
+        "a**",
+        "{2}",
+        "a{3,2}",
+        "a{,2}",
+        "[b-a]",
+        "[a-b-c]",
+        "\\\\d",
+        "\\\\w",
+        "\\\\$",
+        "\\\\p{Xx}",
+        "\\\\p{L",
+        "((a{1000}){1000}){100}",
+    ],
+)
+def test_invalid_literal_patterns(pattern: str) -> None:
+    """Test that invalid patterns are syntax errors when parsing a query."""
+    query = f'$[?match(@.a, "{pattern}")]'
+    with pytest.raises(LiquidSyntaxError, match="invalid I-Regexp pattern") as err:
+        parse_jsonpath_query(query)
+    assert err.value.span == (15, 15 + len(pattern))
+
+
+def test_invalid_patterns_from_data() -> None:
+    """Test that invalid patterns from data don't match anything."""
+    data = [{"p": "a)", "v": "a)"}, {"p": "a.", "v": "ab"}]
+    assert compile("$[?match(@.v, @.p)].v").findall(data) == ["ab"]
+    assert compile("$[?search(@.v, @.p)].v").findall(data) == ["ab"]
+
+
+def test_deeply_nested_patterns_from_data() -> None:
+    """Test that deeply nested patterns from data are invalid, not a crash."""
+    data = [{"a": "x", "p": "(" * 100000 + ")" * 100000}]
+    assert compile("$[?match(@.a, @.p)]").findall(data) == []
+    assert compile("$[?search(@.a, @.p)]").findall(data) == []
+
+    with pytest.raises(LiquidSyntaxError, match="nest more than 250 deep"):
+        parse_jsonpath_query(f'$[?match(@.a, "{"(" * 300}{")" * 300}")]')
@@ -4,6 +4,11 @@ import operator
 from dataclasses import dataclass
 
 import pytest
+from _liquid2 import LiquidSyntaxError
+from _liquid2 import LiquidTypeError
+from _liquid2 import Markup
+from _liquid2 import Token
+from _liquid2 import Whitespace
 from _liquid2 import tokenize
 
 
@@ -175,6 +180,31 @@ TEST_CASES = [
         source="{% liquid break %}",
         want="{% liquid break %}",
     ),
+    Case(
+        name="liquid tag, comment",
+        source="{% liquid # some comment\necho a %}",
+        want="{% liquid # some comment\necho a %}",
+    ),
+    Case(
+        name="liquid tag, trailing comment",
+        source="{% liquid echo a # some comment\necho b %}",
+        want="{% liquid echo a\n# some comment\necho b %}",
+    ),
+    Case(
+        name="liquid tag, hash in string literal",
+        source="{% liquid echo '#' %}",
+        want="{% liquid echo '#' %}",
+    ),
+    Case(
+        name="liquid tag, line continuation",
+        source="{% liquid assign x = a \\\n  | upcase\necho x %}",
+        want="{% liquid assign x = a | upcase\necho x %}",
+    ),
+    Case(
+        name="liquid tag, range with query",
+        source="{% liquid for i in (1..a.b)\necho i\nendfor %}",
+        want="{% liquid for i in (1..a.b)\necho i\nendfor %}",
+    ),
 ]
 
 
@@ -183,3 +213,154 @@ def test_parser(case: Case) -> None:
     """Test the Rust parser."""
     # dump(case.source)
     assert "".join(str(t) for t in tokenize(case.source)) == case.want
+
+
+def test_line_statement_query_does_not_span_lines() -> None:
+    """Test that a query in a line statement stops at the end of its line."""
+    token = tokenize("{% liquid echo a.b\nfor x in c\necho x\nendfor %}")[0]
+    assert isinstance(token, Markup.Lines)
+    assert [str(s) for s in token.statements[:2]] == [
+        "{% echo a.b %}",
+        "{% for x in c %}",
+    ]
+
+    # A line can't start with a segment that continues the previous query.
+    with pytest.raises(LiquidSyntaxError):
+        tokenize("{% liquid echo a.b\n[1] %}")
+
+    # But a bracketed selector can span lines.
+    token = tokenize("{% liquid echo a[\n1] %}")[0]
+    assert isinstance(token, Markup.Lines)
+    assert str(token.statements[0]) == "{% echo a[1] %}"
+
+
+def test_line_statement_range_does_not_span_lines() -> None:
+    """Test that a range literal in a line statement is on one line."""
+    with pytest.raises(LiquidSyntaxError):
+        tokenize("{% liquid for i in (1..\n3)\necho i\nendfor %}")
+
+    with pytest.raises(LiquidSyntaxError):
+        tokenize("{% liquid for i in (1.. \\\n3)\necho i\nendfor %}")
+
+
+INTEGER_LITERALS = [
+    Case(name="exponent", source="1e2", want="100"),
+    Case(name="exponent with plus sign", source="2E+3", want="2000"),
+    Case(name="negative zero", source="-0", want="0"),
+    Case(name="negative zero with exponent", source="-0e2", want="0"),
+    Case(name="zero with large exponent", source="0e999", want="0"),
+    Case(name="zero with huge exponent", source="0e99999999999", want="0"),
+    Case(name="max safe integer", source="9007199254740991", want="9007199254740991"),
+    Case(
+        name="min safe integer",
+        source="-9007199254740991",
+        want="-9007199254740991",
+    ),
+]
+
+
+@pytest.mark.parametrize("case", INTEGER_LITERALS, ids=operator.attrgetter("name"))
+def test_integer_literals(case: Case) -> None:
+    """Test that integer literals are parsed exactly."""
+    token = tokenize(f"{{{{ {case.source} }}}}")[0].expression[0]  # type: ignore
+    assert isinstance(token, Token.IntegerLiteral)
+    assert str(token.value) == case.want
+
+
+INTEGER_OVERFLOW = [
+    "9007199254740992",
+    "-9007199254740992",
+    "123456789012345678901",
+    "1e16",
+    "1e400",
+]
+
+
+@pytest.mark.parametrize("source", INTEGER_OVERFLOW)
+def test_integer_literal_out_of_range(source: str) -> None:
+    """Test that integer literals outside the representable range are errors."""
+    with pytest.raises(LiquidSyntaxError, match="integer literal out of range"):
+        tokenize(f"{{{{ {source} }}}}")
+
+
+@dataclass
+class TrimCase:
+    """Whitespace control test case helper."""
+
+    name: str
+    source: str
+    trim: Whitespace
+    want: list[tuple[str, tuple[int, int]]]
+
+
+TRIM_CASES = [
+    TrimCase(
+        name="default plus",
+        source=" \n a {{ x }} b \n",
+        trim=Whitespace.Plus,
+        want=[(" \n a ", (0, 5)), (" b \n", (12, 16))],
+    ),
+    TrimCase(
+        name="default minus",
+        source=" \n a {{ x }} b \n",
+        trim=Whitespace.Minus,
+        want=[("a", (3, 4)), ("b", (13, 14))],
+    ),
+    TrimCase(
+        name="default smart",
+        source="\n a {{ x }} b \n\n",
+        trim=Whitespace.Smart,
+        want=[(" a ", (1, 4)), (" b ", (11, 14))],
+    ),
+    TrimCase(
+        name="explicit minus",
+        source="a \n {{- x -}} \n b",
+        trim=Whitespace.Plus,
+        want=[("a", (0, 1)), ("b", (16, 17))],
+    ),
+    TrimCase(
+        name="explicit smart",
+        source="a\n{{~ x ~}}\n b",
+        trim=Whitespace.Minus,
+        want=[("a", (0, 1)), (" b", (12, 14))],
+    ),
+    TrimCase(
+        name="explicit plus",
+        source=" a {{+ x +}} b ",
+        trim=Whitespace.Minus,
+        want=[("a ", (1, 3)), (" b", (12, 14))],
+    ),
+    TrimCase(
+        name="after raw",
+        source="{% raw %} x {% endraw -%}  y",
+        trim=Whitespace.Plus,
+        want=[("y", (27, 28))],
+    ),
+    TrimCase(
+        name="after comment",
+        source="{# x -#}  y",
+        trim=Whitespace.Plus,
+        want=[("y", (10, 11))],
+    ),
+]
+
+
+@pytest.mark.parametrize("case", TRIM_CASES, ids=operator.attrgetter("name"))
+def test_whitespace_control(case: TrimCase) -> None:
+    """Test that the lexer can apply whitespace control to content."""
+    content = [
+        (t.text, t.trimmed_span)
+        for t in tokenize(case.source, trim=case.trim)
+        if isinstance(t, Markup.Content)
+    ]
+
+    assert content == case.want
+
+    for text, (start, stop) in content:
+        assert case.source[start:stop] == text
+
+
+def test_default_whitespace_control_is_not_a_trim_mode() -> None:
+    """Test that `Whitespace.Default` can't be used as the default trim mode."""
+    with pytest.raises(LiquidTypeError):
+        tokenize("Hello", trim=Whitespace.Default)
@@ -0,0 +1,88 @@
+"""Test configured, reusable lexers."""
+
+import pytest
+from _liquid2 import ExpressionType
+from _liquid2 import FunctionSignature
+from _liquid2 import Lexer
+from _liquid2 import LiquidNameError
+from _liquid2 import LiquidSyntaxError
+from _liquid2 import TemplateCache
+from _liquid2 import Whitespace
+
+
+def test_default_lexer() -> None:
+    """Test that a lexer with default options behaves like `tokenize`."""
+    lexer = Lexer()
+    assert [str(t) for t in lexer.tokenize("a {{ b }} c")] == [
+        "a ",
+        "{{ b }}",
+        " c",
+        "",
+    ]
+    assert lexer.function_names() == ["count", "length", "match", "search", "value"]
+    assert str(lexer.parse_query("a.b")) == "$['a']['b']"
+    assert str(lexer.parse_jsonpath_query("$.a[?count(@.b) > 1]"))
+
+
+def test_trim() -> None:
+    """Test that we can apply whitespace control when tokenizing."""
+    tokens = Lexer().tokenize("a  {{ b }}  c", trim=Whitespace.Minus)
+    assert tokens[0].trimmed_span == (0, 1)
+
+
+def test_function_extensions() -> None:
+    """Test that we can register JSONPath function extensions."""
+    lexer = Lexer(
+        functions={
+            "is_even": FunctionSignature([ExpressionType.Value], ExpressionType.Logical)
+        }
+    )
+    assert "is_even" in lexer.function_names()
+    assert str(lexer.parse_query("a[?is_even(@.b)]"))
+
+    with pytest.raises(LiquidNameError):
+        Lexer().parse_query("a[?is_even(@.b)]")
+
+
+def test_without_standard_functions() -> None:
+    """Test that we can exclude standard function extensions."""
+    lexer = Lexer(standard_functions=False)
+    assert lexer.function_names() == []
+    with pytest.raises(LiquidNameError):
+        lexer.parse_query("a[?count(@.b) > 1]")
+
+
+def test_max_index() -> None:
+    """Test that we can limit the range of array indices."""
+    lexer = Lexer(max_index=100)
+    assert str(lexer.parse_query("a[100]")) == "$['a'][100]"
+    with pytest.raises(LiquidSyntaxError):
+        lexer.parse_query("a[101]")
+
+    with pytest.raises(ValueError, match="max_index"):
+        Lexer(max_index=-1)
+
+
+def test_max_int() -> None:
+    """Test that integer literals have their own limit."""
+    lexer = Lexer(max_index=10)
+    assert str(lexer.tokenize("{{ 100 }}")[0]) == "{{ 100 }}"
+
+    lexer = Lexer(max_int=100)
+    assert str(lexer.parse_query("a[101]")) == "$['a'][101]"
+    with pytest.raises(LiquidSyntaxError, match="integer literal out of range"):
+        lexer.tokenize("{{ 101 }}")
+    with pytest.raises(LiquidSyntaxError, match="integer literal out of range"):
+        lexer.parse_query("a[?@.b > 101]")
+
+    with pytest.raises(ValueError, match="max_int"):
+        Lexer(max_int=-1)
+
+
+def test_cache_with_lexer() -> None:
+    """Test that a template cache can tokenize with a configured lexer."""
+    cache = TemplateCache()
+    lexer = Lexer(standard_functions=False)
+    with pytest.raises(LiquidNameError):
+        cache.tokenize("a", "{{ a[?count(@) > 1] }}", lexer=lexer)
+    assert cache.tokenize("a", "{{ a[?count(@) > 1] }}")
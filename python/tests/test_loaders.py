@@ -0,0 +1,131 @@
+"""Test template loaders implemented in Rust."""
+
+import os
+import tempfile
+from pathlib import Path
+
+import pytest
+from _liquid2 import ChoiceLoader
+from _liquid2 import FileSystemLoader
+from _liquid2 import MemoryLoader
+from _liquid2 import TemplateNotFoundError
+from liquid2 import ChoiceLoader as EnvChoiceLoader
+from liquid2 import DictLoader
+from liquid2 import Environment
+from liquid2 import FileSystemLoader as EnvFileSystemLoader
+from liquid2.exceptions import TemplateNotFound
+
+
+def test_memory_loader() -> None:
+    """Test that we can load templates from a dictionary."""
+    loader = MemoryLoader({"a": "Hello, {{ you }}!"})
+    source, tokens = loader.load("a")
+    assert source.name == "a"
+    assert source.filename == "a"
+    assert source.source == "Hello, {{ you }}!"
+    assert source.uptodate()
+    assert len(tokens) == 4  # noqa: PLR2004
+
+    with pytest.raises(TemplateNotFoundError):
+        loader.get_source("b")
+
+
+def test_dict_loader_reads_templates_on_load() -> None:
+    """Test that changes to a dict loader's dictionary are seen."""
+    templates = {"a": "Hello, {{ you }}!"}
+    loader = DictLoader(templates)
+    env = Environment(loader=loader)
+    assert loader.templates is templates
+
+    templates["b"] = "Goodbye, {{ you }}!"
+    assert env.get_template("b").render(you="World") == "Goodbye, World!"
+
+    del templates["a"]
+    with pytest.raises(TemplateNotFound):
+        env.get_template("a")
+
+    loader.templates = {"c": "c"}
+    assert env.get_template("c").render() == "c"
+
+
+def test_file_system_loader_search_path() -> None:
+    """Test that directories are searched in order."""
+    with tempfile.TemporaryDirectory() as tmp:
+        first = Path(tmp, "first")
+        second = Path(tmp, "second")
+        first.mkdir()
+        second.mkdir()
+        (first / "a.liquid").write_text("first a")
+        (second / "a.liquid").write_text("second a")
+        (second / "b.liquid").write_text("second b")
+
+        loader = FileSystemLoader([first, second], default_extension=".liquid")
+        assert loader.get_source("a").source == "first a"
+        assert loader.get_source("b.liquid").source == "second b"
+        assert loader.get_source("b").filename == str(second / "b.liquid")
+
+        with pytest.raises(TemplateNotFoundError):
+            loader.get_source("c")
+
+
+def test_file_system_loader_path_traversal() -> None:
+    """Test that we can't load templates from outside the search path."""
+    with tempfile.TemporaryDirectory() as tmp:
+        root = Path(tmp, "templates")
+        root.mkdir()
+        Path(tmp, "secret.liquid").write_text("secret")
+
+        loader = FileSystemLoader(root)
+        for name in ["../secret.liquid", str(Path(tmp, "secret.liquid"))]:
+            with pytest.raises(TemplateNotFoundError):
+                loader.get_source(name)
+
+
+def test_uptodate() -> None:
+    """Test that modified files are not up to date."""
+    with tempfile.TemporaryDirectory() as tmp:
+        path = Path(tmp, "a.liquid")
+        path.write_text("a")
+        source = FileSystemLoader(tmp).get_source("a.liquid")
+        assert source.uptodate()
+
+        stat = path.stat()
+        os.utime(path, ns=(stat.st_atime_ns, stat.st_mtime_ns + 1_000_000_000))
+        assert not source.uptodate()
+
+        path.unlink()
+        assert not source.uptodate()
+
+
+def test_choice_loader() -> None:
+    """Test that we try each loader in turn."""
+    loader = ChoiceLoader(
+        [MemoryLoader({"a": "first a"}), MemoryLoader({"a": "x", "b": "second b"})]
+    )
+    assert loader.get_source("a").source == "first a"
+    assert loader.get_source("b").source == "second b"
+
+    with pytest.raises(TemplateNotFoundError):
+        loader.get_source("c")
+
+
+def test_environment_loaders() -> None:
+    """Test that environment loaders delegate to the Rust loaders."""
+    with tempfile.TemporaryDirectory() as tmp:
+        Path(tmp, "a.liquid").write_text("Hello, {{ you }}!")
+        loader = EnvChoiceLoader(
+            [
+                DictLoader({"b": "Goodbye, {{ you }}!"}),
+                EnvFileSystemLoader(tmp, default_extension=".liquid"),
+            ]
+        )
+        env = Environment(loader=loader)
+
+        template = env.get_template("a")
+        assert template.render(you="World") == "Hello, World!"
+        assert template.uptodate is not None
+        assert template.uptodate()
+        assert env.get_template("b").render(you="World") == "Goodbye, World!"
+
+        with pytest.raises(TemplateNotFound):
+            env.get_template("c")
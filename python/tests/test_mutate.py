@@ -0,0 +1,165 @@
+"""Test changing data at the locations matched by a query."""
+
+import pytest
+from _liquid2 import LiquidTypeError
+from _liquid2 import Query
+from _liquid2 import parse_jsonpath_query
+from _liquid2 import parse_query
+
+
+def _paths(queries: list[Query]) -> list[str]:
+    return [str(query) for query in queries]
+
+
+def test_set() -> None:
+    """Test that we replace existing values only."""
+    data = {"a": {"b": 1}, "c": [1, 2]}
+    assert _paths(parse_query("a.b").set(data, 2)) == ["$['a']['b']"]
+    assert _paths(parse_query("a.x").set(data, 2)) == []
+    assert data == {"a": {"b": 2}, "c": [1, 2]}
+
+
+def test_set_every_match() -> None:
+    """Test that non-singular queries change every matched location."""
+    data = {"c": [1, 2, 3, 4], "d": {"x": 1, "y": 5}}
+    paths = parse_jsonpath_query("$.c[1::2]").set(data, 0)
+    assert _paths(paths) == ["$['c'][1]", "$['c'][3]"]
+    paths = parse_jsonpath_query("$.d[?@ > 2]").set(data, 0)
+    assert _paths(paths) == ["$['d']['y']"]
+    assert data == {"c": [1, 0, 3, 0], "d": {"x": 1, "y": 0}}
+
+
+def test_set_nested_matches() -> None:
+    """Test that we skip locations inside values we've already replaced."""
+    data = [[1, [2]], 3]
+    assert _paths(parse_jsonpath_query("$..*").set(data, 0)) == ["$[0]", "$[1]"]
+    assert data == [0, 0]
+
+
+def test_set_root() -> None:
+    """Test that we can't replace the root of Python data."""
+    with pytest.raises(LiquidTypeError):
+        parse_jsonpath_query("$").set({}, 1)
+
+
+def test_delete() -> None:
+    """Test that we remove every match, keeping indices consistent."""
+    data = {"a": [1, 2, 3, 4], "b": {"c": 1, "d": 2}}
+    paths = parse_query("a[?@ > 1]").delete(data)
+    assert _paths(paths) == ["$['a'][1]", "$['a'][2]", "$['a'][3]"]
+    assert _paths(parse_query("b.c").delete(data)) == ["$['b']['c']"]
+    assert data == {"a": [1], "b": {"d": 2}}
+
+
+def test_delete_nested_matches() -> None:
+    """Test that values inside deleted values are not reported."""
+    data = {"a": {"b": {"c": 1}}, "d": 2}
+    assert _paths(parse_jsonpath_query("$..*").delete(data)) == ["$['a']", "$['d']"]
+    assert data == {}
+
+
+def test_delete_duplicates() -> None:
+    """Test that we remove a location matched twice once."""
+    data = [1, 2, 3]
+    assert _paths(parse_jsonpath_query("$[0, 0, -3]").delete(data)) == ["$[0]"]
+    assert data == [2, 3]
+
+
+def test_delete_root() -> None:
+    """Test that we can't delete the root value."""
+    with pytest.raises(LiquidTypeError):
+        parse_jsonpath_query("$").delete({})
+
+
+def test_upsert_creates_intermediates() -> None:
+    """Test that singular queries create missing dicts and lists."""
+    data: dict[str, object] = {}
+    paths = parse_query("x.y[2].z").upsert(data, 5)
+    assert _paths(paths) == ["$['x']['y'][2]['z']"]
+    assert data == {"x": {"y": [None, None, {"z": 5}]}}
+
+
+def test_upsert_existing() -> None:
+    """Test that negative indices resolve against existing items."""
+    data = {"a": [1, 2, 3]}
+    assert _paths(parse_query("a[-1]").upsert(data, 9)) == ["$['a'][2]"]
+    assert _paths(parse_query("a[3]").upsert(data, 4)) == ["$['a'][3]"]
+    assert data == {"a": [1, 2, 9, 4]}
+
+
+def test_upsert_every_parent() -> None:
+    """Test that a trailing name is created on every match of its parent."""
+    data = {"users": [{"name": "a"}, {"name": "b", "active": False}, 1]}
+    paths = parse_query("users[*].active").upsert(data, True)  # noqa: FBT003
+    assert _paths(paths) == ["$['users'][0]['active']", "$['users'][1]['active']"]
+    assert data == {
+        "users": [{"name": "a", "active": True}, {"name": "b", "active": True}, 1]
+    }
+
+
+def test_upsert_pads_arrays() -> None:
+    """Test that singular and other queries both pad short arrays with nulls."""
+    data: dict[str, object] = {}
+    parse_jsonpath_query("$.a[2].b").upsert(data, 1)
+    assert data == {"a": [None, None, {"b": 1}]}
+
+    items: list[list[object]] = [[], [1]]
+    paths = parse_jsonpath_query("$[*][2]").upsert(items, 0)
+    assert _paths(paths) == ["$[0][2]", "$[1][2]"]
+    assert items == [[None, None, 0], [1, None, 0]]
+
+
+
+def test_upsert_padding_limit() -> None:
+    """Test that an upsert won't pad an array with more than 1000 nulls."""
+    data: dict[str, object] = {"a": [1]}
+    with pytest.raises(LiquidTypeError, match="more than 1000 past the end"):
+        parse_jsonpath_query("$.a[1000000000]").upsert(data, 5)
+    assert data == {"a": [1]}
+
+    items: list[list[object]] = [[1] * 10, []]
+    with pytest.raises(LiquidTypeError, match="more than 1000 past the end"):
+        parse_jsonpath_query("$[*][1005]").upsert(items, 5)
+    assert items == [[1] * 10, []]
+
+    parse_jsonpath_query("$.a[1001]").upsert(data, 5)
+    assert data == {"a": [1, *[None] * 1000, 5]}
+
+def test_upsert_errors() -> None:
+    """Test that we don't replace values of the wrong type."""
+    with pytest.raises(LiquidTypeError, match="expected an object"):
+        parse_query("a.b").upsert({"a": 1}, 1)
+
+    with pytest.raises(LiquidTypeError, match="expected an array"):
+        parse_query("a[0]").upsert({"a": {}}, 1)
+
+    with pytest.raises(LiquidTypeError, match="out of range"):
+        parse_query("a[-2]").upsert({"a": [1]}, 1)
+
+
+def test_errors_use_the_query_notation() -> None:
+    """Test that errors show queries the way they were written."""
+    with pytest.raises(LiquidTypeError, match=r"can't upsert `a\[-2\]`"):
+        parse_query("a[-2]").upsert({"a": [1]}, 1)
+
+    with pytest.raises(LiquidTypeError, match=r"can't upsert `\$\.a\[-2\]`"):
+        parse_jsonpath_query("$.a[-2]").upsert({"a": [1]}, 1)
+
+
+def test_failed_upsert_leaves_data_unchanged() -> None:
+    """Test that we check the whole path before creating anything."""
+    data: dict[str, object] = {"a": {}}
+    with pytest.raises(LiquidTypeError, match="out of range"):
+        parse_query("b.c[-1]").upsert(data, 1)
+    assert data == {"a": {}}
+
+    data = {"a": [1]}
+    with pytest.raises(LiquidTypeError, match="out of range"):
+        parse_query("a[3].b[-1]").upsert(data, 1)
+    assert data == {"a": [1]}
+
+
+def test_immutable_data() -> None:
+    """Test that Python errors are reported as type errors."""
+    with pytest.raises(LiquidTypeError, match="can't change"):
+        parse_query("a[0]").set({"a": (1, 2)}, 0)
@@ -0,0 +1,133 @@
+"""Test query path operations."""
+
+import pytest
+from _liquid2 import Lexer
+from _liquid2 import LiquidSyntaxError
+from _liquid2 import LiquidTypeError
+from _liquid2 import Query
+from _liquid2 import parse_jsonpath_query
+from _liquid2 import parse_query
+
+
+def _str(query: Query | None) -> str | None:
+    return None if query is None else query.shorthand()
+
+
+def test_is_singular() -> None:
+    """Test that we can tell if a query resolves to at most one node."""
+    assert parse_query("order.items[0].sku").is_singular()
+    assert parse_jsonpath_query("$.order.items[0]").is_singular()
+    assert not parse_query("order.items[*].sku").is_singular()
+    assert not parse_query("order..sku").is_singular()
+    assert not parse_query("order.items[0, 1]").is_singular()
+
+
+def test_parent() -> None:
+    """Test that the parent of a path drops its last segment."""
+    assert _str(parse_query("order.items[0].sku").parent()) == "order.items[0]"
+    assert _str(parse_jsonpath_query("$.order.items").parent()) == "order"
+    assert _str(parse_query("order").parent()) == "$"
+    assert parse_query("order").parent().parent() is None  # type: ignore
+
+
+def test_starts_with() -> None:
+    """Test that we can compare path prefixes, ignoring notation and spans."""
+    path = parse_query("order.items[0].sku")
+    assert path.starts_with(parse_query("order.items"))
+    assert path.starts_with(parse_jsonpath_query("$['order']['items'][0]"))
+    assert path.starts_with(path)
+    assert path.starts_with(parse_query("order").parent())  # type: ignore
+    assert not path.starts_with(parse_query("order.item"))
+    assert not parse_query("order").starts_with(path)
+    assert not parse_query("a[0, 1]").starts_with(parse_query("a[0]"))
+    assert parse_query("a[*].b").starts_with(parse_query("a[*]"))
+    assert parse_query("a[1:].b").starts_with(parse_query("a[1::1]"))
+
+
+def test_strip_prefix() -> None:
+    """Test that we can remove a leading path."""
+    path = parse_query("order.items[0].sku")
+    assert _str(path.strip_prefix(parse_query("order.items"))) == "[0].sku"
+    assert _str(path.strip_prefix(path)) == "$"
+    assert path.strip_prefix(parse_query("customer")) is None
+
+
+def test_join() -> None:
+    """Test that we can join a loop variable's path onto its collection."""
+    item = parse_query("item.sku")
+    collection = parse_query("order.items[0]")
+    relative = item.strip_prefix(parse_query("item"))
+    assert relative is not None
+    joined = collection.join(relative)
+    assert _str(joined) == "order.items[0].sku"
+    assert joined.equals(parse_query("order.items[0].sku"), spans=False)
+    assert _str(parse_jsonpath_query("$.a").join(parse_jsonpath_query("$.b"))) == "a.b"
+
+
+def test_to_singular() -> None:
+    """Test that we can rewrite queries as singular queries."""
+    assert _str(parse_query("a[1:2].b").to_singular()) == "a[1].b"
+    assert _str(parse_query("a[-1:]").to_singular()) == "a[-1]"
+    assert _str(parse_query("a[-3:-2:1]").to_singular()) == "a[-3]"
+    assert _str(parse_query("a.b[0]").to_singular()) == "a.b[0]"
+    assert parse_query("a[-1:0]").to_singular() is None
+    assert parse_query("a[1:3]").to_singular() is None
+    assert parse_query("a[1:2:-1]").to_singular() is None
+    assert parse_query("a[*]").to_singular() is None
+    assert parse_query("a..b").to_singular() is None
+
+
+def test_to_pointer() -> None:
+    """Test that we can convert singular queries to JSON Pointers."""
+    assert parse_query("order.items[0].sku").to_pointer() == "/order/items/0/sku"
+    assert parse_jsonpath_query("$").to_pointer() == ""
+    assert parse_query("['a/b']['c~d']['']").to_pointer() == "/a~1b/c~0d/"
+    assert parse_query("a[1:2]").to_pointer() == "/a/1"
+
+
+def test_to_pointer_errors() -> None:
+    """Test that we can't convert non-singular queries or negative indices."""
+    with pytest.raises(LiquidTypeError, match="expected a singular query"):
+        parse_query("order.items[*].sku").to_pointer()
+
+    with pytest.raises(LiquidTypeError, match="negative index -1"):
+        parse_query("order.items[-1]").to_pointer()
+
+
+def test_from_pointer() -> None:
+    """Test that we can convert JSON Pointers to singular queries."""
+    query = Query.from_pointer("/order/items/0/sku")
+    assert query.equals(parse_query("order.items[0].sku"), spans=False)
+    assert query.segments[1].span == (6, 12)  # noqa: PLR2004
+    assert query.segments[1].selectors[0].span == (7, 12)  # noqa: PLR2004
+
+    assert Query.from_pointer("").segments == []
+    assert _str(Query.from_pointer("/a~1b/c~0d//~01")) == "['a/b']['c~d']['']['~1']"
+    assert _str(Query.from_pointer("/a/01/-")) == "a['01']['-']"
+
+
+def test_from_pointer_errors() -> None:
+    """Test that we reject invalid JSON Pointers."""
+    with pytest.raises(LiquidSyntaxError, match="leading slash"):
+        Query.from_pointer("a/b")
+
+    with pytest.raises(LiquidSyntaxError, match="escape"):
+        Query.from_pointer("/a~2")
+
+
+def test_from_pointer_index_range() -> None:
+    """Test that digits outside the lexer's index range are names."""
+    assert _str(Query.from_pointer("/a/99999999999999999999")) == (
+        "a['99999999999999999999']"
+    )
+    assert _str(Query.from_pointer("/a/100")) == "a[100]"
+
+    lexer = Lexer(max_index=99)
+    assert _str(Query.from_pointer("/a/100", lexer=lexer)) == "a['100']"
+    assert _str(Query.from_pointer("/a/99", lexer=lexer)) == "a[99]"
+
+
+def test_pointer_round_trip() -> None:
+    """Test that pointers survive a round trip through a query."""
+    for pointer in ["", "/", "/a", "/a/0/b", "/a~0~1b/10", "/ü/ /-"]:
+        assert Query.from_pointer(pointer).to_pointer() == pointer
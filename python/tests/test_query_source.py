@@ -0,0 +1,201 @@
+"""Test that queries are written as source text that reads back the same."""
+
+import random
+
+import pytest
+from _liquid2 import PyLiquidError
+from _liquid2 import Query
+from _liquid2 import Segment
+from _liquid2 import parse_jsonpath_query
+from _liquid2 import parse_query
+from _liquid2 import tokenize
+
+NAMES = [
+    "a",
+    "b_c",
+    "é",
+    "a'b",
+    'a"b',
+    "\\\\",
+    "\\b\\f\\n\\r\\t",
+    "\\u0041",
+    "\\uD83D\\uDE00",
+    " ",
+    "if",
+]
+
+OPERATORS = ["==", "!=", "<", "<=", ">", ">="]
+
+LITERALS = ["true", "false", "null", "1", "-2", "1.5", "-0.0", "1e-3", "2.5e10", "1e2"]
+
+TESTS = ["@.a", "$..b", "@[*]", "match(@.a, 'x.*')", "search(@, '[a-z]')"]
+
+SLICES = ["1:2", "::-1", ":3", "1:", "::", "-1::2"]
+
+QUERIES = [
+    "$[?!(@.a == 1)]",
+    "$[?!(@.a && @.b)]",
+    "$[?!(!@.a)]",
+    "$[?@.a && (@.b || @.c)]",
+    "$[?(@.a || @.b) && @.c]",
+    "$[?@.a || (@.b || @.c)]",
+    "$[?@.a && (@.b && @.c)]",
+    "$[?(@.a || @.b) || @.c]",
+    "$[?!@.a || !(@.b && !(@.c == 'd'))]",
+    "$['a\\'b', \"c'd\", 'e\"f', '\\\\', '\\b\\f\\n\\r\\t', '\\uD83D\\uDE00']",
+    "$[?@.a == 'it\\'s' || @.b == \"\\\"quoted\\\"\"]",
+    "$[?@.a == 1.0 && @.b == 1e-7 && @.c == -0.0 && @.d == 1e3]",
+    "$..[?length(@.a) > 1][1:2:-1, ::, :3]",
+]
+
+
+class _Generator:
+    """Generate random, mostly valid JSONPath query strings."""
+
+    def __init__(self, seed: int) -> None:
+        self.random = random.Random(seed)  # noqa: S311
+
+    def name(self) -> str:
+        name = self.random.choice(NAMES)
+        quote = self.random.choice("'\"")
+        if '"' in name or ("'" not in name and quote == "'"):
+            return f"'{name}'"
+        return f'"{name}"'
+
+    def singular_query(self) -> str:
+        query = self.random.choice("@$")
+        for _ in range(self.random.randint(0, 2)):
+            query += self.random.choice(
+                [".a", f"[{self.name()}]", f"[{self.random.randint(-3, 3)}]"]
+            )
+        return query
+
+    def comparable(self) -> str:
+        choice = self.random.random()
+        if choice < 0.3:  # noqa: PLR2004
+            return self.random.choice(LITERALS)
+        if choice < 0.4:  # noqa: PLR2004
+            return self.name()
+        if choice < 0.8:  # noqa: PLR2004
+            return self.singular_query()
+        return f"length({self.singular_query()})"
+
+    def basic_expression(self, depth: int) -> str:
+        choice = self.random.random()
+        if depth > 3 or choice < 0.35:  # noqa: PLR2004
+            operator = self.random.choice(OPERATORS)
+            return f"{self.comparable()} {operator} {self.comparable()}"
+        not_ = self.random.choice(["", "!"])
+        if choice < 0.55:  # noqa: PLR2004
+            return not_ + self.random.choice(TESTS)
+        return f"{not_}({self.logical_expression(depth + 1)})"
+
+    def logical_expression(self, depth: int) -> str:
+        expression = self.basic_expression(depth)
+        for _ in range(self.random.randint(0, 2)):
+            operator = self.random.choice(["&&", "||"])
+            expression += f" {operator} {self.basic_expression(depth)}"
+        return expression
+
+    def selector(self) -> str:
+        choice = self.random.randint(0, 4)
+        if choice == 0:
+            return self.name()
+        if choice == 1:
+            return str(self.random.randint(-5, 5))
+        if choice == 2:  # noqa: PLR2004
+            return "*"
+        if choice == 3:  # noqa: PLR2004
+            return self.random.choice(SLICES)
+        return f"?{self.logical_expression(0)}"
+
+    def segment(self) -> str:
+        selectors = ", ".join(
+            self.selector() for _ in range(self.random.randint(1, 3))
+        )
+        prefix = self.random.choice(["", ".."])
+        if self.random.random() < 0.7:  # noqa: PLR2004
+            return f"{prefix}[{selectors}]"
+        return prefix + self.random.choice([".a", ".*"]).lstrip("." if prefix else "")
+
+    def query(self) -> str:
+        return "$" + "".join(
+            self.segment() for _ in range(self.random.randint(0, 4))
+        )
+
+
+def _queries(seed: int, count: int) -> list[Query]:
+    generator = _Generator(seed)
+    queries: list[Query] = []
+    while len(queries) < count:
+        try:
+            queries.append(parse_jsonpath_query(generator.query()))
+        except PyLiquidError:
+            pass
+    return queries
+
+
+def _assert_round_trip(query: Query) -> None:
+    source = str(query)
+    assert parse_jsonpath_query(source).without_spans() == query.without_spans(), (
+        source
+    )
+
+
+@pytest.mark.parametrize("path", QUERIES)
+def test_round_trip(path: str) -> None:
+    """Test that tricky queries read back as the same query."""
+    _assert_round_trip(parse_jsonpath_query(path))
+
+
+@pytest.mark.parametrize("seed", range(5))
+def test_round_trip_random_queries(seed: int) -> None:
+    """Test that randomly generated queries read back as the same query."""
+    for query in _queries(seed, 200):
+        _assert_round_trip(query)
+
+
+def test_minimal_parentheses() -> None:
+    """Test that we only write parentheses where precedence requires them."""
+    assert str(parse_jsonpath_query("$[?(@.a && @.b) || @.c]")) == (
+        "$[?@['a'] && @['b'] || @['c']]"
+    )
+    assert str(parse_jsonpath_query("$[?@.a && (@.b || @.c)]")) == (
+        "$[?@['a'] && (@['b'] || @['c'])]"
+    )
+    assert str(parse_jsonpath_query("$[?!(@.a == 1)]")) == "$[?!(@['a'] == 1)]"
+
+
+def test_escaped_names() -> None:
+    """Test that names and string literals are escaped."""
+    query = parse_jsonpath_query("$[\"a'b\\n\"][?@.c == \"\\\\\\t\"]")
+    assert str(query) == "$['a\\'b\\n'][?@['c'] == '\\\\\\t']"
+
+
+def test_shorthand() -> None:
+    """Test that we can write queries the way Liquid authors do."""
+    for path, want in [
+        ("customer.orders[0].total", "customer.orders[0].total"),
+        ("$['a']['b c'][*]..d", "a['b c'].*..d"),
+        ("a[b.c]", "a[b.c]"),
+        ("a[?@['b'] > 1]", "a[?@.b > 1]"),
+        ("['if'].b", "['if'].b"),
+        ("$..a", "$..a"),
+        ("$", "$"),
+    ]:
+        assert parse_query(path).shorthand() == want
+
+
+@pytest.mark.parametrize("seed", range(5))
+def test_shorthand_round_trip(seed: int) -> None:
+    """Test that queries written in shorthand read back as the same query."""
+    for query in _queries(seed, 200):
+        query = Query([s for s in query.segments if not isinstance(s, Segment.Eoi)])
+        source = query.shorthand()
+        assert parse_query(source).without_spans() == query.without_spans(), source
+
+
+def test_template_queries_use_shorthand() -> None:
+    """Test that queries in markup are written in shorthand."""
+    source = "{{ a.b['c d'] }}{% for x in (1..y.size) %}{% endfor %}"
+    assert "".join(str(t) for t in tokenize(source)) == source
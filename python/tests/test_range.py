@@ -0,0 +1,121 @@
+"""Test range literals."""
+
+import operator
+from dataclasses import dataclass
+
+import pytest
+from _liquid2 import LiquidSyntaxError
+from _liquid2 import LiquidTypeError
+from _liquid2 import Query
+from _liquid2 import Range
+from _liquid2 import RangeArgument
+from _liquid2 import tokenize
+from liquid2 import Environment
+
+
+@dataclass
+class Case:
+    """Test case helper."""
+
+    name: str
+    source: str
+    data: dict[str, object]
+    want: list[int]
+
+
+def _range(source: str) -> Range:
+    return Range(tokenize(f"{{{{ {source} }}}}")[0].expression[0])  # type: ignore
+
+
+TEST_CASES = [
+    Case(name="integers", source="(1..3)", data={}, want=[1, 2, 3]),
+    Case(name="negative integers", source="(-2..0)", data={}, want=[-2, -1, 0]),
+    Case(name="descending", source="(3..1)", data={}, want=[]),
+    Case(name="floats", source="(1.2..2.9)", data={}, want=[1, 2]),
+    Case(name="strings", source="('1'..'3')", data={}, want=[1, 2, 3]),
+    Case(name="escaped strings", source="('\\u0031'..3)", data={}, want=[1, 2, 3]),
+    Case(name="not a number", source="('a'..2)", data={}, want=[0, 1, 2]),
+    Case(name="string exponent", source="('1e1'..'1.1e1')", data={}, want=[0]),
+    Case(name="string float", source="('1.5'..2)", data={}, want=[0, 1, 2]),
+    Case(name="padded string", source="(' 2 '..3)", data={}, want=[2, 3]),
+    Case(name="signed string", source="('+1'..'2')", data={}, want=[1, 2]),
+    Case(name="underscores", source="('1_0'..11)", data={}, want=[10, 11]),
+    Case(
+        name="query exponent",
+        source="(a..b)",
+        data={"a": "1e1", "b": 1},
+        want=[0, 1],
+    ),
+    Case(name="queries", source="(a..b)", data={"a": 2, "b": "4"}, want=[2, 3, 4]),
+    Case(name="undefined", source="(a..b)", data={"b": 1}, want=[0, 1]),
+]
+
+
+@pytest.mark.parametrize("case", TEST_CASES, ids=operator.attrgetter("name"))
+def test_evaluate_range(case: Case) -> None:
+    """Test that we can resolve and iterate range literals."""
+
+    def resolve(query: Query) -> object:
+        name = query.as_word()
+        assert name is not None
+        return case.data.get(name)
+
+    assert list(_range(case.source).evaluate(resolve)) == case.want
+
+
+def test_string_endpoints_are_unescaped() -> None:
+    """Test that range string endpoints are unescaped."""
+    range_ = _range("('\\u0031'..\"\\t\")")
+    start, stop = range_.start, range_.stop
+    assert isinstance(start, RangeArgument.StringLiteral)
+    assert isinstance(stop, RangeArgument.StringLiteral)
+    assert start.value == "1"
+    assert stop.value == "\t"
+
+
+def test_numeric_string_endpoint_out_of_range() -> None:
+    """Test that numeric string endpoints are validated at lex time."""
+    with pytest.raises(LiquidSyntaxError, match="integer literal out of range"):
+        tokenize("{{ ('99999999999999999999'..3) }}")
+
+
+@pytest.mark.parametrize(
+    "value",
+    [2**63, -(2**63) - 1, 1e19, float("inf"), "9223372036854775808", "-9223372036854775809"],
+)
+def test_resolved_endpoint_out_of_range(value: object) -> None:
+    """Test that endpoints that don't fit in an integer are an error."""
+    with pytest.raises(LiquidTypeError, match="range endpoint out of range"):
+        _range("(a..1)").resolve(lambda _: value)
+
+
+
+def test_stop_at_max_int() -> None:
+    """Test that an inclusive range can stop at the largest integer."""
+    max_int = 2**63 - 1
+    assert list(_range("(a..b)").evaluate(lambda _: max_int)) == [max_int]
+
+
+@pytest.mark.parametrize(
+    "value",
+    ["1", " 2 ", "+2", "-1", "1_0", "1__0", "_1", "1.5", "1e3", "0x10", "a", "", 2.9],
+)
+def test_parity_with_the_renderer(value: object) -> None:
+    """Test that we resolve endpoints like the Python renderer does."""
+    template = Environment().from_string("{% for i in (a..12) %}{{ i }},{% endfor %}")
+    rendered = template.render(a=value)
+    want = [str(i) for i in _range("(a..12)").evaluate(lambda _: value)]
+    assert rendered.split(",")[:-1] == want
+
+
+def test_line_statement_range() -> None:
+    """Test that range literals in line statements don't span lines."""
+    token = tokenize("{% liquid for i in (1..a.b)\necho i\nendfor %}")[0]
+    statement = token.statements[0]  # type: ignore
+    assert str(Range(statement.expression[2])) == "(1..a.b)"
+
+
+def test_not_a_range() -> None:
+    """Test that we can't make a range from other tokens."""
+    with pytest.raises(TypeError):
+        Range(tokenize("{{ a }}")[0].expression[0])  # type: ignore
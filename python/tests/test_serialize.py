@@ -0,0 +1,100 @@
+"""Test binary encoding of token streams."""
+
+import hashlib
+import pickle
+
+import pytest
+from _liquid2 import LiquidSerializationError
+from _liquid2 import Markup
+from _liquid2 import dumps
+from _liquid2 import is_current
+from _liquid2 import loads
+from _liquid2 import tokenize
+
+SOURCE = (
+    "{%- liquid\n  for x in (1..y.size)\n    echo x | default: 'a'\n  endfor\n-%}"
+    "{{ a[?@.b > 1 && count(@.c[*]) == $.d]['e'] || 'f' }}"
+    "{% raw %}{{ g }}{% endraw %}{# h #}{% if i and 1.5 %}j{% endif %}"
+)
+
+# Exercises as many kinds of markup, tokens and query nodes as we can.
+LAYOUT_SOURCE = """\
+Hello, {{ you | upcase }}!{# note #}{% raw %}{{ x }}{% endraw %}
+{%- if a.b[0] == 'x' and not c or d contains "e" -%}
+  {{- $.users[?@.age >= 18 && length(@.name) > 1].name | default: nil ~}}
+  {% for x in (1..a.size) limit: 2 %}{{ x[-1:2:1] }}{% endfor %}
+{%+ elsif true %}{{ 1.5 }}{{ -2 }}{{ false }}{{ $..items.* }}
+{% endif %}
+{% liquid
+  assign y = a["b c"]
+  echo y
+%}
+"""
+
+# SHA-256 of the encoding of `LAYOUT_SOURCE`, after the header, by format
+# version. If a change to the syntax tree changes the encoding, bump
+# `FORMAT_VERSION` in serialize.rs and add the new digest here.
+LAYOUT_DIGESTS = {
+    1: "92cd7cd9357a4cecd242dd7524c97b26ed0cd95bfcb0fac158b29040da530850",
+    2: "a0b064b31fbeed7612014fc11538cd3514659ebc476e612eeb85106d84087175",
+}
+
+
+def _strings(tokens: list[Markup]) -> list[str]:
+    return [str(t) for t in tokens]
+
+
+def test_round_trip() -> None:
+    """Test that we can decode an encoded token stream."""
+    tokens = tokenize(SOURCE)
+    data = dumps(tokens, SOURCE)
+    assert data[:4] == b"LQ2T"
+    assert _strings(loads(data)) == _strings(tokens)
+    assert _strings(loads(data, source=SOURCE)) == _strings(tokens)
+
+
+def test_format_version_changes_with_layout() -> None:
+    """Test that the encoding doesn't change without a format version bump."""
+    data = dumps(tokenize(LAYOUT_SOURCE), LAYOUT_SOURCE)
+    version = int.from_bytes(data[4:6], "little")
+    assert hashlib.sha256(data[22:]).hexdigest() == LAYOUT_DIGESTS[version]
+
+
+def test_stale_token_stream() -> None:
+    """Test that we detect token streams encoded from different source."""
+    data = dumps(tokenize(SOURCE), SOURCE)
+    assert is_current(data, SOURCE)
+    assert not is_current(data, SOURCE + " ")
+
+    with pytest.raises(LiquidSerializationError, match="stale"):
+        loads(data, source=SOURCE + " ")
+
+
+def test_invalid_token_stream() -> None:
+    """Test that we reject data that is not an encoded token stream."""
+    data = dumps(tokenize(SOURCE), SOURCE)
+    assert not is_current(b"nosuchthing", SOURCE)
+
+    with pytest.raises(LiquidSerializationError):
+        loads(b"nosuchthing")
+
+    with pytest.raises(LiquidSerializationError):
+        loads(data[:-3])
+
+    # Unsupported format version.
+    with pytest.raises(LiquidSerializationError, match="version"):
+        loads(data[:4] + b"\xff\xff" + data[6:])
+
+    # Different grammar.
+    with pytest.raises(LiquidSerializationError, match="grammar"):
+        loads(data[:6] + bytes(8) + data[14:])
+
+
+def test_pickle_markup() -> None:
+    """Test that markup can be pickled."""
+    tokens = tokenize(SOURCE)
+    unpickled = pickle.loads(pickle.dumps(tokens))  # noqa: S301
+    assert _strings(unpickled) == _strings(tokens)
+    assert isinstance(unpickled[1], Markup.Output)
+    assert unpickled[1].span == tokens[1].span
+    assert str(Markup.from_bytes(tokens[0].to_bytes())) == str(tokens[0])
@@ -11,13 +11,17 @@ from typing import Sequence
 from typing import TypeAlias
 
 import pytest
+from _liquid2 import parse_query
 from liquid2 import DictLoader
 from liquid2 import Environment
+from liquid2 import Markup
+from liquid2 import Template
+from liquid2 import Token
+from liquid2 import Whitespace
 from liquid2.exceptions import TemplateInheritanceError
 from liquid2.static_analysis import Span
 
 if TYPE_CHECKING:
-    from liquid2 import Template
     from liquid2.static_analysis import TemplateAnalysis
 
 
@@ -878,3 +882,12 @@ def test_analyze_super() -> None:
             "upcase": _Span(35, 41, template_name="base"),
         },
     )
+
+
+def test_nodes_without_spans(env: Environment) -> None:
+    """Test that we can analyze templates built from nodes without spans."""
+    wc = (Whitespace.Default, Whitespace.Default)
+    tokens = [Markup.Output(wc, [Token.Query(parse_query("x"))]), Markup.EOI()]
+    analysis = Template(env, env.parser.parse(tokens)).analyze()
+    spans = [span for spans in analysis.variables.values() for span in spans]
+    assert spans == [Span("<string>", -1, -1)]
@@ -0,0 +1,166 @@
+"""Test evaluating queries while reading a JSON document."""
+
+import io
+import json
+import os
+import tempfile
+
+import pytest
+from _liquid2 import LiquidSyntaxError
+from _liquid2 import LiquidTypeError
+from _liquid2 import compile  # noqa: A004
+
+DATA = {
+    "users": [
+        {"name": "Sue", "score": 100, "tags": ["a", "b"]},
+        {"name": "John", "score": 86, "tags": []},
+        {"name": "Sally", "score": 84, "tags": ["c"]},
+    ],
+    "moderators": ["John"],
+    "escaped": {"a\"b\\cé\U0001f600": 1},
+}
+
+
+def _stream(path: str, data: object = DATA) -> list[tuple[str, object]]:
+    source = io.BytesIO(json.dumps(data).encode())
+    return [(str(m.path), m.value) for m in compile(path).stream(source)]
+
+
+def test_names_and_indices() -> None:
+    """Test that we find values by name and index."""
+    assert _stream("$.users[1].name") == [("$['users'][1]['name']", "John")]
+    assert _stream("$.moderators[0]") == [("$['moderators'][0]", "John")]
+    assert _stream("$.users[5]") == []
+
+
+def test_wildcard_and_descendants() -> None:
+    """Test that we find values with wildcards and descendant segments."""
+    assert _stream("$.users[*].score") == [
+        ("$['users'][0]['score']", 100),
+        ("$['users'][1]['score']", 86),
+        ("$['users'][2]['score']", 84),
+    ]
+    assert _stream("$..tags[0]") == [
+        ("$['users'][0]['tags'][0]", "a"),
+        ("$['users'][2]['tags'][0]", "c"),
+    ]
+
+
+def test_nested_matches() -> None:
+    """Test that a match inside another match is reported after it."""
+    data = {"a": {"a": 1}}
+    assert _stream("$..a", data) == [("$['a']", {"a": 1}), ("$['a']['a']", 1)]
+
+
+@pytest.mark.parametrize(
+    ("path", "data"),
+    [
+        ("$.a[1,0]", {"a": [1, 2]}),
+        ('$["a","a"]', {"a": 1}),
+        ("$[*,0]", [[1], 2]),
+        ("$..a", {"b": {"a": 1}, "a": 2}),
+        ("$..*", {"a": {"b": 1}, "c": [2, {"d": 3}]}),
+        ("$..[0,*]", [[1, [2]], 3]),
+        ("$..b..c", {"b": {"b": {"c": 1}, "c": 2}}),
+        ("$..[?@.x].x", [{"x": {"x": 1}}, {"y": {"x": 2}}]),
+    ],
+)
+def test_same_order_as_finditer(path: str, data: object) -> None:
+    """Test that we report matches in the same order as finditer."""
+    want = [(str(m.path), m.value) for m in compile(path).finditer(data)]
+    assert _stream(path, data) == want
+
+
+def test_filters() -> None:
+    """Test that filters are evaluated against each candidate's value."""
+    assert _stream("$.users[?@.score > 85].name") == [
+        ("$['users'][0]['name']", "Sue"),
+        ("$['users'][1]['name']", "John"),
+    ]
+    assert _stream("$.users[?length(@.tags) == 1]") == [
+        ("$['users'][2]", DATA["users"][2]),  # type: ignore
+    ]
+
+
+def test_escaped_strings() -> None:
+    """Test that we decode JSON string escapes."""
+    assert _stream("$.escaped.*") == [("$['escaped']['a\"b\\\\cé😀']", 1)]
+
+
+def test_stream_from_file() -> None:
+    """Test that we can read from a file path."""
+    with tempfile.TemporaryDirectory() as tmp:
+        path = os.path.join(tmp, "data.json")
+        with open(path, "w", encoding="utf-8") as fd:
+            json.dump(DATA, fd)
+        matches = list(compile("$.users[*].name").stream(path))
+    assert [m.value for m in matches] == ["Sue", "John", "Sally"]
+
+
+def test_stops_early() -> None:
+    """Test that we read no more than we need to."""
+    source = io.BytesIO(b'{"a": 1, "b": ' + b"[" * 10000)
+    it = compile("$.a").stream(source)
+    assert next(it).value == 1
+
+
+@pytest.mark.parametrize(
+    ("path", "reason"),
+    [
+        ("$.users[-1]", "negative indices"),
+        ("$.users[1:]", "slice selectors"),
+        ("$.users[?@.score > $.min]", "root value"),
+        ("$.users[?@[$.i]]", "root value"),
+        ("$[$.a]", "embedded queries"),
+    ],
+)
+def test_unsupported_queries(path: str, reason: str) -> None:
+    """Test that we explain why a query can't be streamed."""
+    with pytest.raises(LiquidTypeError, match=f"can't stream .*{reason}"):
+        compile(path).stream(io.BytesIO(b"{}"))
+
+
+def test_duplicate_names() -> None:
+    """Test that every occurrence of a duplicate name is matched."""
+    source = io.BytesIO(b'{"a": 1, "a": 2}')
+    assert [m.value for m in compile("$.a").stream(source)] == [1, 2]
+
+    # Values read in full keep the last occurrence, like `json.loads`.
+    source = io.BytesIO(b'[[{"a": 1, "a": 2}]]')
+    assert [m.value for m in compile("$[*][?@.a]").stream(source)] == [{"a": 2}]
+
+
+def test_big_integers() -> None:
+    """Test that integers we can't read exactly are an error."""
+    big = b"123456789012345678901234567890"
+    with pytest.raises(LiquidTypeError, match="signed 64 bit integer"):
+        list(compile("$.a").stream(io.BytesIO(b'{"a": ' + big + b"}")))
+
+    # Integers we skip over are not read.
+    source = io.BytesIO(b'{"a": 1, "b": ' + big + b"}")
+    assert [m.value for m in compile("$.a").stream(source)] == [1]
+
+    source = io.BytesIO(b'{"a": 9223372036854775807}')
+    assert [m.value for m in compile("$.a").stream(source)] == [2**63 - 1]
+
+    with pytest.raises(LiquidTypeError, match="signed 64 bit integer"):
+        list(compile("$.a").stream(io.BytesIO(b'{"a": 9223372036854775808}')))
+
+
+
+def test_skipped_numbers() -> None:
+    """Test that numbers we skip over only have to be valid JSON."""
+    source = io.BytesIO(b'{"a": 1e400, "b": 2}')
+    assert [m.value for m in compile("$.b").stream(source)] == [2]
+
+    with pytest.raises(LiquidSyntaxError, match="bad number `01`"):
+        list(compile("$.b").stream(io.BytesIO(b'{"a": 01, "b": 2}')))
+
+@pytest.mark.parametrize(
+    "source",
+    [b"", b"[1,]", b'{"a" 1}', b"[1] 2", b'["\\x"]', b"[01x]", b"[tru]", b"[1"],
+)
+def test_invalid_json(source: bytes) -> None:
+    """Test that invalid JSON is a syntax error."""
+    with pytest.raises(LiquidSyntaxError, match="invalid JSON"):
+        list(compile("$..*").stream(io.BytesIO(source)))
@@ -0,0 +1,51 @@
+"""Test tokenizing many templates in parallel."""
+
+import tempfile
+from pathlib import Path
+
+from _liquid2 import Lexer
+from _liquid2 import LiquidSyntaxError
+from _liquid2 import Whitespace
+from _liquid2 import tokenize
+from _liquid2 import tokenize_many
+from _liquid2 import tokenize_paths
+
+
+def test_tokenize_many() -> None:
+    """Test that results are in the same order as sources."""
+    sources = [f"{{{{ x{i} }}}}" for i in range(100)]
+    results = tokenize_many(sources)
+    assert len(results) == len(sources)
+    for source, tokens in zip(sources, results, strict=True):
+        assert isinstance(tokens, list)
+        assert [str(t) for t in tokens] == [str(t) for t in tokenize(source)]
+
+
+def test_tokenize_many_errors() -> None:
+    """Test that errors are returned for each template that fails."""
+    results = tokenize_many(["{{ a }}", "{{ a ", "{{ b }}"])
+    assert isinstance(results[0], list)
+    assert isinstance(results[1], LiquidSyntaxError)
+    assert isinstance(results[2], list)
+
+
+def test_tokenize_many_trim() -> None:
+    """Test that we can apply whitespace control to many templates."""
+    results = Lexer().tokenize_many(["a  {{ b }}"], trim=Whitespace.Minus)
+    assert isinstance(results[0], list)
+    assert results[0][0].trimmed_span == (0, 1)
+
+
+def test_tokenize_paths() -> None:
+    """Test that we can read and tokenize many files."""
+    with tempfile.TemporaryDirectory() as tmp:
+        a = Path(tmp, "a.liquid")
+        b = Path(tmp, "b.liquid")
+        a.write_text("Hello, {{ you }}!")
+        b.write_text("{% if x %}")
+
+        results = tokenize_paths([a, str(b), Path(tmp, "nosuchthing.liquid")])
+        assert isinstance(results[0], list)
+        assert len(results[0]) == 4  # noqa: PLR2004
+        assert isinstance(results[1], list)
+        assert isinstance(results[2], OSError)
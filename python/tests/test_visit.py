@@ -0,0 +1,108 @@
+"""Test walking syntax trees."""
+
+import pytest
+from _liquid2 import FilterExpression
+from _liquid2 import Markup
+from _liquid2 import Query
+from _liquid2 import Segment
+from _liquid2 import Selector
+from _liquid2 import Token
+from _liquid2 import parse_query
+from _liquid2 import tokenize
+from _liquid2 import walk
+
+
+def test_walk_query() -> None:
+    """Test that we visit every node of a query in pre-order."""
+    query = parse_query("a[?@.b > 1]")
+    nodes = list(walk(query))
+
+    assert [type(node).__name__ for node, _, _ in nodes] == [
+        "Query",
+        "Segment_Child",
+        "Selector_Name",
+        "Segment_Child",
+        "Selector_Filter",
+        "FilterExpression_Comparison",
+        "FilterExpression_RelativeQuery",
+        "Query",
+        "Segment_Child",
+        "Selector_Name",
+        "FilterExpression_Int",
+    ]
+
+    root, parent, span = nodes[0]
+    assert root is query
+    assert parent is None
+    assert span == (0, 11)
+
+
+def test_walk_parents() -> None:
+    """Test that each node is paired with its parent."""
+    nodes = list(walk(parse_query("a[?@.b > 1]")))
+    for node, parent, _ in nodes[1:]:
+        assert parent is not None
+        assert any(n is parent for n, _, _ in nodes)
+
+    comparison = nodes[5][0]
+    assert isinstance(comparison, FilterExpression.Comparison)
+    children = [node for node, parent, _ in nodes if parent is comparison]
+    assert children == [comparison.left, comparison.right]
+
+
+def test_walk_markup() -> None:
+    """Test that we walk into output, tags and range literals."""
+    tokens = tokenize("Hello {{ x.y }}{% for a in (1..b) %}{% endfor %}")
+    nodes = list(walk(tokens))
+    top_level = [node for node, parent, _ in nodes if parent is None]
+    assert top_level == tokens
+    assert all(a is b for a, b in zip(top_level, tokens, strict=True))
+
+    queries = [str(node) for node, _, _ in nodes if isinstance(node, Query)]
+    assert queries == ["$['x']['y']", "$['b']"]
+
+    spans = [span for node, _, span in nodes if isinstance(node, Query)]
+    assert spans == [(9, 12), (31, 32)]
+
+
+def test_walk_liquid_tag() -> None:
+    """Test that we walk into `liquid` tag statements."""
+    tokens = tokenize("{% liquid\nif a.b\necho c\nendif %}")
+    nodes = list(walk(tokens))
+    lines = tokens[0]
+    assert isinstance(lines, Markup.Lines)
+    statements = [node for node, parent, _ in nodes if parent is lines]
+    assert statements == lines.statements
+    words = [node.value for node, _, _ in nodes if isinstance(node, Token.Word)]
+    assert words == ["c"]
+
+
+def test_walk_constructed_nodes() -> None:
+    """Test that we can walk nodes without spans."""
+    query = Query([Segment.Child([Selector.Name("a")])])
+    nodes = list(walk(query))
+    assert len(nodes) == 3  # noqa: PLR2004
+    assert nodes[0][2] is None
+
+
+@pytest.mark.parametrize("nodes", ["a", 1, [Query([]), "a"], [[Query([])]]])
+def test_walk_non_nodes(nodes: object) -> None:
+    """Test that we only walk nodes and lists or tuples of nodes."""
+    with pytest.raises(TypeError, match="expected a syntax tree node"):
+        walk(nodes)  # type: ignore
+
+
+def test_walk_self_containing_list() -> None:
+    """Test that a list containing itself is not walked forever."""
+    nodes: list[object] = [Query([])]
+    nodes.append(nodes)
+    with pytest.raises(TypeError, match="found list"):
+        walk(nodes)  # type: ignore
+
+
+def test_walk_tuple() -> None:
+    """Test that we walk a tuple of nodes."""
+    assert [node for node, _, _ in walk((Query([]), Query([])))] == [
+        Query([]),
+        Query([]),
+    ]
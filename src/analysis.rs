@@ -0,0 +1,386 @@
+//! Static analysis of template variables, filters and tags
+//!
+
+use std::collections::hash_map::Entry;
+use std::collections::{HashMap, HashSet};
+
+use pyo3::prelude::*;
+
+use crate::block::{parse_blocks, standard_blocks, BlockNode};
+use crate::errors::LiquidError;
+use crate::markup::{Markup, RangeArgument, Token};
+use crate::model::{Span, Structural};
+use crate::query::{FilterExpression, Query, Segment, Selector};
+
+/// A variable path and where it appears in template source.
+#[pyclass(frozen)]
+#[derive(Debug, Clone)]
+pub struct Variable {
+    #[pyo3(get)]
+    pub path: Query,
+    #[pyo3(get)]
+    pub span: Option<Span>,
+}
+
+/// Variables, filters and tags found in a template, in order of appearance.
+#[pyclass(frozen)]
+#[derive(Debug, Clone, Default)]
+pub struct TemplateAnalysis {
+    /// Every variable referenced by the template.
+    #[pyo3(get)]
+    pub variables: Vec<Variable>,
+    /// Variables that are referenced before being assigned, and are not in
+    /// scope of an enclosing block.
+    #[pyo3(get)]
+    pub globals: Vec<Variable>,
+    /// Names introduced by `assign`, `capture`, `increment`, `decrement` and
+    /// `for`, with the span of the name.
+    #[pyo3(get)]
+    pub locals: Vec<Variable>,
+    #[pyo3(get)]
+    pub filters: Vec<(String, Option<Span>)>,
+    #[pyo3(get)]
+    pub tags: Vec<(String, Option<Span>)>,
+}
+
+#[pymethods]
+impl TemplateAnalysis {
+    /// Return each distinct variable path with the spans of every reference
+    /// to it, in order of first appearance. Global variables only if
+    /// _globals_ is `true`.
+    #[pyo3(signature = (*, globals=false))]
+    fn group_variables(&self, globals: bool) -> Vec<(Query, Vec<Option<Span>>)> {
+        if globals {
+            group_variables(&self.globals)
+        } else {
+            group_variables(&self.variables)
+        }
+    }
+}
+
+/// Group _variables_ by path, ignoring spans and equivalent forms of the same
+/// path, in order of first appearance.
+pub fn group_variables(variables: &[Variable]) -> Vec<(Query, Vec<Option<Span>>)> {
+    let mut groups: Vec<(Query, Vec<Option<Span>>)> = Vec::new();
+    let mut index: HashMap<Structural<Query>, usize> = HashMap::new();
+
+    for variable in variables {
+        match index.entry(Structural::new(&variable.path)) {
+            Entry::Occupied(entry) => groups[*entry.get()].1.push(variable.span),
+            Entry::Vacant(entry) => {
+                entry.insert(groups.len());
+                groups.push((variable.path.clone(), vec![variable.span]));
+            }
+        }
+    }
+
+    groups
+}
+
+#[derive(Default)]
+struct Analyzer {
+    analysis: TemplateAnalysis,
+    template_scope: HashSet<String>,
+    block_scope: Vec<Vec<String>>,
+}
+
+/// Analyze variables, filters and tags in _tokens_, as returned from the lexer.
+pub fn analyze(tokens: &[Markup]) -> Result<TemplateAnalysis, LiquidError> {
+    let nodes = parse_blocks(tokens, &standard_blocks())?;
+    let mut analyzer = Analyzer::default();
+    analyzer.visit_nodes(&nodes);
+    Ok(analyzer.analysis)
+}
+
+impl Analyzer {
+    fn visit_nodes(&mut self, nodes: &[BlockNode]) {
+        for node in nodes {
+            match node {
+                BlockNode::Markup { markup } => self.visit_markup(markup),
+                BlockNode::Block { name, branches, .. } => {
+                    for (i, branch) in branches.iter().enumerate() {
+                        let scope = if i == 0 {
+                            self.visit_block_tag(name, &branch.tag)
+                        } else {
+                            self.visit_expression(tag_expression(&branch.tag));
+                            Vec::new()
+                        };
+
+                        self.block_scope.push(scope);
+                        self.visit_nodes(&branch.nodes);
+                        self.block_scope.pop();
+                    }
+                }
+                BlockNode::Lines {
+                    name,
+                    statements,
+                    span,
+                    ..
+                } => {
+                    self.analysis.tags.push((name.to_owned(), *span));
+                    self.visit_nodes(statements);
+                }
+            }
+        }
+    }
+
+    fn visit_markup(&mut self, markup: &Markup) {
+        match markup {
+            Markup::Output { expression, .. } => self.visit_expression(expression),
+            Markup::Tag {
+                name,
+                expression,
+                span,
+                ..
+            } => {
+                self.analysis.tags.push((name.to_owned(), *span));
+                let tokens = expression.as_deref().unwrap_or_default();
+                match name.as_str() {
+                    "assign" => {
+                        // The assigned expression is analyzed before the name
+                        // comes into scope.
+                        if let Some(position) = tokens
+                            .iter()
+                            .position(|t| matches!(t, Token::Assign { .. }))
+                        {
+                            self.visit_expression(&tokens[position + 1..]);
+                        }
+                        self.assign(tokens.first());
+                    }
+                    "increment" | "decrement" => self.assign(tokens.first()),
+                    _ => self.visit_expression(tokens),
+                }
+            }
+            Markup::Lines { .. } => {
+                unreachable!("line statements are grouped into BlockNode::Lines")
+            }
+            Markup::Content { .. }
+            | Markup::Raw { .. }
+            | Markup::Comment { .. }
+            | Markup::EOI {} => {}
+        }
+    }
+
+    /// Record the opening tag of a block and return names in scope of its
+    /// first branch.
+    fn visit_block_tag(&mut self, name: &str, tag: &Markup) -> Vec<String> {
+        if let Markup::Tag { span, .. } = tag {
+            self.analysis.tags.push((name.to_owned(), *span));
+        }
+
+        let tokens = tag_expression(tag);
+
+        match name {
+            "for" => {
+                let mut rest = tokens.get(1..).unwrap_or_default();
+                if let Some(Token::In { .. }) = rest.first() {
+                    rest = &rest[1..];
+                }
+                self.visit_for_expression(rest);
+                match tokens.first().and_then(identifier) {
+                    Some((target, span)) => {
+                        self.analysis.locals.push(Variable {
+                            path: name_query(&target, span),
+                            span,
+                        });
+                        vec![target, "forloop".to_owned()]
+                    }
+                    None => vec!["forloop".to_owned()],
+                }
+            }
+            "capture" => {
+                self.assign(tokens.first());
+                Vec::new()
+            }
+            "block" => vec!["block".to_owned()],
+            _ => {
+                self.visit_expression(tokens);
+                Vec::new()
+            }
+        }
+    }
+
+    /// Like `visit_expression`, but skip the `reversed` flag and the special
+    /// `continue` offset of a `for` tag.
+    fn visit_for_expression(&mut self, tokens: &[Token]) {
+        let mut start = 0;
+        for (i, token) in tokens.iter().enumerate() {
+            let skip = match token {
+                Token::Word { value, .. } if value == "reversed" => true,
+                Token::Word { value, .. } if value == "continue" => {
+                    i >= 2
+                        && matches!(
+                            &tokens[i - 2..i],
+                            [Token::Word { value, .. }, Token::Colon { .. }] if value == "offset"
+                        )
+                }
+                _ => false,
+            };
+
+            if skip {
+                self.visit_expression(&tokens[start..i]);
+                start = i + 1;
+            }
+        }
+        self.visit_expression(&tokens[start..]);
+    }
+
+    fn visit_expression(&mut self, tokens: &[Token]) {
+        let mut previous: Option<&Token> = None;
+        for (i, token) in tokens.iter().enumerate() {
+            match token {
+                Token::Word { value, span } => {
+                    if matches!(
+                        previous,
+                        Some(Token::Pipe { .. } | Token::DoublePipe { .. })
+                    ) {
+                        self.analysis.filters.push((value.to_owned(), *span));
+                    } else if matches!(tokens.get(i + 1), Some(Token::Colon { .. }))
+                        || matches!(previous, Some(Token::As { .. }))
+                        || value == "empty"
+                        || value == "blank"
+                    {
+                        // Argument names, aliases and special literals.
+                    } else {
+                        self.reference(&name_query(value, *span), *span);
+                    }
+                }
+                Token::Query { path, span } if !matches!(previous, Some(Token::As { .. })) => {
+                    self.reference(path, *span);
+                }
+                Token::RangeLiteral { start, stop, .. } => {
+                    for argument in [start, stop] {
+                        if let RangeArgument::Query { path, span } = argument {
+                            self.reference(path, *span);
+                        }
+                    }
+                }
+                _ => (),
+            }
+            previous = Some(token);
+        }
+    }
+
+    /// Record a reference to _path_ and any queries nested inside it.
+    fn reference(&mut self, path: &Query, span: Option<Span>) {
+        let variable = Variable {
+            path: path.clone(),
+            span,
+        };
+
+        if !head(path).is_some_and(|name| self.in_scope(name)) {
+            self.analysis.globals.push(variable.clone());
+        }
+
+        self.analysis.variables.push(variable);
+        self.visit_segments(path);
+    }
+
+    fn visit_segments(&mut self, path: &Query) {
+        for segment in &path.segments {
+            match segment {
+                Segment::Child { selectors, .. } | Segment::Recursive { selectors, .. } => {
+                    for selector in selectors {
+                        match selector {
+                            Selector::SingularQuery { query, span } => self.reference(query, *span),
+                            Selector::Filter { expression, .. } => {
+                                self.visit_filter_expression(expression)
+                            }
+                            _ => (),
+                        }
+                    }
+                }
+                Segment::Eoi {} => (),
+            }
+        }
+    }
+
+    fn visit_filter_expression(&mut self, expression: &FilterExpression) {
+        match expression {
+            FilterExpression::Not { expression, .. } => self.visit_filter_expression(expression),
+            FilterExpression::Logical { left, right, .. }
+            | FilterExpression::Comparison { left, right, .. } => {
+                self.visit_filter_expression(left);
+                self.visit_filter_expression(right);
+            }
+            FilterExpression::RelativeQuery { query, .. } => self.visit_segments(query),
+            FilterExpression::RootQuery { query, span } => self.reference(query, *span),
+            FilterExpression::Function { args, .. } => {
+                for arg in args {
+                    self.visit_filter_expression(arg);
+                }
+            }
+            _ => (),
+        }
+    }
+
+    /// Bring the name in _token_ into template scope.
+    fn assign(&mut self, token: Option<&Token>) {
+        if let Some((name, span)) = token.and_then(identifier) {
+            self.analysis.locals.push(Variable {
+                path: name_query(&name, span),
+                span,
+            });
+            self.template_scope.insert(name);
+        }
+    }
+
+    fn in_scope(&self, name: &str) -> bool {
+        self.template_scope.contains(name)
+            || self
+                .block_scope
+                .iter()
+                .any(|scope| scope.iter().any(|n| n == name))
+    }
+}
+
+fn tag_expression(tag: &Markup) -> &[Token] {
+    match tag {
+        Markup::Tag {
+            expression: Some(expression),
+            ..
+        } => expression,
+        _ => &[],
+    }
+}
+
+/// Return the name and span of a word, string literal or single name query.
+fn identifier(token: &Token) -> Option<(String, Option<Span>)> {
+    match token {
+        Token::Word { value, span } | Token::StringLiteral { value, span } => {
+            Some((value.to_owned(), *span))
+        }
+        Token::Query { path, span } => path.as_word().map(|name| (name, *span)),
+        _ => None,
+    }
+}
+
+/// Return the name of the first segment of _path_, if it is a name selector.
+fn head(path: &Query) -> Option<&str> {
+    match path.segments.first() {
+        Some(Segment::Child { selectors, .. }) => match selectors.as_slice() {
+            [Selector::Name { name, .. }] => Some(name),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+fn name_query(name: &str, span: Option<Span>) -> Query {
+    Query {
+        segments: vec![Segment::Child {
+            selectors: vec![Selector::Name {
+                name: name.to_owned(),
+                span,
+            }],
+            span,
+        }],
+    }
+}
+
+#[pymethods]
+impl Variable {
+    fn __str__(&self) -> String {
+        self.path.to_string()
+    }
+}
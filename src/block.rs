@@ -0,0 +1,215 @@
+//! Block structure for markup and line statements
+//!
+
+use std::collections::HashMap;
+
+use pyo3::prelude::*;
+
+use crate::errors::LiquidError;
+use crate::markup::{Markup, Whitespace};
+use crate::model::Span;
+
+/// Names of the tags that can appear inside a block and the tag that closes it.
+pub struct BlockSignature {
+    pub branches: Vec<String>,
+    pub end: String,
+}
+
+pub fn standard_blocks() -> HashMap<String, BlockSignature> {
+    let mut blocks = HashMap::new();
+
+    blocks.insert(
+        "if".to_owned(),
+        BlockSignature {
+            branches: vec!["elsif".to_owned(), "else".to_owned()],
+            end: "endif".to_owned(),
+        },
+    );
+
+    blocks.insert(
+        "unless".to_owned(),
+        BlockSignature {
+            branches: vec!["elsif".to_owned(), "else".to_owned()],
+            end: "endunless".to_owned(),
+        },
+    );
+
+    blocks.insert(
+        "for".to_owned(),
+        BlockSignature {
+            branches: vec!["else".to_owned()],
+            end: "endfor".to_owned(),
+        },
+    );
+
+    blocks.insert(
+        "case".to_owned(),
+        BlockSignature {
+            branches: vec!["when".to_owned(), "else".to_owned()],
+            end: "endcase".to_owned(),
+        },
+    );
+
+    blocks.insert(
+        "capture".to_owned(),
+        BlockSignature {
+            branches: Vec::new(),
+            end: "endcapture".to_owned(),
+        },
+    );
+
+    blocks.insert(
+        "block".to_owned(),
+        BlockSignature {
+            branches: Vec::new(),
+            end: "endblock".to_owned(),
+        },
+    );
+
+    blocks
+}
+
+#[pyclass(frozen)]
+#[derive(Debug, Clone)]
+pub enum BlockNode {
+    /// Markup that does not open or close a block.
+    Markup { markup: Markup },
+    /// A block tag, its branches and the tag that closed it. The first branch
+    /// is always the opening tag.
+    Block {
+        name: String,
+        branches: Vec<Branch>,
+        end: Markup,
+        span: Option<Span>,
+    },
+    /// A `liquid` tag with its line statements grouped into blocks.
+    Lines {
+        wc: (Whitespace, Whitespace),
+        name: String,
+        statements: Vec<BlockNode>,
+        span: Option<Span>,
+    },
+}
+
+/// A tag that opens a block or one of its branches, and the nodes that
+/// follow it up to the next branch or end tag.
+#[pyclass(frozen)]
+#[derive(Debug, Clone)]
+pub struct Branch {
+    #[pyo3(get)]
+    pub tag: Markup,
+    #[pyo3(get)]
+    pub nodes: Vec<BlockNode>,
+}
+
+struct OpenBlock<'a> {
+    name: &'a str,
+    signature: &'a BlockSignature,
+    span: Option<Span>,
+    branches: Vec<Branch>,
+}
+
+/// Group _tokens_ into a tree of blocks described by _blocks_.
+///
+/// Statements in `Markup::Lines` are grouped independently, so a block
+/// opened inside a `liquid` tag must be closed inside the same tag.
+pub fn parse_blocks(
+    tokens: &[Markup],
+    blocks: &HashMap<String, BlockSignature>,
+) -> Result<Vec<BlockNode>, LiquidError> {
+    let mut root: Vec<BlockNode> = Vec::new();
+    let mut stack: Vec<OpenBlock> = Vec::new();
+
+    for token in tokens {
+        let node = match token {
+            Markup::Tag { name, span, .. } => {
+                if let Some((name, signature)) = blocks.get_key_value(name) {
+                    stack.push(OpenBlock {
+                        name,
+                        signature,
+                        span: *span,
+                        branches: vec![Branch {
+                            tag: token.clone(),
+                            nodes: Vec::new(),
+                        }],
+                    });
+                    continue;
+                }
+
+                if let Some(open) = stack.last_mut() {
+                    if open.signature.branches.contains(name) {
+                        open.branches.push(Branch {
+                            tag: token.clone(),
+                            nodes: Vec::new(),
+                        });
+                        continue;
+                    }
+
+                    if open.signature.end == *name {
+                        let open = stack.pop().unwrap();
+                        BlockNode::Block {
+                            name: open.name.to_owned(),
+                            branches: open.branches,
+                            end: token.clone(),
+                            span: open.span.zip(*span).map(|(open, end)| (open.0, end.1)),
+                        }
+                    } else if is_block_structure(name, blocks) {
+                        return Err(LiquidError::syntax(format!(
+                            "unexpected tag '{}', expected '{}'",
+                            name, open.signature.end
+                        ))
+                        .with_span(*span));
+                    } else {
+                        BlockNode::Markup {
+                            markup: token.clone(),
+                        }
+                    }
+                } else if is_block_structure(name, blocks) {
+                    return Err(
+                        LiquidError::syntax(format!("unexpected tag '{}'", name)).with_span(*span)
+                    );
+                } else {
+                    BlockNode::Markup {
+                        markup: token.clone(),
+                    }
+                }
+            }
+            Markup::Lines {
+                wc,
+                name,
+                statements,
+                span,
+            } => BlockNode::Lines {
+                wc: wc.clone(),
+                name: name.to_owned(),
+                statements: parse_blocks(statements, blocks)?,
+                span: *span,
+            },
+            _ => BlockNode::Markup {
+                markup: token.clone(),
+            },
+        };
+
+        match stack.last_mut() {
+            Some(open) => open.branches.last_mut().unwrap().nodes.push(node),
+            None => root.push(node),
+        }
+    }
+
+    if let Some(open) = stack.pop() {
+        return Err(LiquidError::syntax(format!(
+            "tag '{}' was never closed, expected '{}'",
+            open.name, open.signature.end
+        ))
+        .with_span(open.span));
+    }
+
+    Ok(root)
+}
+
+/// Return `true` if _name_ is the name of a branch or end tag of any block.
+fn is_block_structure(name: &str, blocks: &HashMap<String, BlockSignature>) -> bool {
+    blocks
+        .values()
+        .any(|b| b.end == name || b.branches.iter().any(|n| n == name))
+}
@@ -0,0 +1,586 @@
+//! Programmatic construction, validation and source generation
+//!
+//! Nodes built with the constructors in this module have no spans. A
+//! node is valid if its source text, as given by `Display`, is read back by
+//! the lexer as the same node, ignoring spans.
+
+use pyo3::prelude::*;
+use pyo3::types::PyType;
+
+use crate::errors::LiquidError;
+use crate::lexer::{default_lexer, is_word, Lexer};
+use crate::markup::{Markup, RangeArgument, Token, Whitespace};
+use crate::model::{Normalize, Span};
+use crate::query::{
+    ComparisonOperator, FilterExpression, LogicalOperator, Query, Segment, Selector,
+};
+
+const NO_WC: (Whitespace, Whitespace) = (Whitespace::Default, Whitespace::Default);
+
+impl Markup {
+    pub fn content(text: &str) -> Self {
+        Markup::Content {
+            text: text.to_owned(),
+            span: None,
+            trimmed_span: None,
+        }
+    }
+
+    pub fn raw(text: &str) -> Self {
+        Markup::Raw {
+            wc: (
+                Whitespace::Default,
+                Whitespace::Default,
+                Whitespace::Default,
+                Whitespace::Default,
+            ),
+            text: text.to_owned(),
+            span: None,
+        }
+    }
+
+    /// A comment with a single hash, like `{# text #}`. _text_ includes any
+    /// leading and trailing whitespace.
+    pub fn comment(text: &str) -> Self {
+        Markup::Comment {
+            wc: NO_WC,
+            hashes: "#".to_owned(),
+            text: text.to_owned(),
+            span: None,
+        }
+    }
+
+    pub fn output(expression: Vec<Token>) -> Self {
+        Markup::Output {
+            wc: NO_WC,
+            expression,
+            span: None,
+        }
+    }
+
+    pub fn tag(name: &str, expression: Option<Vec<Token>>) -> Self {
+        Markup::Tag {
+            wc: NO_WC,
+            name: name.to_owned(),
+            expression,
+            span: None,
+        }
+    }
+
+    /// A `liquid` tag. _statements_ are `Markup::Tag` and `Markup::Comment`.
+    pub fn lines(statements: Vec<Markup>) -> Self {
+        Markup::Lines {
+            wc: NO_WC,
+            name: "liquid".to_owned(),
+            statements,
+            span: None,
+        }
+    }
+}
+
+impl Token {
+    pub fn word(value: &str) -> Self {
+        Token::Word {
+            value: value.to_owned(),
+            span: None,
+        }
+    }
+
+    pub fn string(value: &str) -> Self {
+        Token::StringLiteral {
+            value: value.to_owned(),
+            span: None,
+        }
+    }
+
+    pub fn integer(value: i64) -> Self {
+        Token::IntegerLiteral { value, span: None }
+    }
+
+    pub fn float(value: f64) -> Self {
+        Token::FloatLiteral { value, span: None }
+    }
+
+    pub fn query(path: Query) -> Self {
+        Token::Query { path, span: None }
+    }
+
+    pub fn range(start: RangeArgument, stop: RangeArgument) -> Self {
+        Token::RangeLiteral {
+            start,
+            stop,
+            span: None,
+        }
+    }
+
+    /// Return this token with its span set to _span_.
+    pub fn with_span(mut self, span: Span) -> Self {
+        *self.span_mut() = Some(span);
+        self
+    }
+}
+
+impl RangeArgument {
+    pub fn string(value: &str) -> Self {
+        RangeArgument::StringLiteral {
+            value: value.to_owned(),
+            span: None,
+        }
+    }
+
+    pub fn integer(value: i64) -> Self {
+        RangeArgument::IntegerLiteral { value, span: None }
+    }
+
+    pub fn float(value: f64) -> Self {
+        RangeArgument::FloatLiteral { value, span: None }
+    }
+
+    pub fn query(path: Query) -> Self {
+        RangeArgument::Query { path, span: None }
+    }
+}
+
+impl Query {
+    pub fn builder() -> QueryBuilder {
+        QueryBuilder::default()
+    }
+}
+
+/// Build a `Query` one segment at a time.
+///
+/// ```ignore
+/// let query = Query::builder().name("products").index(0).name("title").build()?;
+/// assert_eq!(query.to_string(), "$['products'][0]['title']");
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct QueryBuilder {
+    segments: Vec<Segment>,
+}
+
+impl QueryBuilder {
+    /// Append a child segment with _selectors_.
+    pub fn child(mut self, selectors: Vec<Selector>) -> Self {
+        self.segments.push(Segment::Child {
+            selectors,
+            span: None,
+        });
+        self
+    }
+
+    /// Append a descendant segment with _selectors_.
+    pub fn recursive(mut self, selectors: Vec<Selector>) -> Self {
+        self.segments.push(Segment::Recursive {
+            selectors,
+            span: None,
+        });
+        self
+    }
+
+    pub fn name(self, name: &str) -> Self {
+        self.child(vec![Selector::name(name)])
+    }
+
+    pub fn index(self, index: i64) -> Self {
+        self.child(vec![Selector::index(index)])
+    }
+
+    pub fn wild(self) -> Self {
+        self.child(vec![Selector::wild()])
+    }
+
+    pub fn filter(self, expression: FilterExpression) -> Self {
+        self.child(vec![Selector::filter(expression)])
+    }
+
+    /// Return the query, or an error if it is not valid with the default
+    /// lexer.
+    pub fn build(self) -> Result<Query, LiquidError> {
+        let query = Query {
+            segments: self.segments,
+        };
+        query.validate(default_lexer())?;
+        Ok(query)
+    }
+}
+
+impl Selector {
+    pub fn name(name: &str) -> Self {
+        Selector::Name {
+            name: name.to_owned(),
+            span: None,
+        }
+    }
+
+    pub fn index(index: i64) -> Self {
+        Selector::Index { index, span: None }
+    }
+
+    pub fn slice(start: Option<i64>, stop: Option<i64>, step: Option<i64>) -> Self {
+        Selector::Slice {
+            start,
+            stop,
+            step,
+            span: None,
+        }
+    }
+
+    pub fn wild() -> Self {
+        Selector::Wild { span: None }
+    }
+
+    pub fn filter(expression: FilterExpression) -> Self {
+        Selector::Filter {
+            expression: Box::new(expression),
+            span: None,
+        }
+    }
+
+    pub fn singular_query(query: Query) -> Self {
+        Selector::SingularQuery {
+            query: Box::new(query),
+            span: None,
+        }
+    }
+}
+
+impl FilterExpression {
+    pub fn boolean(value: bool) -> Self {
+        if value {
+            FilterExpression::True_ { span: None }
+        } else {
+            FilterExpression::False_ { span: None }
+        }
+    }
+
+    pub fn null() -> Self {
+        FilterExpression::Null { span: None }
+    }
+
+    pub fn string(value: &str) -> Self {
+        FilterExpression::StringLiteral {
+            value: value.to_owned(),
+            span: None,
+        }
+    }
+
+    pub fn int(value: i64) -> Self {
+        FilterExpression::Int { value, span: None }
+    }
+
+    pub fn float(value: f64) -> Self {
+        FilterExpression::Float { value, span: None }
+    }
+
+    /// A query relative to the current node, like `@.a`.
+    pub fn relative(query: Query) -> Self {
+        FilterExpression::RelativeQuery {
+            query: Box::new(query),
+            span: None,
+        }
+    }
+
+    /// A query relative to the root node, like `$.a`.
+    pub fn root(query: Query) -> Self {
+        FilterExpression::RootQuery {
+            query: Box::new(query),
+            span: None,
+        }
+    }
+
+    pub fn negate(expression: FilterExpression) -> Self {
+        FilterExpression::Not {
+            expression: Box::new(expression),
+            span: None,
+        }
+    }
+
+    pub fn and(left: FilterExpression, right: FilterExpression) -> Self {
+        Self::logical(left, LogicalOperator::And, right)
+    }
+
+    pub fn or(left: FilterExpression, right: FilterExpression) -> Self {
+        Self::logical(left, LogicalOperator::Or, right)
+    }
+
+    fn logical(left: FilterExpression, operator: LogicalOperator, right: FilterExpression) -> Self {
+        FilterExpression::Logical {
+            left: Box::new(left),
+            operator,
+            right: Box::new(right),
+            span: None,
+        }
+    }
+
+    pub fn compare(
+        left: FilterExpression,
+        operator: ComparisonOperator,
+        right: FilterExpression,
+    ) -> Self {
+        FilterExpression::Comparison {
+            left: Box::new(left),
+            operator,
+            right: Box::new(right),
+            span: None,
+        }
+    }
+
+    /// A call to function _name_. Calls to standard functions are checked
+    /// against their signatures. Calls to other functions are checked by
+    /// `validate()`, with a lexer that registers them.
+    pub fn function(name: &str, args: Vec<FilterExpression>) -> Result<Self, LiquidError> {
+        Self::function_with_span(name, args, None)
+    }
+
+    /// Like `function`, with a span. This is `FilterExpression.Function` in
+    /// Python.
+    pub(crate) fn function_with_span(
+        name: &str,
+        args: Vec<FilterExpression>,
+        span: Option<Span>,
+    ) -> Result<Self, LiquidError> {
+        let parser = &default_lexer().query_parser;
+        let args = match parser.functions.contains_key(name) {
+            true => parser.assert_well_typed(name, args)?,
+            false => args,
+        };
+        Ok(FilterExpression::Function {
+            name: name.to_owned(),
+            args,
+            span,
+        })
+    }
+}
+
+/// `FilterExpression.Function.__new__`. PyO3 generates constructors for enum
+/// variants that can't check their arguments, so this replaces the one for
+/// `Function` with `FilterExpression::function_with_span`.
+#[pyfunction]
+#[pyo3(signature = (_cls, name, args, span=None))]
+fn new_function(
+    _cls: &Bound<'_, PyType>,
+    name: &str,
+    args: Vec<FilterExpression>,
+    span: Option<Span>,
+) -> Result<FilterExpression, LiquidError> {
+    FilterExpression::function_with_span(name, args, span)
+}
+
+/// Replace constructors generated by PyO3 that can't check their arguments.
+pub(crate) fn add_constructors(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.getattr("FilterExpression")?
+        .getattr("Function")?
+        .setattr("__new__", wrap_pyfunction!(new_function, m)?)
+}
+
+/// Syntax tree nodes that can be checked for validity before writing them
+/// as source text.
+pub trait Validate {
+    /// Return an error if this node's source text would not be read back by
+    /// _lexer_ as the same node.
+    fn validate(&self, lexer: &Lexer) -> Result<(), LiquidError>;
+}
+
+/// Compare _node_ to _parsed_, the result of reading _source_ back, ignoring
+/// spans.
+fn check<T: Normalize + PartialEq>(
+    node: &T,
+    parsed: Option<T>,
+    source: &str,
+    what: &str,
+) -> Result<(), LiquidError> {
+    match parsed {
+        Some(parsed) if parsed.without_spans() == node.without_spans() => Ok(()),
+        _ => Err(LiquidError::syntax(format!(
+            "`{source}` does not read back as the same {what}"
+        ))),
+    }
+}
+
+/// Tokenize _source_, expecting a single markup node.
+fn read_markup(lexer: &Lexer, source: &str) -> Result<Option<Markup>, LiquidError> {
+    let mut tokens = lexer.tokenize(source)?;
+    match tokens.as_slice() {
+        [_, Markup::EOI {}] => Ok(tokens.drain(..).next()),
+        _ => Ok(None),
+    }
+}
+
+/// Parse _source_ as a JSONPath query, dropping the end of input segment.
+fn read_query(lexer: &Lexer, source: &str) -> Result<Query, LiquidError> {
+    let mut query = lexer.parse_jsonpath_query(source)?;
+    query.segments.retain(|s| !matches!(s, Segment::Eoi {}));
+    Ok(query)
+}
+
+/// Return _token_ as the lexer would produce it. Single word queries are
+/// tokenized as words.
+fn canonical_token(token: &Token) -> Token {
+    match token {
+        Token::Query { path, span } => match path.as_word() {
+            Some(value) if is_word(&value) => Token::Word { value, span: *span },
+            _ => token.clone(),
+        },
+        _ => token.clone(),
+    }
+}
+
+fn canonical_markup(markup: &Markup) -> Markup {
+    let mut markup = markup.clone();
+    match &mut markup {
+        Markup::Output { expression, .. }
+        | Markup::Tag {
+            expression: Some(expression),
+            ..
+        } => {
+            for token in expression.iter_mut() {
+                *token = canonical_token(token);
+            }
+        }
+        Markup::Lines { statements, .. } => {
+            for statement in statements.iter_mut() {
+                *statement = canonical_markup(statement);
+            }
+        }
+        _ => (),
+    }
+    markup
+}
+
+impl Validate for Markup {
+    fn validate(&self, lexer: &Lexer) -> Result<(), LiquidError> {
+        let source = self.to_string();
+        match self {
+            // Empty content is written as nothing at all.
+            Markup::EOI {} => return Ok(()),
+            Markup::Content { text, .. } if text.is_empty() => return Ok(()),
+            _ => (),
+        }
+        check(
+            &canonical_markup(self),
+            read_markup(lexer, &source)?,
+            &source,
+            "markup",
+        )
+    }
+}
+
+impl Validate for Token {
+    fn validate(&self, lexer: &Lexer) -> Result<(), LiquidError> {
+        let source = format!("{{{{ {self} }}}}");
+        let parsed = match read_markup(lexer, &source)? {
+            Some(Markup::Output { mut expression, .. }) if expression.len() == 1 => {
+                expression.pop()
+            }
+            _ => None,
+        };
+        check(&canonical_token(self), parsed, &self.to_string(), "token")
+    }
+}
+
+impl Validate for RangeArgument {
+    fn validate(&self, lexer: &Lexer) -> Result<(), LiquidError> {
+        let source = format!("{{{{ ({self}..0) }}}}");
+        let parsed = match read_markup(lexer, &source)? {
+            Some(Markup::Output { mut expression, .. }) if expression.len() == 1 => {
+                match expression.pop() {
+                    Some(Token::RangeLiteral { start, .. }) => Some(start),
+                    _ => None,
+                }
+            }
+            _ => None,
+        };
+        check(self, parsed, &self.to_string(), "range argument")
+    }
+}
+
+impl Validate for Query {
+    fn validate(&self, lexer: &Lexer) -> Result<(), LiquidError> {
+        let source = self.to_string();
+        let mut query = self.clone();
+        query.segments.retain(|s| !matches!(s, Segment::Eoi {}));
+        check(&query, Some(read_query(lexer, &source)?), &source, "query")
+    }
+}
+
+impl Validate for Segment {
+    fn validate(&self, lexer: &Lexer) -> Result<(), LiquidError> {
+        let source = self.to_string();
+        let mut query = read_query(lexer, &format!("${source}"))?;
+        let parsed = (query.segments.len() == 1).then(|| query.segments.remove(0));
+        check(self, parsed, &source, "segment")
+    }
+}
+
+impl Validate for Selector {
+    fn validate(&self, lexer: &Lexer) -> Result<(), LiquidError> {
+        let source = self.to_string();
+        let query = read_query(lexer, &format!("$[{source}]"))?;
+        let parsed = match query.segments.as_slice() {
+            [Segment::Child { selectors, .. }] if selectors.len() == 1 => {
+                Some(selectors[0].clone())
+            }
+            _ => None,
+        };
+        check(self, parsed, &source, "selector")
+    }
+}
+
+impl Validate for FilterExpression {
+    fn validate(&self, lexer: &Lexer) -> Result<(), LiquidError> {
+        let source = self.to_string();
+        let query = read_query(lexer, &format!("$[?{source}]"))?;
+        let parsed = match query.segments.as_slice() {
+            [Segment::Child { selectors, .. }] => match selectors.as_slice() {
+                [Selector::Filter { expression, .. }] => Some(*expression.clone()),
+                _ => None,
+            },
+            _ => None,
+        };
+        check(self, parsed, &source, "filter expression")
+    }
+}
+
+/// Return template source text for _tokens_, without validating them.
+pub fn source_text(tokens: &[Markup]) -> String {
+    tokens.iter().map(|markup| markup.to_string()).collect()
+}
+
+/// Return _tokens_ as the lexer would read back their source text. Empty
+/// content and end of input markers are dropped and adjacent content is
+/// joined.
+fn canonical_markup_stream(tokens: &[Markup]) -> Vec<Markup> {
+    let mut stream: Vec<Markup> = Vec::new();
+    for markup in tokens {
+        match markup {
+            Markup::EOI {} => (),
+            Markup::Content { text, .. } => match stream.last_mut() {
+                _ if text.is_empty() => (),
+                Some(Markup::Content { text: last, .. }) => last.push_str(text),
+                _ => stream.push(Markup::content(text)),
+            },
+            _ => stream.push(canonical_markup(markup).without_spans()),
+        }
+    }
+    stream
+}
+
+/// Return template source text for _tokens_, or an error if any of them is
+/// not valid with _lexer_, or if together they don't read back as the same
+/// markup. Content ending in `{` followed by an output statement, for
+/// example, is valid on its own but not as part of a template.
+pub fn to_source(tokens: &[Markup], lexer: &Lexer) -> Result<String, LiquidError> {
+    for markup in tokens {
+        markup.validate(lexer)?;
+    }
+
+    let source = source_text(tokens);
+    match lexer.tokenize(&source) {
+        Ok(parsed) if canonical_markup_stream(&parsed) == canonical_markup_stream(tokens) => {
+            Ok(source)
+        }
+        _ => Err(LiquidError::syntax(format!(
+            "`{source}` does not read back as the same markup"
+        ))),
+    }
+}
@@ -0,0 +1,367 @@
+//! A thread-safe LRU cache of tokenized templates
+//!
+
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{BTreeMap, HashMap};
+use std::hash::{Hash, Hasher};
+use std::ops::Deref;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use pyo3::prelude::*;
+use pyo3::sync::GILOnceCell;
+use pyo3::types::{PyList, PyTuple};
+
+use crate::errors::LiquidError;
+use crate::lexer::{default_lexer, Lexer};
+use crate::markup::Markup;
+
+/// Cache hit and miss counts, and the current size of a cache.
+#[pyclass(frozen)]
+#[derive(Debug, Clone, Default)]
+pub struct CacheStats {
+    #[pyo3(get)]
+    pub hits: u64,
+    #[pyo3(get)]
+    pub misses: u64,
+    #[pyo3(get)]
+    pub evictions: u64,
+    #[pyo3(get)]
+    pub entries: usize,
+    /// The total length, in bytes, of the source text of cached templates.
+    #[pyo3(get)]
+    pub bytes: usize,
+}
+
+/// Cached tokens, shared by everyone who looks them up.
+pub struct CachedTokens {
+    tokens: Vec<Markup>,
+    // Built from `tokens` the first time they're returned to Python, so later
+    // hits from Python don't copy them again.
+    py_tokens: GILOnceCell<Py<PyTuple>>,
+}
+
+impl CachedTokens {
+    fn new(tokens: Vec<Markup>) -> Self {
+        Self {
+            tokens,
+            py_tokens: GILOnceCell::new(),
+        }
+    }
+
+    /// Return these tokens as a new Python list of shared markup objects.
+    pub fn to_py<'py>(&self, py: Python<'py>) -> Bound<'py, PyList> {
+        let tokens = self.py_tokens.get_or_init(py, || {
+            PyTuple::new_bound(py, self.tokens.iter().map(|t| t.clone().into_py(py))).unbind()
+        });
+        PyList::new_bound(py, tokens.bind(py))
+    }
+}
+
+impl Deref for CachedTokens {
+    type Target = [Markup];
+
+    fn deref(&self) -> &Self::Target {
+        &self.tokens
+    }
+}
+
+struct Entry<V> {
+    value: V,
+    bytes: usize,
+    tick: u64,
+}
+
+struct State<V> {
+    entries: HashMap<String, Entry<V>>,
+    recent: BTreeMap<u64, String>,
+    tick: u64,
+    stats: CacheStats,
+}
+
+/// A thread-safe LRU map from names to values, holding at most _capacity_
+/// entries, and at most _max_bytes_ bytes if given. Each entry's size is
+/// given when it is inserted.
+pub struct Lru<V> {
+    capacity: usize,
+    max_bytes: Option<usize>,
+    state: Mutex<State<V>>,
+}
+
+impl<V: Clone> Lru<V> {
+    pub fn new(capacity: usize, max_bytes: Option<usize>) -> Self {
+        Self {
+            capacity,
+            max_bytes,
+            state: Mutex::new(State {
+                entries: HashMap::new(),
+                recent: BTreeMap::new(),
+                tick: 0,
+                stats: CacheStats::default(),
+            }),
+        }
+    }
+
+    /// Return the value for _name_ if there is one and _fresh_ accepts it,
+    /// marking it as recently used. Anything else is a miss.
+    pub fn get(&self, name: &str, fresh: impl FnOnce(&V) -> bool) -> Option<V> {
+        let mut state = self.lock();
+        let state = &mut *state;
+
+        match state.entries.get_mut(name) {
+            Some(entry) if fresh(&entry.value) => {
+                state.tick += 1;
+                state.recent.remove(&entry.tick);
+                state.recent.insert(state.tick, name.to_owned());
+                entry.tick = state.tick;
+                state.stats.hits += 1;
+                Some(entry.value.clone())
+            }
+            _ => {
+                state.stats.misses += 1;
+                None
+            }
+        }
+    }
+
+    /// Replace any value for _name_ with _value_, evicting least recently
+    /// used entries if necessary. Values bigger than the byte limit replace
+    /// the old value without being stored.
+    pub fn insert(&self, name: &str, value: V, bytes: usize) {
+        let mut state = self.lock();
+        state.remove(name);
+
+        if self.capacity == 0 || self.max_bytes.is_some_and(|max| bytes > max) {
+            return;
+        }
+
+        while state.stats.entries >= self.capacity
+            || self
+                .max_bytes
+                .is_some_and(|max| state.stats.bytes + bytes > max)
+        {
+            let Some((_, oldest)) = state.recent.pop_first() else {
+                break;
+            };
+            state.remove(&oldest);
+            state.stats.evictions += 1;
+        }
+
+        state.tick += 1;
+        let tick = state.tick;
+        state.recent.insert(tick, name.to_owned());
+        state
+            .entries
+            .insert(name.to_owned(), Entry { value, bytes, tick });
+        state.stats.entries += 1;
+        state.stats.bytes += bytes;
+    }
+
+    /// Remove _name_. Return `true` if it was cached.
+    pub fn remove(&self, name: &str) -> bool {
+        self.lock().remove(name)
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.lock().entries.contains_key(name)
+    }
+
+    /// Remove every entry. Statistics are not reset.
+    pub fn clear(&self) {
+        let mut state = self.lock();
+        state.entries.clear();
+        state.recent.clear();
+        state.stats.entries = 0;
+        state.stats.bytes = 0;
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        self.lock().stats.clone()
+    }
+
+    fn lock(&self) -> MutexGuard<'_, State<V>> {
+        // Nothing that happens while the lock is held can panic part way
+        // through updating state, so a poisoned lock is safe to reuse.
+        self.state.lock().unwrap_or_else(|err| err.into_inner())
+    }
+}
+
+impl<V> State<V> {
+    fn remove(&mut self, name: &str) -> bool {
+        match self.entries.remove(name) {
+            Some(entry) => {
+                self.recent.remove(&entry.tick);
+                self.stats.entries -= 1;
+                self.stats.bytes -= entry.bytes;
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+/// An LRU cache of tokenized templates, keyed by template name.
+///
+/// There is at most one entry per template name. Each entry keeps the source
+/// text it was tokenized from and a fingerprint of the lexer that tokenized
+/// it. Looking up a name with source text or a lexer configuration that has
+/// changed is a miss, and inserting it replaces the stale entry.
+#[pyclass(frozen)]
+pub struct TemplateCache {
+    lru: Lru<Cached>,
+}
+
+#[derive(Clone)]
+struct Cached {
+    source: Arc<str>,
+    lexer: u64,
+    tokens: Arc<CachedTokens>,
+}
+
+impl TemplateCache {
+    /// Create a cache holding at most _capacity_ templates, and at most
+    /// _max_bytes_ bytes of template source text if given.
+    pub fn new(capacity: usize, max_bytes: Option<usize>) -> Self {
+        Self {
+            lru: Lru::new(capacity, max_bytes),
+        }
+    }
+
+    /// Return a fingerprint of the options of _lexer_ that change how
+    /// templates are tokenized.
+    pub fn fingerprint(lexer: &Lexer) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        lexer.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    /// Return tokens for template _name_ if they are cached and were
+    /// produced from _source_ by a lexer configured like _lexer_.
+    pub fn get(&self, name: &str, source: &str, lexer: &Lexer) -> Option<Arc<CachedTokens>> {
+        let fingerprint = Self::fingerprint(lexer);
+        self.lru
+            .get(name, |cached| {
+                cached.lexer == fingerprint && *cached.source == *source
+            })
+            .map(|cached| cached.tokens)
+    }
+
+    /// Cache _tokens_ for template _name_ with source text _source_,
+    /// tokenized by _lexer_, evicting least recently used templates if
+    /// necessary.
+    ///
+    /// Templates with source text longer than the cache's byte limit are
+    /// not cached.
+    pub fn insert(
+        &self,
+        name: &str,
+        source: &str,
+        lexer: &Lexer,
+        tokens: Vec<Markup>,
+    ) -> Arc<CachedTokens> {
+        let tokens = Arc::new(CachedTokens::new(tokens));
+        let cached = Cached {
+            source: Arc::from(source),
+            lexer: Self::fingerprint(lexer),
+            tokens: tokens.clone(),
+        };
+        self.lru.insert(name, cached, source.len());
+        tokens
+    }
+
+    /// Return cached tokens for template _name_, or tokenize _source_ with
+    /// _lexer_ and cache the result.
+    pub fn get_or_tokenize(
+        &self,
+        name: &str,
+        source: &str,
+        lexer: &Lexer,
+    ) -> Result<Arc<CachedTokens>, LiquidError> {
+        match self.get(name, source, lexer) {
+            Some(tokens) => Ok(tokens),
+            None => Ok(self.insert(name, source, lexer, lexer.tokenize(source)?)),
+        }
+    }
+
+    /// Remove template _name_ from the cache. Return `true` if it was cached.
+    pub fn invalidate(&self, name: &str) -> bool {
+        self.lru.remove(name)
+    }
+
+    /// Remove all templates from the cache. Statistics are not reset.
+    pub fn clear(&self) {
+        self.lru.clear()
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        self.lru.stats()
+    }
+
+    pub fn len(&self) -> usize {
+        self.stats().entries
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+#[pymethods]
+impl TemplateCache {
+    #[new]
+    #[pyo3(signature = (capacity=300, *, max_bytes=None))]
+    fn py_new(capacity: usize, max_bytes: Option<usize>) -> Self {
+        TemplateCache::new(capacity, max_bytes)
+    }
+
+    /// Return cached tokens for template _name_ as a list of markup shared
+    /// with other callers, or `None` if they are not cached.
+    #[pyo3(name = "get", signature = (name, source, *, lexer=None))]
+    fn py_get<'py>(
+        &self,
+        py: Python<'py>,
+        name: &str,
+        source: &str,
+        lexer: Option<PyRef<'_, Lexer>>,
+    ) -> Option<Bound<'py, PyList>> {
+        let lexer = lexer.as_deref().unwrap_or(default_lexer());
+        self.get(name, source, lexer).map(|tokens| tokens.to_py(py))
+    }
+
+    /// Return cached tokens for template _name_, or tokenize _source_ with
+    /// _lexer_ and cache the result. The GIL is released while tokenizing.
+    #[pyo3(signature = (name, source, *, lexer=None))]
+    fn tokenize<'py>(
+        &self,
+        py: Python<'py>,
+        name: &str,
+        source: &str,
+        lexer: Option<PyRef<'_, Lexer>>,
+    ) -> PyResult<Bound<'py, PyList>> {
+        let lexer = lexer.as_deref().unwrap_or(default_lexer());
+        let tokens = py.allow_threads(|| self.get_or_tokenize(name, source, lexer))?;
+        Ok(tokens.to_py(py))
+    }
+
+    #[pyo3(name = "invalidate")]
+    fn py_invalidate(&self, name: &str) -> bool {
+        self.invalidate(name)
+    }
+
+    #[pyo3(name = "clear")]
+    fn py_clear(&self) {
+        self.clear()
+    }
+
+    #[pyo3(name = "stats")]
+    fn py_stats(&self) -> CacheStats {
+        self.stats()
+    }
+
+    fn __len__(&self) -> usize {
+        self.len()
+    }
+
+    fn __contains__(&self, name: &str) -> bool {
+        self.lru.contains(name)
+    }
+}
@@ -0,0 +1,353 @@
+//! Compiled JSONPath queries for evaluating against Python data
+//!
+//! `compile` parses a query once and keeps it in a shared LRU cache keyed by
+//! query text, so compiling the same path again is a cache hit. A
+//! `CompiledQuery` reads Python dicts, lists and scalars in place, or streams
+//! a JSON document from a file. `find_file` loads a JSON, YAML, TOML, CBOR
+//! or MessagePack file and evaluates the query against it directly.
+
+use std::fs::File;
+use std::io::Read;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use lazy_static::lazy_static;
+use pyo3::prelude::*;
+use pyo3::types::{PyBytes, PyDict, PyList};
+
+use crate::cache::{CacheStats, Lru};
+use crate::errors::LiquidError;
+use crate::eval::{find, find_iter, JsonLike, Kind, Matches, Node, PyValue};
+use crate::formats::Document;
+use crate::lexer::default_lexer;
+use crate::query::Query;
+use crate::stream::{stream, StreamMatches};
+
+lazy_static! {
+    static ref DEFAULT_QUERY_CACHE: QueryCache = QueryCache::new(1024);
+}
+
+/// Return the shared cache used by `compile`.
+pub fn default_query_cache() -> &'static QueryCache {
+    &DEFAULT_QUERY_CACHE
+}
+
+/// A thread-safe LRU cache of parsed JSONPath queries, keyed by query text.
+pub struct QueryCache {
+    lru: Lru<Arc<Query>>,
+}
+
+impl QueryCache {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            lru: Lru::new(capacity, None),
+        }
+    }
+
+    /// Return the parsed query for _path_, parsing and caching it if it is
+    /// not already cached. Paths that fail to parse are not cached.
+    pub fn get_or_parse(&self, path: &str) -> Result<Arc<Query>, LiquidError> {
+        if let Some(query) = self.lru.get(path, |_| true) {
+            return Ok(query);
+        }
+
+        // Parse without holding the lock. Two threads might both parse the
+        // same path, in which case the second insert wins.
+        let query = Arc::new(default_lexer().parse_jsonpath_query(path)?);
+        self.lru.insert(path, query.clone(), path.len());
+        Ok(query)
+    }
+
+    /// Remove all queries from the cache. Statistics are not reset.
+    pub fn clear(&self) {
+        self.lru.clear()
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        self.lru.stats()
+    }
+}
+
+/// A parsed JSONPath query, ready to evaluate against Python data.
+#[pyclass(frozen, module = "_liquid2")]
+pub struct CompiledQuery {
+    query: Arc<Query>,
+}
+
+/// A value matched by a compiled query, and its normalized path.
+#[pyclass(frozen, module = "_liquid2")]
+pub struct QueryMatch {
+    #[pyo3(get)]
+    value: PyObject,
+    #[pyo3(get)]
+    path: Query,
+}
+
+impl QueryMatch {
+    fn new(node: Node<Bound<'_, PyAny>>) -> Self {
+        Self {
+            path: node.location.to_query(),
+            value: node.value.unbind(),
+        }
+    }
+}
+
+#[pymethods]
+impl QueryMatch {
+    fn __repr__(&self, py: Python<'_>) -> String {
+        format!(
+            "QueryMatch(path={}, value={})",
+            self.path,
+            self.value.bind(py)
+        )
+    }
+}
+
+/// A lazy iterator over the matches of a compiled query.
+#[pyclass(unsendable, module = "_liquid2")]
+pub struct QueryMatchIterator {
+    matches: Matches<PyValue>,
+}
+
+#[pymethods]
+impl QueryMatchIterator {
+    fn __iter__(slf: PyRef<'_, Self>) -> PyRef<'_, Self> {
+        slf
+    }
+
+    fn __next__(&mut self, py: Python<'_>) -> Result<Option<QueryMatch>, LiquidError> {
+        self.matches.next().transpose().map(|node| {
+            node.map(|node| {
+                QueryMatch::new(Node {
+                    value: node.value.0.into_bound(py),
+                    location: node.location,
+                })
+            })
+        })
+    }
+}
+
+/// An iterator over matches of a compiled query in a JSON document that is
+/// read as the iterator is consumed.
+#[pyclass(unsendable, module = "_liquid2")]
+pub struct StreamIterator {
+    matches: StreamMatches<Box<dyn Read>>,
+}
+
+#[pymethods]
+impl StreamIterator {
+    fn __iter__(slf: PyRef<'_, Self>) -> PyRef<'_, Self> {
+        slf
+    }
+
+    fn __next__(&mut self, py: Python<'_>) -> Result<Option<QueryMatch>, LiquidError> {
+        self.matches.next().transpose().map(|item| {
+            item.map(|(path, value)| QueryMatch {
+                value: to_python(py, &&value),
+                path,
+            })
+        })
+    }
+}
+
+// Reads bytes from a Python binary file object.
+struct PyFileReader(PyObject);
+
+impl Read for PyFileReader {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        Python::with_gil(|py| {
+            let data = self
+                .0
+                .call_method1(py, "read", (buf.len(),))
+                .map_err(std::io::Error::other)?;
+            let data = data
+                .downcast_bound::<PyBytes>(py)
+                .map_err(|_| std::io::Error::other("expected a file opened in binary mode"))?;
+            let data = data.as_bytes();
+            let length = data.len().min(buf.len());
+            buf[..length].copy_from_slice(&data[..length]);
+            Ok(length)
+        })
+    }
+}
+
+/// Return a Python copy of _value_.
+pub fn to_python<V: JsonLike>(py: Python<'_>, value: &V) -> PyObject {
+    match value.kind() {
+        Kind::Null | Kind::Other => py.None(),
+        Kind::Bool(value) => value.into_py(py),
+        Kind::Int(value) => value.into_py(py),
+        Kind::Float(value) => value.into_py(py),
+        Kind::String(value) => value.into_py(py),
+        Kind::DateTime(value) => datetime(py, &value).unwrap_or_else(|| value.into_py(py)),
+        Kind::Bytes => value
+            .with_bytes(|bytes| PyBytes::new_bound(py, bytes).into_any().unbind())
+            .unwrap_or_else(|| py.None()),
+        Kind::Array(_) => {
+            PyList::new_bound(py, value.items().iter().map(|item| to_python(py, item)))
+                .into_any()
+                .unbind()
+        }
+        Kind::Object => {
+            let members = PyDict::new_bound(py);
+            for (name, value) in value.members() {
+                // Setting a string key on a new dict can't fail.
+                let _ = members.set_item(name, to_python(py, &value));
+            }
+            members.into_any().unbind()
+        }
+    }
+}
+
+// Converts RFC 3339 _text_ to a `datetime`, `date` or `time`, or returns
+// `None` if Python can't read it. Before Python 3.11, `fromisoformat` accepts
+// neither `Z` nor fractions of a second that aren't three or six digits.
+fn datetime(py: Python<'_>, text: &str) -> Option<PyObject> {
+    let class = if text.contains(['T', 't', ' ']) {
+        "datetime"
+    } else if text.contains(':') {
+        "time"
+    } else {
+        "date"
+    };
+
+    let mut iso = text.replace(['t', ' '], "T");
+    if iso.ends_with(['Z', 'z']) {
+        iso.pop();
+        iso.push_str("+00:00");
+    }
+
+    if let Some(start) = iso.find('.') {
+        let end = iso[start + 1..]
+            .find(|c: char| !c.is_ascii_digit())
+            .map_or(iso.len(), |index| start + 1 + index);
+        let fraction = format!("{:0<6.6}", &iso[start + 1..end]);
+        iso.replace_range(start + 1..end, &fraction);
+    }
+
+    py.import_bound("datetime")
+        .and_then(|module| module.getattr(class)?.call_method1("fromisoformat", (iso,)))
+        .ok()
+        .map(Bound::unbind)
+}
+
+#[pymethods]
+impl CompiledQuery {
+    #[new]
+    fn py_new(query: Query) -> Self {
+        Self {
+            query: Arc::new(query),
+        }
+    }
+
+    #[getter]
+    fn query(&self) -> Query {
+        (*self.query).clone()
+    }
+
+    /// Return a list of every value matched by this query in _data_.
+    fn findall<'py>(
+        &self,
+        data: &Bound<'py, PyAny>,
+    ) -> Result<Vec<Bound<'py, PyAny>>, LiquidError> {
+        Ok(find(&self.query, data)?
+            .into_iter()
+            .map(|node| node.value)
+            .collect())
+    }
+
+    /// Return an iterator over matches of this query in _data_. Matches are
+    /// found as the iterator is consumed.
+    fn finditer(&self, data: &Bound<'_, PyAny>) -> QueryMatchIterator {
+        QueryMatchIterator {
+            matches: find_iter(self.query.clone(), PyValue::from(data.clone())),
+        }
+    }
+
+    /// Return the first match of this query in _data_, or `None` if there are
+    /// no matches.
+    fn first(&self, data: &Bound<'_, PyAny>) -> Result<Option<QueryMatch>, LiquidError> {
+        find_iter(self.query.clone(), data.clone())
+            .next()
+            .transpose()
+            .map(|node| node.map(QueryMatch::new))
+    }
+
+    /// Return `True` if this query matches at least one value in _data_.
+    #[pyo3(name = "match")]
+    fn py_match(&self, data: &Bound<'_, PyAny>) -> Result<bool, LiquidError> {
+        Ok(self.first(data)?.is_some())
+    }
+
+    /// Return an iterator over matches of this query in a JSON document,
+    /// read from _source_ as it is consumed. _source_ is a file path or a
+    /// binary file object. Raise an error if this query can't be evaluated
+    /// without reading the whole document first.
+    ///
+    /// Unlike `finditer` on parsed data, every occurrence of a duplicate
+    /// member name is matched, and integers that don't fit in a signed 64 bit
+    /// integer are an error instead of a float.
+    fn stream(&self, source: &Bound<'_, PyAny>) -> Result<StreamIterator, LiquidError> {
+        let reader: Box<dyn Read> = if source.hasattr("read").unwrap_or(false) {
+            Box::new(PyFileReader(source.clone().unbind()))
+        } else {
+            let path: PathBuf = source
+                .extract()
+                .map_err(|_| LiquidError::typ("expected a path or a binary file".to_owned()))?;
+            Box::new(File::open(path)?)
+        };
+
+        Ok(StreamIterator {
+            matches: stream(&self.query, reader)?,
+        })
+    }
+
+    /// Return matches of this query in the JSON, YAML, TOML, CBOR or
+    /// MessagePack file at _path_. If _format_ is not given, it is guessed
+    /// from the file's extension.
+    #[pyo3(signature = (path, *, format=None))]
+    fn find_file(
+        &self,
+        py: Python<'_>,
+        path: PathBuf,
+        format: Option<&str>,
+    ) -> Result<Vec<QueryMatch>, LiquidError> {
+        let format = format.map(str::parse).transpose()?;
+        let document = py.allow_threads(|| Document::load(&path, format))?;
+        Ok(document
+            .find_python(py, &self.query)?
+            .into_iter()
+            .map(|(path, value)| QueryMatch { value, path })
+            .collect())
+    }
+
+    fn __str__(&self) -> String {
+        self.query.to_string()
+    }
+
+    fn __repr__(&self) -> String {
+        format!("CompiledQuery({:?})", self.query.to_string())
+    }
+}
+
+/// Parse JSONPath query _path_ for evaluating against Python data. Parsed
+/// queries are cached by path.
+#[pyfunction]
+#[pyo3(name = "compile")]
+pub fn py_compile(path: &str) -> Result<CompiledQuery, LiquidError> {
+    Ok(CompiledQuery {
+        query: default_query_cache().get_or_parse(path)?,
+    })
+}
+
+/// Return hit, miss and eviction counts for the cache used by `compile`.
+#[pyfunction]
+pub fn query_cache_stats() -> CacheStats {
+    default_query_cache().stats()
+}
+
+/// Remove all queries from the cache used by `compile`.
+#[pyfunction]
+pub fn clear_query_cache() {
+    default_query_cache().clear()
+}
@@ -0,0 +1,459 @@
+//! Template dependencies from `include`, `render` and `extends` tags
+//!
+
+use std::collections::{btree_set, BTreeMap, BTreeSet, HashMap, HashSet, VecDeque};
+use std::fs;
+use std::path::Path;
+
+use pyo3::prelude::*;
+
+use crate::errors::LiquidError;
+use crate::lexer::{default_lexer, Lexer};
+use crate::markup::{Markup, Token};
+use crate::model::Span;
+
+/// Names of tags that load another template by name.
+pub const DEPENDENCY_TAGS: [&str; 3] = ["include", "render", "extends"];
+
+/// A reference from one template to another.
+#[pyclass(frozen)]
+#[derive(Debug, Clone)]
+pub struct Dependency {
+    /// The name of the tag that loads the template.
+    #[pyo3(get)]
+    pub tag: String,
+    /// The name of the loaded template, or `None` if the name is not a
+    /// string literal and can only be known at render time.
+    #[pyo3(get)]
+    pub name: Option<String>,
+    /// The span of the template name expression, or of the tag if it has no
+    /// expression.
+    #[pyo3(get)]
+    pub span: Option<Span>,
+}
+
+/// Return dependencies in _tokens_, in order of appearance.
+pub fn dependencies(tokens: &[Markup]) -> Vec<Dependency> {
+    let mut deps = Vec::new();
+    collect_dependencies(tokens, &mut deps);
+    deps
+}
+
+fn collect_dependencies(tokens: &[Markup], deps: &mut Vec<Dependency>) {
+    for token in tokens {
+        match token {
+            Markup::Tag {
+                name,
+                expression,
+                span,
+                ..
+            } if DEPENDENCY_TAGS.contains(&name.as_str()) => {
+                let dependency = match expression.as_deref().and_then(|e| e.first()) {
+                    Some(Token::StringLiteral { value, span }) => Dependency {
+                        tag: name.to_owned(),
+                        name: Some(value.to_owned()),
+                        span: *span,
+                    },
+                    Some(token) => Dependency {
+                        tag: name.to_owned(),
+                        name: None,
+                        span: token.span(),
+                    },
+                    None => Dependency {
+                        tag: name.to_owned(),
+                        name: None,
+                        span: *span,
+                    },
+                };
+                deps.push(dependency);
+            }
+            Markup::Lines { statements, .. } => collect_dependencies(statements, deps),
+            _ => (),
+        }
+    }
+}
+
+/// A directed graph of templates and the templates they load.
+///
+/// Template names used in tags are matched against names in the graph
+/// as written, or with _extension_ appended if given. _extension_ may be
+/// given with or without its leading `.`.
+#[pyclass]
+#[derive(Debug, Clone, Default)]
+pub struct DependencyGraph {
+    extension: Option<String>,
+    templates: BTreeMap<String, Vec<Dependency>>,
+    errors: Vec<(String, String)>,
+}
+
+impl DependencyGraph {
+    pub fn new(extension: Option<String>) -> Self {
+        Self {
+            extension: extension.map(|ext| match ext.starts_with('.') {
+                true => ext,
+                false => format!(".{ext}"),
+            }),
+            templates: BTreeMap::new(),
+            errors: Vec::new(),
+        }
+    }
+
+    /// Build a graph from every file under _path_, or every file with
+    /// _extension_ if given, tokenized with _lexer_. Templates are named by
+    /// their path relative to _path_, using `/` as a separator.
+    ///
+    /// Hidden files and directories, those with names starting with a `.`,
+    /// are skipped, and symbolic links to directories are not followed. Files
+    /// that can't be read or tokenized are left out of the graph and reported
+    /// by `errors()`.
+    pub fn from_directory(
+        path: &Path,
+        extension: Option<String>,
+        lexer: &Lexer,
+    ) -> Result<Self, LiquidError> {
+        let mut graph = DependencyGraph::new(extension);
+        let mut pending = vec![path.to_path_buf()];
+        let name = |entry_path: &Path| {
+            entry_path
+                .strip_prefix(path)
+                .unwrap_or(entry_path)
+                .components()
+                .map(|c| c.as_os_str().to_string_lossy())
+                .collect::<Vec<_>>()
+                .join("/")
+        };
+
+        while let Some(dir) = pending.pop() {
+            let entries = match fs::read_dir(&dir) {
+                Ok(entries) => entries,
+                Err(err) if dir == path => return Err(err.into()),
+                Err(err) => {
+                    graph.errors.push((name(&dir), err.to_string()));
+                    continue;
+                }
+            };
+
+            for entry in entries {
+                let (entry_path, file_type) =
+                    match entry.and_then(|e| Ok((e.path(), e.file_type()?))) {
+                        Ok(entry) => entry,
+                        Err(err) => {
+                            graph.errors.push((name(&dir), err.to_string()));
+                            continue;
+                        }
+                    };
+
+                if entry_path
+                    .file_name()
+                    .is_some_and(|n| n.to_string_lossy().starts_with('.'))
+                {
+                    continue;
+                }
+
+                if file_type.is_dir() {
+                    pending.push(entry_path);
+                    continue;
+                }
+
+                // `file_type` doesn't follow symbolic links, `is_file` does.
+                if !entry_path.is_file() || !graph.has_extension(&entry_path) {
+                    continue;
+                }
+
+                let relative = name(&entry_path);
+                match fs::read_to_string(&entry_path) {
+                    Ok(source) => match lexer.tokenize(&source) {
+                        Ok(tokens) => graph.add_template(&relative, &tokens),
+                        Err(err) => graph.errors.push((relative, err.to_string())),
+                    },
+                    Err(err) => graph.errors.push((relative, err.to_string())),
+                }
+            }
+        }
+
+        graph.errors.sort();
+        Ok(graph)
+    }
+
+    fn has_extension(&self, path: &Path) -> bool {
+        self.extension
+            .as_deref()
+            .is_none_or(|ext| path.extension().is_some_and(|e| e == &ext[1..]))
+    }
+
+    /// Return names of files that were skipped by `from_directory()` because
+    /// they could not be read or tokenized, with a description of the error.
+    pub fn errors(&self) -> &[(String, String)] {
+        &self.errors
+    }
+
+    /// Add or replace template _name_ and its dependencies.
+    pub fn add_template(&mut self, name: &str, tokens: &[Markup]) {
+        self.templates.insert(name.to_owned(), dependencies(tokens));
+    }
+
+    /// Remove template _name_ from the graph. Dependencies on _name_ from
+    /// other templates are kept.
+    pub fn remove_template(&mut self, name: &str) -> bool {
+        self.templates.remove(name).is_some()
+    }
+
+    /// Return the name of the template in the graph that _name_ refers to.
+    pub fn resolve(&self, name: &str) -> Option<&str> {
+        if let Some((key, _)) = self.templates.get_key_value(name) {
+            return Some(key);
+        }
+
+        let ext = self.extension.as_deref()?;
+        self.templates
+            .get_key_value(&format!("{name}{ext}"))
+            .map(|(key, _)| key.as_str())
+    }
+
+    /// Return names of templates in the graph, in sorted order.
+    pub fn templates(&self) -> impl Iterator<Item = &str> {
+        self.templates.keys().map(|k| k.as_str())
+    }
+
+    /// Return all dependencies of template _name_, including dynamic ones.
+    pub fn dependencies(&self, name: &str) -> &[Dependency] {
+        self.resolve(name)
+            .and_then(|key| self.templates.get(key))
+            .map(|deps| deps.as_slice())
+            .unwrap_or_default()
+    }
+
+    /// Return dependencies that have a dynamic name, with the name of the
+    /// template they were found in.
+    pub fn dynamic(&self) -> Vec<(&str, &Dependency)> {
+        self.templates
+            .iter()
+            .flat_map(|(name, deps)| {
+                deps.iter()
+                    .filter(|d| d.name.is_none())
+                    .map(move |d| (name.as_str(), d))
+            })
+            .collect()
+    }
+
+    /// Return names of static dependencies that are not in the graph, with
+    /// the name of the template they were found in.
+    pub fn missing(&self) -> Vec<(&str, &Dependency)> {
+        self.templates
+            .iter()
+            .flat_map(|(name, deps)| {
+                deps.iter()
+                    .filter(|d| d.name.as_deref().is_some_and(|n| self.resolve(n).is_none()))
+                    .map(move |d| (name.as_str(), d))
+            })
+            .collect()
+    }
+
+    /// Return names of templates that _name_ loads directly and that are in
+    /// the graph.
+    fn edges(&self, name: &str) -> BTreeSet<&str> {
+        self.dependencies(name)
+            .iter()
+            .filter_map(|d| d.name.as_deref().and_then(|n| self.resolve(n)))
+            .collect()
+    }
+
+    /// Return groups of templates that depend on each other, directly or
+    /// indirectly. Each group is sorted, and a template that loads itself is
+    /// a group of one.
+    pub fn cycles(&self) -> Vec<Vec<String>> {
+        let mut tarjan = Tarjan {
+            graph: self,
+            index: 0,
+            indices: HashMap::new(),
+            low_links: HashMap::new(),
+            stack: Vec::new(),
+            on_stack: HashSet::new(),
+            components: Vec::new(),
+        };
+
+        for name in self.templates() {
+            if !tarjan.indices.contains_key(name) {
+                tarjan.connect(name);
+            }
+        }
+
+        let mut cycles: Vec<Vec<String>> = tarjan
+            .components
+            .into_iter()
+            .filter(|c| c.len() > 1 || self.edges(c[0]).contains(c[0]))
+            .map(|c| {
+                let mut names: Vec<String> = c.into_iter().map(|n| n.to_owned()).collect();
+                names.sort();
+                names
+            })
+            .collect();
+
+        cycles.sort();
+        cycles
+    }
+
+    /// Return names of templates that load _name_, directly or indirectly,
+    /// in sorted order. These are the templates that need to be rebuilt
+    /// when _name_ changes.
+    pub fn affected(&self, name: &str) -> Vec<String> {
+        let mut dependents: HashMap<&str, Vec<&str>> = HashMap::new();
+        for template in self.templates() {
+            for dependency in self.edges(template) {
+                dependents.entry(dependency).or_default().push(template);
+            }
+        }
+
+        let target = self.resolve(name).unwrap_or(name);
+        let mut affected: BTreeSet<&str> = BTreeSet::new();
+        let mut queue = VecDeque::from([target]);
+
+        while let Some(current) = queue.pop_front() {
+            for dependent in dependents.get(current).into_iter().flatten() {
+                if affected.insert(dependent) {
+                    queue.push_back(dependent);
+                }
+            }
+        }
+
+        affected.remove(target);
+        affected.into_iter().map(|n| n.to_owned()).collect()
+    }
+}
+
+/// Tarjan's strongly connected components algorithm.
+struct Tarjan<'a> {
+    graph: &'a DependencyGraph,
+    index: usize,
+    indices: HashMap<&'a str, usize>,
+    low_links: HashMap<&'a str, usize>,
+    stack: Vec<&'a str>,
+    on_stack: HashSet<&'a str>,
+    components: Vec<Vec<&'a str>>,
+}
+
+impl<'a> Tarjan<'a> {
+    /// Find the components reachable from _root_. Templates are visited with
+    /// an explicit work stack, not recursion, so that a long chain of
+    /// templates can't overflow the call stack.
+    fn connect(&mut self, root: &'a str) {
+        // Templates being visited, with the dependencies each has left.
+        let mut work: Vec<(&'a str, btree_set::IntoIter<&'a str>)> = vec![self.visit(root)];
+
+        while let Some((name, edges)) = work.last_mut() {
+            let name = *name;
+            if let Some(dependency) = edges.next() {
+                if !self.indices.contains_key(dependency) {
+                    work.push(self.visit(dependency));
+                } else if self.on_stack.contains(dependency) {
+                    let low = self.low_links[name].min(self.indices[dependency]);
+                    self.low_links.insert(name, low);
+                }
+                continue;
+            }
+
+            work.pop();
+            if let Some((parent, _)) = work.last() {
+                let low = self.low_links[parent].min(self.low_links[name]);
+                self.low_links.insert(parent, low);
+            }
+
+            if self.low_links[name] == self.indices[name] {
+                let mut component = Vec::new();
+                while let Some(member) = self.stack.pop() {
+                    self.on_stack.remove(member);
+                    component.push(member);
+                    if member == name {
+                        break;
+                    }
+                }
+                self.components.push(component);
+            }
+        }
+    }
+
+    // Numbers _name_ and returns it with its dependencies, ready to visit.
+    fn visit(&mut self, name: &'a str) -> (&'a str, btree_set::IntoIter<&'a str>) {
+        self.indices.insert(name, self.index);
+        self.low_links.insert(name, self.index);
+        self.index += 1;
+        self.stack.push(name);
+        self.on_stack.insert(name);
+        (name, self.graph.edges(name).into_iter())
+    }
+}
+
+#[pymethods]
+impl DependencyGraph {
+    #[new]
+    #[pyo3(signature = (*, extension=None))]
+    fn py_new(extension: Option<String>) -> Self {
+        DependencyGraph::new(extension)
+    }
+
+    #[staticmethod]
+    #[pyo3(name = "from_directory", signature = (path, *, extension=None, lexer=None))]
+    fn py_from_directory(
+        path: &str,
+        extension: Option<String>,
+        lexer: Option<PyRef<'_, Lexer>>,
+    ) -> PyResult<Self> {
+        let lexer = lexer.as_deref().unwrap_or(default_lexer());
+        Ok(DependencyGraph::from_directory(
+            Path::new(path),
+            extension,
+            lexer,
+        )?)
+    }
+
+    #[pyo3(name = "add_template")]
+    fn py_add_template(&mut self, name: &str, tokens: Vec<Markup>) {
+        self.add_template(name, &tokens);
+    }
+
+    #[pyo3(name = "remove_template")]
+    fn py_remove_template(&mut self, name: &str) -> bool {
+        self.remove_template(name)
+    }
+
+    #[pyo3(name = "templates")]
+    fn py_templates(&self) -> Vec<String> {
+        self.templates().map(|n| n.to_owned()).collect()
+    }
+
+    #[pyo3(name = "dependencies")]
+    fn py_dependencies(&self, name: &str) -> Vec<Dependency> {
+        self.dependencies(name).to_vec()
+    }
+
+    #[pyo3(name = "errors")]
+    fn py_errors(&self) -> Vec<(String, String)> {
+        self.errors.clone()
+    }
+
+    #[pyo3(name = "dynamic")]
+    fn py_dynamic(&self) -> Vec<(String, Dependency)> {
+        self.dynamic()
+            .into_iter()
+            .map(|(name, d)| (name.to_owned(), d.clone()))
+            .collect()
+    }
+
+    #[pyo3(name = "missing")]
+    fn py_missing(&self) -> Vec<(String, Dependency)> {
+        self.missing()
+            .into_iter()
+            .map(|(name, d)| (name.to_owned(), d.clone()))
+            .collect()
+    }
+
+    #[pyo3(name = "cycles")]
+    fn py_cycles(&self) -> Vec<Vec<String>> {
+        self.cycles()
+    }
+
+    #[pyo3(name = "affected")]
+    fn py_affected(&self, name: &str) -> Vec<String> {
+        self.affected(name)
+    }
+}
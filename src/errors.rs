@@ -1,7 +1,7 @@
 use std::fmt;
 
 use pyo3::create_exception;
-use pyo3::exceptions::PyException;
+use pyo3::exceptions::{PyException, PyOSError};
 use pyo3::prelude::*;
 
 #[derive(Debug)]
@@ -11,23 +11,38 @@ pub enum LiquidErrorType {
     TypeError,
     NameError,
     ExtError,
+    IoError,
+    TemplateNotFoundError,
+    SerializationError,
 }
 
 #[derive(Debug)]
 pub struct LiquidError {
     pub kind: LiquidErrorType,
     pub msg: String,
+    pub span: Option<(usize, usize)>,
 }
 
 impl LiquidError {
     pub fn new(error: LiquidErrorType, msg: String) -> Self {
-        Self { kind: error, msg }
+        Self {
+            kind: error,
+            msg,
+            span: None,
+        }
+    }
+
+    /// Attach the source span of the offending markup or token to this error.
+    pub fn with_span(mut self, span: impl Into<Option<(usize, usize)>>) -> Self {
+        self.span = span.into();
+        self
     }
 
     pub fn syntax(msg: String) -> Self {
         Self {
             kind: LiquidErrorType::SyntaxError,
             msg,
+            span: None,
         }
     }
 
@@ -35,6 +50,7 @@ impl LiquidError {
         Self {
             kind: LiquidErrorType::TypeError,
             msg,
+            span: None,
         }
     }
 
@@ -42,6 +58,7 @@ impl LiquidError {
         Self {
             kind: LiquidErrorType::NameError,
             msg,
+            span: None,
         }
     }
 
@@ -49,10 +66,41 @@ impl LiquidError {
         Self {
             kind: LiquidErrorType::ExtError,
             msg,
+            span: None,
+        }
+    }
+
+    pub fn template_not_found(msg: String) -> Self {
+        Self {
+            kind: LiquidErrorType::TemplateNotFoundError,
+            msg,
+            span: None,
+        }
+    }
+
+    pub fn serialization(msg: String) -> Self {
+        Self {
+            kind: LiquidErrorType::SerializationError,
+            msg,
+            span: None,
+        }
+    }
+
+    pub fn io(msg: String) -> Self {
+        Self {
+            kind: LiquidErrorType::IoError,
+            msg,
+            span: None,
         }
     }
 }
 
+impl std::convert::From<std::io::Error> for LiquidError {
+    fn from(err: std::io::Error) -> Self {
+        Self::io(err.to_string())
+    }
+}
+
 impl std::error::Error for LiquidError {}
 
 create_exception!(
@@ -90,17 +138,41 @@ create_exception!(
     "Liquid function extension error."
 );
 
+create_exception!(
+    _liquid2,
+    TemplateNotFoundError,
+    PyLiquidError,
+    "Template not found."
+);
+
+create_exception!(
+    _liquid2,
+    LiquidSerializationError,
+    PyLiquidError,
+    "Error encoding or decoding a token stream."
+);
+
 impl std::convert::From<LiquidError> for PyErr {
     fn from(err: LiquidError) -> Self {
         use LiquidErrorType::*;
-        match err.kind {
+        let py_err = match err.kind {
             // TODO: improve error messages
             TypeError => LiquidTypeError::new_err(err.to_string()),
             SyntaxError => LiquidSyntaxError::new_err(err.to_string()),
             NameError => LiquidNameError::new_err(err.to_string()),
             ExtError => LiquidExtensionError::new_err(err.to_string()),
+            IoError => PyOSError::new_err(err.to_string()),
+            TemplateNotFoundError => self::TemplateNotFoundError::new_err(err.to_string()),
+            SerializationError => LiquidSerializationError::new_err(err.to_string()),
             _ => PyLiquidError::new_err(err.to_string()),
-        }
+        };
+
+        Python::with_gil(|py| {
+            // Exceptions without a span have `span` set to `None`.
+            let _ = py_err.value_bound(py).setattr("span", err.span);
+        });
+
+        py_err
     }
 }
 
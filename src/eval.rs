@@ -0,0 +1,1073 @@
+//! JSONPath query evaluation
+//!
+//! Queries are evaluated following RFC 9535 against any JSON-like data that
+//! implements `JsonLike`. Implementations are provided for `serde_json` values
+//! and for Python dicts, lists, scalars, dates and times, which are read in
+//! place rather than copied. `formats` adds YAML, TOML, CBOR and MessagePack
+//! values.
+//!
+//! Every node found comes with its location, which can be written as a
+//! normalized path like `$['a'][0]`. Python data that contains itself is an
+//! error for descendant segments and comparisons, which would otherwise never
+//! finish.
+
+use std::cmp::Ordering;
+use std::rc::Rc;
+use std::sync::Arc;
+
+use lazy_static::lazy_static;
+use pyo3::prelude::*;
+use pyo3::sync::GILOnceCell;
+use pyo3::types::{
+    PyBool, PyBytes, PyDict, PyFloat, PyInt, PyList, PyMapping, PySequence, PyString, PyTuple,
+};
+use regex::Regex;
+
+use crate::errors::LiquidError;
+use crate::iregexp;
+use crate::query::{
+    ComparisonOperator, FilterExpression, LogicalOperator, Query, Segment, Selector,
+};
+
+/// The type of a JSON-like value, with its scalar value if it has one.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Kind {
+    Null,
+    Bool(bool),
+    Int(i64),
+    Float(f64),
+    String(String),
+    /// A date, time or date-time as RFC 3339 text, like `2024-01-31` or
+    /// `2024-01-31T12:30:00Z`. Dates, local times, local date-times and
+    /// date-times with an offset only compare with values of the same kind,
+    /// including strings that read as one. Date-times with an offset compare
+    /// as instants in UTC.
+    DateTime(String),
+    /// Binary data, which is only ever equal to the same bytes. See
+    /// `JsonLike::with_bytes`.
+    Bytes,
+    Array(usize),
+    Object,
+    /// A value with no JSON equivalent, which filters never match.
+    Other,
+}
+
+/// JSON-like data that queries can be evaluated against.
+pub trait JsonLike: Clone {
+    fn kind(&self) -> Kind;
+
+    /// Return the item at _index_ if this is an array.
+    fn get_index(&self, index: usize) -> Option<Self>;
+
+    /// Return the value of member _name_ if this is an object.
+    fn get_member(&self, name: &str) -> Option<Self>;
+
+    /// Return this array's items, or an empty vector if this is not an array.
+    fn items(&self) -> Vec<Self>;
+
+    /// Return this object's members in order, or an empty vector if this is
+    /// not an object.
+    fn members(&self) -> Vec<(String, Self)>;
+
+    /// Return _f_ applied to this value's bytes if this is binary data.
+    fn with_bytes<T>(&self, _f: impl FnOnce(&[u8]) -> T) -> Option<T> {
+        None
+    }
+
+    /// Return a number that is the same for every view of this array or
+    /// object, if values of this type can contain themselves. Queries use it
+    /// to stop at cyclic data instead of descending forever.
+    fn identity(&self) -> Option<usize> {
+        None
+    }
+}
+
+/// One step in a normalized path, an object member name or an array index.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum PathElement {
+    Name(String),
+    Index(usize),
+}
+
+/// The location of a node, from the root value of a query.
+#[derive(Debug, Clone, Default)]
+pub struct Location(Option<Rc<(Location, PathElement)>>);
+
+impl Location {
+    pub fn push(&self, element: PathElement) -> Location {
+        Location(Some(Rc::new((self.clone(), element))))
+    }
+
+    pub fn to_vec(&self) -> Vec<PathElement> {
+        let mut path = Vec::new();
+        let mut location = self;
+        while let Some(step) = &location.0 {
+            path.push(step.1.clone());
+            location = &step.0;
+        }
+        path.reverse();
+        path
+    }
+
+    /// Return this location as a normalized path query.
+    pub fn to_query(&self) -> Query {
+        normalized_path(&self.to_vec())
+    }
+}
+
+/// Return a query for _path_ that displays as an RFC 9535 normalized path.
+pub fn normalized_path(path: &[PathElement]) -> Query {
+    Query {
+        segments: path
+            .iter()
+            .map(|element| Segment::Child {
+                selectors: vec![match element {
+                    PathElement::Name(name) => Selector::Name {
+                        name: name.clone(),
+                        span: None,
+                    },
+                    PathElement::Index(index) => Selector::Index {
+                        index: *index as i64,
+                        span: None,
+                    },
+                }],
+                span: None,
+            })
+            .collect(),
+    }
+}
+
+/// A value found by a query and its location.
+#[derive(Debug, Clone)]
+pub struct Node<V> {
+    pub value: V,
+    pub location: Location,
+}
+
+/// Return the nodes matched by _query_ in _root_, in document order.
+pub fn find<V: JsonLike>(query: &Query, root: &V) -> Result<Vec<Node<V>>, LiquidError> {
+    Evaluator { root }.query(
+        query,
+        Node {
+            value: root.clone(),
+            location: Location::default(),
+        },
+    )
+}
+
+/// Return `true` if filter _expression_ selects _value_, with _root_ as the
+/// value of `$`.
+pub fn test_filter<V: JsonLike>(
+    expression: &FilterExpression,
+    value: &V,
+    root: &V,
+) -> Result<bool, LiquidError> {
+    Evaluator { root }.test(expression, value)
+}
+
+/// Return an iterator over the nodes matched by _query_ in _root_, in
+/// document order. Nodes are found one at a time, depth first, so stopping
+/// early skips the rest of the work `find` would do.
+pub fn find_iter<V: JsonLike>(query: Arc<Query>, root: V) -> Matches<V> {
+    Matches {
+        query,
+        stack: vec![(
+            0,
+            Node {
+                value: root.clone(),
+                location: Location::default(),
+            },
+            Ancestors::default(),
+        )],
+        root,
+    }
+}
+
+/// An iterator over the nodes matched by a query. See `find_iter`.
+pub struct Matches<V> {
+    query: Arc<Query>,
+    root: V,
+    // Nodes waiting for the segment at their index. A descendant segment
+    // puts a node's children back on the stack with the same index, so
+    // subtrees are visited one node at a time, along with the containers
+    // above them in that subtree.
+    stack: Vec<(usize, Node<V>, Ancestors)>,
+}
+
+impl<V: JsonLike> Iterator for Matches<V> {
+    type Item = Result<Node<V>, LiquidError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let (index, node, ancestors) = self.stack.pop()?;
+            let evaluator = Evaluator { root: &self.root };
+            let nodes = match self.query.segments.get(index) {
+                None => return Some(Ok(node)),
+                Some(Segment::Recursive { selectors, .. }) => {
+                    match descend(&node, &ancestors) {
+                        Ok(children) => self.stack.extend(
+                            children
+                                .into_iter()
+                                .rev()
+                                .map(|(child, ancestors)| (index, child, ancestors)),
+                        ),
+                        Err(err) => {
+                            self.stack.clear();
+                            return Some(Err(err));
+                        }
+                    }
+                    evaluator.select_all(selectors, &node)
+                }
+                Some(segment) => evaluator.segment(segment, vec![node]),
+            };
+
+            match nodes {
+                Ok(nodes) => self.stack.extend(
+                    nodes
+                        .into_iter()
+                        .rev()
+                        .map(|node| (index + 1, node, Ancestors::default())),
+                ),
+                Err(err) => {
+                    self.stack.clear();
+                    return Some(Err(err));
+                }
+            }
+        }
+    }
+}
+
+// The result of evaluating a filter function argument or a comparable.
+enum Value<V> {
+    Nothing,
+    Literal(Kind),
+    Node(V),
+}
+
+impl<V: JsonLike> Value<V> {
+    fn from_nodes(mut nodes: Vec<V>) -> Self {
+        if nodes.len() == 1 {
+            Value::Node(nodes.remove(0))
+        } else {
+            Value::Nothing
+        }
+    }
+}
+
+enum Evaluated<V> {
+    Value(Value<V>),
+    Nodes(Vec<V>),
+    Logical(bool),
+}
+
+impl<V: JsonLike> Evaluated<V> {
+    fn into_value(self) -> Value<V> {
+        match self {
+            Evaluated::Value(value) => value,
+            Evaluated::Nodes(nodes) => Value::from_nodes(nodes),
+            Evaluated::Logical(_) => Value::Nothing,
+        }
+    }
+
+    fn into_nodes(self) -> Vec<V> {
+        match self {
+            Evaluated::Nodes(nodes) => nodes,
+            Evaluated::Value(Value::Node(node)) => vec![node],
+            _ => Vec::new(),
+        }
+    }
+}
+
+struct Evaluator<'a, V> {
+    root: &'a V,
+}
+
+impl<V: JsonLike> Evaluator<'_, V> {
+    fn query(&self, query: &Query, node: Node<V>) -> Result<Vec<Node<V>>, LiquidError> {
+        query
+            .segments
+            .iter()
+            .try_fold(vec![node], |nodes, segment| self.segment(segment, nodes))
+    }
+
+    fn values(&self, query: &Query, value: &V) -> Result<Vec<V>, LiquidError> {
+        Ok(self
+            .query(
+                query,
+                Node {
+                    value: value.clone(),
+                    location: Location::default(),
+                },
+            )?
+            .into_iter()
+            .map(|node| node.value)
+            .collect())
+    }
+
+    fn segment(&self, segment: &Segment, nodes: Vec<Node<V>>) -> Result<Vec<Node<V>>, LiquidError> {
+        let mut matched = Vec::new();
+        match segment {
+            Segment::Child { selectors, .. } => {
+                for node in &nodes {
+                    matched.extend(self.select_all(selectors, node)?);
+                }
+            }
+            Segment::Recursive { selectors, .. } => {
+                for node in nodes.iter().flat_map(descendants) {
+                    matched.extend(self.select_all(selectors, &node?)?);
+                }
+            }
+            Segment::Eoi {} => return Ok(nodes),
+        }
+        Ok(matched)
+    }
+
+    fn select_all(
+        &self,
+        selectors: &[Selector],
+        node: &Node<V>,
+    ) -> Result<Vec<Node<V>>, LiquidError> {
+        let mut matched = Vec::new();
+        for selector in selectors {
+            self.select(selector, node, &mut matched)?;
+        }
+        Ok(matched)
+    }
+
+    fn select(
+        &self,
+        selector: &Selector,
+        node: &Node<V>,
+        matched: &mut Vec<Node<V>>,
+    ) -> Result<(), LiquidError> {
+        match selector {
+            Selector::Name { name, .. } => matched.extend(member(node, name)),
+            Selector::Index { index, .. } => matched.extend(item(node, *index)),
+            Selector::Slice {
+                start, stop, step, ..
+            } => {
+                if let Kind::Array(length) = node.value.kind() {
+                    matched.extend(
+                        slice_indices(length as i64, *start, *stop, step.unwrap_or(1))
+                            .filter_map(|index| item(node, index)),
+                    );
+                }
+            }
+            Selector::Wild { .. } => matched.extend(children(node)),
+            Selector::Filter { expression, .. } => {
+                for child in children(node) {
+                    if self.test(expression, &child.value)? {
+                        matched.push(child);
+                    }
+                }
+            }
+            Selector::SingularQuery { query, .. } => {
+                let key = Value::from_nodes(self.values(query, self.root)?);
+                match resolve(&key) {
+                    Some(Kind::String(name)) => matched.extend(member(node, &name)),
+                    Some(Kind::Int(index)) => matched.extend(item(node, index)),
+                    _ => (),
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn test(&self, expression: &FilterExpression, current: &V) -> Result<bool, LiquidError> {
+        match expression {
+            FilterExpression::True_ { .. } => Ok(true),
+            FilterExpression::Logical {
+                left,
+                operator,
+                right,
+                ..
+            } => Ok(match operator {
+                LogicalOperator::And => self.test(left, current)? && self.test(right, current)?,
+                LogicalOperator::Or => self.test(left, current)? || self.test(right, current)?,
+            }),
+            FilterExpression::Not { expression, .. } => Ok(!self.test(expression, current)?),
+            FilterExpression::Comparison {
+                left,
+                operator,
+                right,
+                ..
+            } => {
+                let left = self.evaluate(left, current)?.into_value();
+                let right = self.evaluate(right, current)?.into_value();
+                compare(&left, operator, &right)
+            }
+            FilterExpression::RelativeQuery { .. }
+            | FilterExpression::RootQuery { .. }
+            | FilterExpression::Function { .. } => {
+                Ok(match self.evaluate(expression, current)? {
+                    Evaluated::Logical(value) => value,
+                    Evaluated::Nodes(nodes) => !nodes.is_empty(),
+                    Evaluated::Value(_) => false,
+                })
+            }
+            _ => Ok(false),
+        }
+    }
+
+    fn evaluate(
+        &self,
+        expression: &FilterExpression,
+        current: &V,
+    ) -> Result<Evaluated<V>, LiquidError> {
+        Ok(match expression {
+            FilterExpression::True_ { .. } => Evaluated::Value(Value::Literal(Kind::Bool(true))),
+            FilterExpression::False_ { .. } => Evaluated::Value(Value::Literal(Kind::Bool(false))),
+            FilterExpression::Null { .. } => Evaluated::Value(Value::Literal(Kind::Null)),
+            FilterExpression::StringLiteral { value, .. } => {
+                Evaluated::Value(Value::Literal(Kind::String(value.clone())))
+            }
+            FilterExpression::Int { value, .. } => {
+                Evaluated::Value(Value::Literal(Kind::Int(*value)))
+            }
+            FilterExpression::Float { value, .. } => {
+                Evaluated::Value(Value::Literal(Kind::Float(*value)))
+            }
+            FilterExpression::RelativeQuery { query, .. } => {
+                Evaluated::Nodes(self.values(query, current)?)
+            }
+            FilterExpression::RootQuery { query, .. } => {
+                Evaluated::Nodes(self.values(query, self.root)?)
+            }
+            FilterExpression::Function { name, args, span } => {
+                let args = args
+                    .iter()
+                    .map(|arg| self.evaluate(arg, current))
+                    .collect::<Result<Vec<_>, _>>()?;
+                call(name, args).map_err(|err| err.with_span(*span))?
+            }
+            FilterExpression::Not { .. }
+            | FilterExpression::Logical { .. }
+            | FilterExpression::Comparison { .. } => {
+                Evaluated::Logical(self.test(expression, current)?)
+            }
+        })
+    }
+}
+
+fn member<V: JsonLike>(node: &Node<V>, name: &str) -> Option<Node<V>> {
+    node.value.get_member(name).map(|value| Node {
+        value,
+        location: node.location.push(PathElement::Name(name.to_owned())),
+    })
+}
+
+fn item<V: JsonLike>(node: &Node<V>, index: i64) -> Option<Node<V>> {
+    let Kind::Array(length) = node.value.kind() else {
+        return None;
+    };
+
+    let index = normalize_index(index, length)?;
+    node.value.get_index(index).map(|value| Node {
+        value,
+        location: node.location.push(PathElement::Index(index)),
+    })
+}
+
+/// Return _index_ as a non-negative index into an array of _length_ items,
+/// or `None` if it is out of range.
+pub fn normalize_index(index: i64, length: usize) -> Option<usize> {
+    let index = if index < 0 {
+        index.checked_add(length as i64)?
+    } else {
+        index
+    };
+    usize::try_from(index).ok().filter(|index| *index < length)
+}
+
+fn children<V: JsonLike>(node: &Node<V>) -> Vec<Node<V>> {
+    match node.value.kind() {
+        Kind::Array(_) => node
+            .value
+            .items()
+            .into_iter()
+            .enumerate()
+            .map(|(index, value)| Node {
+                value,
+                location: node.location.push(PathElement::Index(index)),
+            })
+            .collect(),
+        Kind::Object => node
+            .value
+            .members()
+            .into_iter()
+            .map(|(name, value)| Node {
+                value,
+                location: node.location.push(PathElement::Name(name)),
+            })
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+// Returns _node_ and all of its descendants in pre-order, stopping with an
+// error if _node_ contains itself.
+fn descendants<V: JsonLike>(node: &Node<V>) -> impl Iterator<Item = Result<Node<V>, LiquidError>> {
+    let mut stack = vec![(node.clone(), Ancestors::default())];
+    std::iter::from_fn(move || {
+        let (node, ancestors) = stack.pop()?;
+        match descend(&node, &ancestors) {
+            Ok(children) => stack.extend(children.into_iter().rev()),
+            Err(err) => {
+                stack.clear();
+                return Some(Err(err));
+            }
+        }
+        Some(Ok(node))
+    })
+}
+
+// Returns the children of _node_, each with _node_ added to _ancestors_, or
+// an error if _node_ is one of its own _ancestors_.
+fn descend<V: JsonLike>(
+    node: &Node<V>,
+    ancestors: &Ancestors,
+) -> Result<Vec<(Node<V>, Ancestors)>, LiquidError> {
+    let children = children(node);
+    if children.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let ancestors = ancestors.enter(&node.value).ok_or_else(|| {
+        LiquidError::typ(format!(
+            "can't query cyclic data, the value at `{}` contains itself",
+            node.location.to_query()
+        ))
+    })?;
+
+    Ok(children
+        .into_iter()
+        .map(|child| (child, ancestors.clone()))
+        .collect())
+}
+
+// The identities of the arrays and objects above a value, for values that
+// can contain themselves.
+#[derive(Clone, Default)]
+struct Ancestors(Option<Rc<(usize, Ancestors)>>);
+
+impl Ancestors {
+    // Returns these ancestors with _value_ added, or `None` if _value_ is
+    // already one of them.
+    fn enter<V: JsonLike>(&self, value: &V) -> Option<Ancestors> {
+        let Some(identity) = value.identity() else {
+            return Some(self.clone());
+        };
+
+        let mut ancestors = self;
+        while let Some(step) = &ancestors.0 {
+            if step.0 == identity {
+                return None;
+            }
+            ancestors = &step.1;
+        }
+
+        Some(Ancestors(Some(Rc::new((identity, self.clone())))))
+    }
+}
+
+// Returns the indices selected by a slice, following RFC 9535 section 2.3.4.2.
+fn slice_indices(
+    length: i64,
+    start: Option<i64>,
+    stop: Option<i64>,
+    step: i64,
+) -> Box<dyn Iterator<Item = i64>> {
+    let normalize = |index: i64| if index >= 0 { index } else { length + index };
+
+    match step.cmp(&0) {
+        Ordering::Equal => Box::new(std::iter::empty()),
+        Ordering::Greater => {
+            let lower = normalize(start.unwrap_or(0)).clamp(0, length);
+            let upper = normalize(stop.unwrap_or(length)).clamp(0, length);
+            Box::new((lower..upper).step_by(step as usize))
+        }
+        Ordering::Less => {
+            let upper = start.map_or(length - 1, normalize).clamp(-1, length - 1);
+            let lower = stop.map_or(-1, normalize).clamp(-1, length - 1);
+            Box::new(
+                std::iter::successors(Some(upper), move |index| index.checked_add(step))
+                    .take_while(move |index| *index > lower),
+            )
+        }
+    }
+}
+
+fn resolve<V: JsonLike>(value: &Value<V>) -> Option<Kind> {
+    match value {
+        Value::Nothing => None,
+        Value::Literal(kind) => Some(kind.clone()),
+        Value::Node(node) => Some(node.kind()),
+    }
+}
+
+fn compare<V: JsonLike>(
+    left: &Value<V>,
+    operator: &ComparisonOperator,
+    right: &Value<V>,
+) -> Result<bool, LiquidError> {
+    Ok(match operator {
+        ComparisonOperator::Eq => equal(left, right)?,
+        ComparisonOperator::Ne => !equal(left, right)?,
+        ComparisonOperator::Lt => less(left, right),
+        ComparisonOperator::Le => less(left, right) || equal(left, right)?,
+        ComparisonOperator::Gt => less(right, left),
+        ComparisonOperator::Ge => less(right, left) || equal(left, right)?,
+    })
+}
+
+fn equal<V: JsonLike>(left: &Value<V>, right: &Value<V>) -> Result<bool, LiquidError> {
+    Ok(match (left, right) {
+        (Value::Nothing, Value::Nothing) => true,
+        (Value::Nothing, _) | (_, Value::Nothing) => false,
+        (Value::Node(left), Value::Node(right)) => deep_equal(left, right, &Ancestors::default())?,
+        _ => match (resolve(left), resolve(right)) {
+            (Some(left), Some(right)) => scalar_equal(&left, &right),
+            _ => false,
+        },
+    })
+}
+
+// Compares _left_ and _right_ item by item. Only _left_'s _ancestors_ are
+// tracked, since a cycle on one side alone stops where the other side ends.
+fn deep_equal<V: JsonLike>(
+    left: &V,
+    right: &V,
+    ancestors: &Ancestors,
+) -> Result<bool, LiquidError> {
+    let cyclic = || LiquidError::typ("can't compare cyclic data".to_owned());
+    match (left.kind(), right.kind()) {
+        (Kind::Array(left_length), Kind::Array(right_length)) => {
+            if left_length != right_length {
+                return Ok(false);
+            }
+            let ancestors = ancestors.enter(left).ok_or_else(cyclic)?;
+            for (left, right) in left.items().iter().zip(right.items().iter()) {
+                if !deep_equal(left, right, &ancestors)? {
+                    return Ok(false);
+                }
+            }
+            Ok(true)
+        }
+        (Kind::Object, Kind::Object) => {
+            let members = left.members();
+            if members.len() != right.members().len() {
+                return Ok(false);
+            }
+            let ancestors = ancestors.enter(left).ok_or_else(cyclic)?;
+            for (name, value) in &members {
+                match right.get_member(name) {
+                    Some(other) if deep_equal(value, &other, &ancestors)? => (),
+                    _ => return Ok(false),
+                }
+            }
+            Ok(true)
+        }
+        (Kind::Bytes, Kind::Bytes) => {
+            Ok(left.with_bytes(|left| right.with_bytes(|right| left == right)) == Some(Some(true)))
+        }
+        (left, right) => Ok(scalar_equal(&left, &right)),
+    }
+}
+
+fn scalar_equal(left: &Kind, right: &Kind) -> bool {
+    match (left, right) {
+        (Kind::Int(left), Kind::Float(right)) | (Kind::Float(right), Kind::Int(left)) => {
+            *left as f64 == *right
+        }
+        (Kind::DateTime(left), Kind::String(right) | Kind::DateTime(right))
+        | (Kind::String(left), Kind::DateTime(right)) => {
+            compare_date_times(left, right) == Some(Ordering::Equal)
+        }
+        // Binary data is compared by `deep_equal`, without copying it.
+        (Kind::Array(_) | Kind::Object | Kind::Bytes | Kind::Other, _)
+        | (_, Kind::Array(_) | Kind::Object | Kind::Bytes | Kind::Other) => false,
+        (left, right) => left == right,
+    }
+}
+
+fn less<V: JsonLike>(left: &Value<V>, right: &Value<V>) -> bool {
+    match (resolve(left), resolve(right)) {
+        (Some(Kind::Int(left)), Some(Kind::Int(right))) => left < right,
+        (Some(Kind::Int(left)), Some(Kind::Float(right))) => (left as f64) < right,
+        (Some(Kind::Float(left)), Some(Kind::Int(right))) => left < right as f64,
+        (Some(Kind::Float(left)), Some(Kind::Float(right))) => left < right,
+        (Some(Kind::String(left)), Some(Kind::String(right))) => left < right,
+        (Some(Kind::DateTime(left)), Some(Kind::String(right) | Kind::DateTime(right)))
+        | (Some(Kind::String(left)), Some(Kind::DateTime(right))) => {
+            compare_date_times(&left, &right) == Some(Ordering::Less)
+        }
+        _ => false,
+    }
+}
+
+/// A date or time read from RFC 3339 text.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Temporal {
+    /// Days since the Unix epoch.
+    Date(i64),
+    /// Seconds and nanoseconds since the Unix epoch, ignoring time zones.
+    LocalDateTime(i64, u32),
+    /// Seconds and nanoseconds since the Unix epoch, in UTC.
+    OffsetDateTime(i64, u32),
+    /// Seconds and nanoseconds since midnight.
+    LocalTime(i64, u32),
+}
+
+lazy_static! {
+    static ref DATE_TIME: Regex = Regex::new(
+        r"^(?:([0-9]{4})-([0-9]{2})-([0-9]{2}))?(?:(?:^|[Tt ])([0-9]{2}):([0-9]{2}):([0-9]{2})(?:\.([0-9]+))?([Zz]|[-+][0-9]{2}:[0-9]{2})?)?$"
+    )
+    .unwrap();
+}
+
+impl Temporal {
+    /// Read a date, local time or date-time from RFC 3339 _text_.
+    fn parse(text: &str) -> Option<Temporal> {
+        let captures = DATE_TIME.captures(text)?;
+        let number = |index: usize| captures.get(index).map(|m| m.as_str().parse::<i64>());
+
+        let days = match (number(1), number(2), number(3)) {
+            (Some(year), Some(month), Some(day)) => {
+                let (month, day) = (month.ok()?, day.ok()?);
+                if !(1..=12).contains(&month) || !(1..=31).contains(&day) {
+                    return None;
+                }
+                Some(days_from_civil(year.ok()?, month, day))
+            }
+            _ => None,
+        };
+
+        let Some(hour) = number(4) else {
+            return days.map(Temporal::Date);
+        };
+        let (hour, minute, second) = (hour.ok()?, number(5)?.ok()?, number(6)?.ok()?);
+        if hour > 23 || minute > 59 || second > 60 {
+            return None;
+        }
+
+        let seconds = hour * 3600 + minute * 60 + second;
+        let nanos = captures.get(7).map_or(Some(0), |m| {
+            format!("{:0<9.9}", m.as_str()).parse::<u32>().ok()
+        })?;
+
+        Some(match (days, captures.get(8).map(|m| m.as_str())) {
+            (None, None) => Temporal::LocalTime(seconds, nanos),
+            (None, Some(_)) => return None,
+            (Some(days), None) => Temporal::LocalDateTime(days * 86_400 + seconds, nanos),
+            (Some(days), Some("Z" | "z")) => {
+                Temporal::OffsetDateTime(days * 86_400 + seconds, nanos)
+            }
+            (Some(days), Some(offset)) => {
+                let hours = offset[1..3].parse::<i64>().ok()?;
+                let minutes = offset[4..].parse::<i64>().ok()?;
+                let sign = if offset.starts_with('-') { -1 } else { 1 };
+                let offset = sign * (hours * 3600 + minutes * 60);
+                Temporal::OffsetDateTime(days * 86_400 + seconds - offset, nanos)
+            }
+        })
+    }
+
+    fn compare(&self, other: &Temporal) -> Option<Ordering> {
+        match (self, other) {
+            (Temporal::Date(left), Temporal::Date(right)) => Some(left.cmp(right)),
+            (Temporal::LocalDateTime(ls, ln), Temporal::LocalDateTime(rs, rn))
+            | (Temporal::OffsetDateTime(ls, ln), Temporal::OffsetDateTime(rs, rn))
+            | (Temporal::LocalTime(ls, ln), Temporal::LocalTime(rs, rn)) => {
+                Some((ls, ln).cmp(&(rs, rn)))
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Compare two dates or times given as text, or `None` if they are not of
+/// the same kind. Text that isn't a date or time is only equal to the same
+/// text.
+fn compare_date_times(left: &str, right: &str) -> Option<Ordering> {
+    match (Temporal::parse(left), Temporal::parse(right)) {
+        (Some(left), Some(right)) => left.compare(&right),
+        _ => (left == right).then_some(Ordering::Equal),
+    }
+}
+
+/// Return the number of days from the Unix epoch to a date in the proleptic
+/// Gregorian calendar.
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    // Howard Hinnant's `days_from_civil`.
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146_097 + day_of_era - 719_468
+}
+
+fn call<V: JsonLike>(name: &str, args: Vec<Evaluated<V>>) -> Result<Evaluated<V>, LiquidError> {
+    let mut args = args.into_iter();
+    let mut arg = || {
+        args.next().ok_or_else(|| {
+            LiquidError::typ(format!("not enough arguments for filter function `{name}`"))
+        })
+    };
+
+    Ok(match name {
+        "length" => Evaluated::Value(match arg()?.into_value() {
+            Value::Node(node) => length(&node.kind(), || node.members().len()),
+            Value::Literal(kind) => length(&kind, || 0),
+            Value::Nothing => Value::Nothing,
+        }),
+        "count" => Evaluated::Value(Value::Literal(Kind::Int(arg()?.into_nodes().len() as i64))),
+        "value" => Evaluated::Value(Value::from_nodes(arg()?.into_nodes())),
+        "match" | "search" => {
+            let value = resolve(&arg()?.into_value());
+            let pattern = resolve(&arg()?.into_value());
+            Evaluated::Logical(match (value, pattern) {
+                (Some(Kind::String(value)), Some(Kind::String(pattern))) => {
+                    iregexp::cached(&pattern, name == "match").is_some_and(|re| re.is_match(&value))
+                }
+                _ => false,
+            })
+        }
+        _ => {
+            return Err(LiquidError::ext(format!(
+                "no implementation for filter function `{name}`"
+            )))
+        }
+    })
+}
+
+fn length<V>(kind: &Kind, member_count: impl FnOnce() -> usize) -> Value<V> {
+    match kind {
+        Kind::String(value) => Value::Literal(Kind::Int(value.chars().count() as i64)),
+        Kind::Array(length) => Value::Literal(Kind::Int(*length as i64)),
+        Kind::Object => Value::Literal(Kind::Int(member_count() as i64)),
+        _ => Value::Nothing,
+    }
+}
+
+impl JsonLike for &serde_json::Value {
+    fn kind(&self) -> Kind {
+        match self {
+            serde_json::Value::Null => Kind::Null,
+            serde_json::Value::Bool(value) => Kind::Bool(*value),
+            serde_json::Value::Number(number) => match number.as_i64() {
+                Some(value) => Kind::Int(value),
+                None => Kind::Float(number.as_f64().unwrap_or(f64::NAN)),
+            },
+            serde_json::Value::String(value) => Kind::String(value.clone()),
+            serde_json::Value::Array(items) => Kind::Array(items.len()),
+            serde_json::Value::Object(_) => Kind::Object,
+        }
+    }
+
+    fn get_index(&self, index: usize) -> Option<Self> {
+        self.as_array()?.get(index)
+    }
+
+    fn get_member(&self, name: &str) -> Option<Self> {
+        self.as_object()?.get(name)
+    }
+
+    fn items(&self) -> Vec<Self> {
+        self.as_array()
+            .map(|items| items.iter().collect())
+            .unwrap_or_default()
+    }
+
+    fn members(&self) -> Vec<(String, Self)> {
+        self.as_object()
+            .map(|members| {
+                members
+                    .iter()
+                    .map(|(name, value)| (name.clone(), value))
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+}
+
+static DATE_AND_TIME: GILOnceCell<Py<PyTuple>> = GILOnceCell::new();
+
+// Returns `true` if _value_ is a `datetime.date`, `datetime.datetime` or
+// `datetime.time`. `datetime` is a subclass of `date`.
+fn is_date_or_time(value: &Bound<'_, PyAny>) -> bool {
+    let py = value.py();
+    DATE_AND_TIME
+        .get_or_try_init(py, || -> PyResult<_> {
+            let module = py.import_bound("datetime")?;
+            let types = [module.getattr("date")?, module.getattr("time")?];
+            Ok(PyTuple::new_bound(py, types).unbind())
+        })
+        .and_then(|types| value.is_instance(types.bind(py)))
+        .unwrap_or(false)
+}
+
+// Python dicts and other mappings are objects, and lists, tuples and other
+// sequences, except strings and bytes, are arrays. Mapping keys that are not
+// strings are skipped. Dates and times are `DateTime`s.
+impl JsonLike for Bound<'_, PyAny> {
+    fn kind(&self) -> Kind {
+        if self.is_none() {
+            Kind::Null
+        } else if let Ok(value) = self.downcast::<PyBool>() {
+            Kind::Bool(value.is_true())
+        } else if let Ok(value) = self.downcast::<PyInt>() {
+            match value.extract::<i64>() {
+                Ok(value) => Kind::Int(value),
+                Err(_) => value.extract::<f64>().map_or(Kind::Other, Kind::Float),
+            }
+        } else if let Ok(value) = self.downcast::<PyFloat>() {
+            Kind::Float(value.value())
+        } else if let Ok(value) = self.downcast::<PyString>() {
+            value
+                .to_str()
+                .map_or(Kind::Other, |value| Kind::String(value.to_owned()))
+        } else if let Ok(items) = self.downcast::<PyList>() {
+            Kind::Array(items.len())
+        } else if let Ok(items) = self.downcast::<PyTuple>() {
+            Kind::Array(items.len())
+        } else if self.is_instance_of::<PyDict>() || self.downcast::<PyMapping>().is_ok() {
+            Kind::Object
+        } else if self.is_instance_of::<PyBytes>() {
+            Kind::Bytes
+        } else if let Ok(items) = self.downcast::<PySequence>() {
+            items.len().map_or(Kind::Other, Kind::Array)
+        } else if is_date_or_time(self) {
+            self.call_method0("isoformat")
+                .and_then(|text| text.extract())
+                .map_or(Kind::Other, Kind::DateTime)
+        } else {
+            Kind::Other
+        }
+    }
+
+    fn get_index(&self, index: usize) -> Option<Self> {
+        if let Ok(items) = self.downcast::<PyList>() {
+            items.get_item(index).ok()
+        } else if let Ok(items) = self.downcast::<PyTuple>() {
+            items.get_item(index).ok()
+        } else if self.is_instance_of::<PyString>() || self.is_instance_of::<PyBytes>() {
+            None
+        } else {
+            self.downcast::<PySequence>().ok()?.get_item(index).ok()
+        }
+    }
+
+    fn get_member(&self, name: &str) -> Option<Self> {
+        if let Ok(members) = self.downcast::<PyDict>() {
+            members.get_item(name).ok().flatten()
+        } else {
+            self.downcast::<PyMapping>().ok()?.get_item(name).ok()
+        }
+    }
+
+    fn items(&self) -> Vec<Self> {
+        if let Ok(items) = self.downcast::<PyList>() {
+            items.iter().collect()
+        } else if let Ok(items) = self.downcast::<PyTuple>() {
+            items.iter().collect()
+        } else if let Kind::Array(_) = self.kind() {
+            self.iter()
+                .map(|items| items.filter_map(Result::ok).collect())
+                .unwrap_or_default()
+        } else {
+            Vec::new()
+        }
+    }
+
+    fn members(&self) -> Vec<(String, Self)> {
+        if let Ok(members) = self.downcast::<PyDict>() {
+            return members
+                .iter()
+                .filter_map(|(name, value)| Some((name.extract::<String>().ok()?, value)))
+                .collect();
+        }
+
+        let Ok(items) = self
+            .downcast::<PyMapping>()
+            .map_err(PyErr::from)
+            .and_then(|members| members.items()?.iter())
+        else {
+            return Vec::new();
+        };
+
+        items
+            .filter_map(|item| item.ok()?.extract::<(String, Bound<'_, PyAny>)>().ok())
+            .collect()
+    }
+
+    fn with_bytes<T>(&self, f: impl FnOnce(&[u8]) -> T) -> Option<T> {
+        self.downcast::<PyBytes>()
+            .ok()
+            .map(|value| f(value.as_bytes()))
+    }
+
+    fn identity(&self) -> Option<usize> {
+        Some(self.as_ptr() as usize)
+    }
+}
+
+/// A Python value without a GIL lifetime, for iterators that are resumed
+/// from Python.
+pub struct PyValue(pub PyObject);
+
+impl Clone for PyValue {
+    fn clone(&self) -> Self {
+        Python::with_gil(|py| PyValue(self.0.clone_ref(py)))
+    }
+}
+
+impl From<Bound<'_, PyAny>> for PyValue {
+    fn from(value: Bound<'_, PyAny>) -> Self {
+        PyValue(value.unbind())
+    }
+}
+
+impl JsonLike for PyValue {
+    fn kind(&self) -> Kind {
+        Python::with_gil(|py| self.0.bind(py).kind())
+    }
+
+    fn get_index(&self, index: usize) -> Option<Self> {
+        Python::with_gil(|py| self.0.bind(py).get_index(index).map(PyValue::from))
+    }
+
+    fn get_member(&self, name: &str) -> Option<Self> {
+        Python::with_gil(|py| self.0.bind(py).get_member(name).map(PyValue::from))
+    }
+
+    fn items(&self) -> Vec<Self> {
+        Python::with_gil(|py| {
+            self.0
+                .bind(py)
+                .items()
+                .into_iter()
+                .map(PyValue::from)
+                .collect()
+        })
+    }
+
+    fn members(&self) -> Vec<(String, Self)> {
+        Python::with_gil(|py| {
+            self.0
+                .bind(py)
+                .members()
+                .into_iter()
+                .map(|(name, value)| (name, PyValue::from(value)))
+                .collect()
+        })
+    }
+
+    fn with_bytes<T>(&self, f: impl FnOnce(&[u8]) -> T) -> Option<T> {
+        Python::with_gil(|py| self.0.bind(py).with_bytes(f))
+    }
+
+    fn identity(&self) -> Option<usize> {
+        Some(self.0.as_ptr() as usize)
+    }
+}
@@ -0,0 +1,445 @@
+//! Query data loaded from JSON, YAML, TOML, CBOR and MessagePack files
+//!
+//! Each format's own value type implements `JsonLike`, so queries are
+//! evaluated against loaded data without converting it to JSON first. Object
+//! member order is kept, integers and floats stay distinct, and dates and
+//! times are `Kind::DateTime` values.
+//!
+//! Only string keys are object member names. Entries with other keys, which
+//! YAML, CBOR and MessagePack allow, are skipped.
+
+use std::fmt;
+use std::path::Path;
+use std::str::FromStr;
+
+use ciborium::value::Value as Cbor;
+use pyo3::prelude::*;
+use rmpv::Value as MessagePack;
+
+use crate::compile::to_python;
+use crate::errors::LiquidError;
+use crate::eval::{find, JsonLike, Kind};
+use crate::query::Query;
+use crate::yaml::Yaml;
+
+/// A data file format.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Json,
+    Yaml,
+    Toml,
+    Cbor,
+    MessagePack,
+}
+
+impl Format {
+    /// Guess the format of the file at _path_ from its extension.
+    pub fn from_path(path: &Path) -> Option<Format> {
+        match path.extension()?.to_str()?.to_ascii_lowercase().as_str() {
+            "json" => Some(Format::Json),
+            "yaml" | "yml" => Some(Format::Yaml),
+            "toml" => Some(Format::Toml),
+            "cbor" => Some(Format::Cbor),
+            "msgpack" | "mpk" => Some(Format::MessagePack),
+            _ => None,
+        }
+    }
+}
+
+impl FromStr for Format {
+    type Err = LiquidError;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name.to_ascii_lowercase().as_str() {
+            "json" => Ok(Format::Json),
+            "yaml" => Ok(Format::Yaml),
+            "toml" => Ok(Format::Toml),
+            "cbor" => Ok(Format::Cbor),
+            "msgpack" => Ok(Format::MessagePack),
+            _ => Err(LiquidError::typ(format!(
+                "unknown data format `{name}`, expected one of json, yaml, toml, cbor or msgpack"
+            ))),
+        }
+    }
+}
+
+impl fmt::Display for Format {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Format::Json => "JSON",
+            Format::Yaml => "YAML",
+            Format::Toml => "TOML",
+            Format::Cbor => "CBOR",
+            Format::MessagePack => "MessagePack",
+        })
+    }
+}
+
+/// Data loaded from a file, in its format's own value type.
+#[derive(Debug, Clone)]
+pub enum Document {
+    Json(serde_json::Value),
+    Yaml(Yaml),
+    Toml(toml::Value),
+    Cbor(Cbor),
+    MessagePack(MessagePack),
+}
+
+impl Document {
+    /// Parse _data_ as _format_.
+    pub fn parse(data: &[u8], format: Format) -> Result<Document, LiquidError> {
+        let invalid =
+            |err: &dyn fmt::Display| LiquidError::syntax(format!("invalid {format}, {err}"));
+        let text = || std::str::from_utf8(data).map_err(|err| invalid(&err));
+
+        Ok(match format {
+            Format::Json => {
+                Document::Json(serde_json::from_slice(data).map_err(|err| invalid(&err))?)
+            }
+            Format::Yaml => Document::Yaml(crate::yaml::parse(text()?)?),
+            Format::Toml => Document::Toml(toml::Value::Table(
+                toml::from_str(text()?).map_err(|err| invalid(&err))?,
+            )),
+            Format::Cbor => {
+                Document::Cbor(ciborium::from_reader(data).map_err(|err| invalid(&err))?)
+            }
+            Format::MessagePack => Document::MessagePack(
+                rmpv::decode::read_value(&mut &data[..]).map_err(|err| invalid(&err))?,
+            ),
+        })
+    }
+
+    /// Read and parse the file at _path_. If _format_ is `None`, it is guessed
+    /// from the file's extension.
+    pub fn load(path: &Path, format: Option<Format>) -> Result<Document, LiquidError> {
+        let format = format.or_else(|| Format::from_path(path)).ok_or_else(|| {
+            LiquidError::typ(format!(
+                "can't guess the format of `{}` from its extension",
+                path.display()
+            ))
+        })?;
+        Document::parse(&std::fs::read(path)?, format)
+    }
+
+    /// Return the normalized path and a Python copy of every value matched by
+    /// _query_ in this document.
+    pub fn find_python(
+        &self,
+        py: Python<'_>,
+        query: &Query,
+    ) -> Result<Vec<(Query, PyObject)>, LiquidError> {
+        fn matches<V: JsonLike>(
+            py: Python<'_>,
+            query: &Query,
+            root: &V,
+        ) -> Result<Vec<(Query, PyObject)>, LiquidError> {
+            Ok(find(query, root)?
+                .into_iter()
+                .map(|node| (node.location.to_query(), to_python(py, &node.value)))
+                .collect())
+        }
+
+        match self {
+            Document::Json(root) => matches(py, query, &root),
+            Document::Yaml(root) => matches(py, query, &root),
+            Document::Toml(root) => matches(py, query, &root),
+            Document::Cbor(root) => matches(py, query, &root),
+            Document::MessagePack(root) => matches(py, query, &root),
+        }
+    }
+}
+
+impl JsonLike for &Yaml {
+    fn kind(&self) -> Kind {
+        match self {
+            Yaml::Null => Kind::Null,
+            Yaml::Bool(value) => Kind::Bool(*value),
+            Yaml::Int(value) => Kind::Int(*value),
+            Yaml::Float(value) => Kind::Float(*value),
+            Yaml::String(value) => Kind::String(value.clone()),
+            Yaml::Timestamp(value) => Kind::DateTime(value.clone()),
+            Yaml::Sequence(items) => Kind::Array(items.len()),
+            Yaml::Mapping(_) => Kind::Object,
+        }
+    }
+
+    fn get_index(&self, index: usize) -> Option<Self> {
+        match self {
+            Yaml::Sequence(items) => items.get(index),
+            _ => None,
+        }
+    }
+
+    fn get_member(&self, name: &str) -> Option<Self> {
+        match self {
+            Yaml::Mapping(entries) => entries
+                .iter()
+                .find(|(key, _)| matches!(key, Yaml::String(key) if key == name))
+                .map(|(_, value)| value),
+            _ => None,
+        }
+    }
+
+    fn items(&self) -> Vec<Self> {
+        match self {
+            Yaml::Sequence(items) => items.iter().collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    fn members(&self) -> Vec<(String, Self)> {
+        match self {
+            Yaml::Mapping(entries) => entries
+                .iter()
+                .filter_map(|(key, value)| match key {
+                    Yaml::String(key) => Some((key.clone(), value)),
+                    _ => None,
+                })
+                .collect(),
+            _ => Vec::new(),
+        }
+    }
+}
+
+impl JsonLike for &toml::Value {
+    fn kind(&self) -> Kind {
+        match self {
+            toml::Value::Boolean(value) => Kind::Bool(*value),
+            toml::Value::Integer(value) => Kind::Int(*value),
+            toml::Value::Float(value) => Kind::Float(*value),
+            toml::Value::String(value) => Kind::String(value.clone()),
+            toml::Value::Datetime(value) => Kind::DateTime(value.to_string()),
+            toml::Value::Array(items) => Kind::Array(items.len()),
+            toml::Value::Table(_) => Kind::Object,
+        }
+    }
+
+    fn get_index(&self, index: usize) -> Option<Self> {
+        self.as_array()?.get(index)
+    }
+
+    fn get_member(&self, name: &str) -> Option<Self> {
+        self.as_table()?.get(name)
+    }
+
+    fn items(&self) -> Vec<Self> {
+        self.as_array()
+            .map(|items| items.iter().collect())
+            .unwrap_or_default()
+    }
+
+    fn members(&self) -> Vec<(String, Self)> {
+        self.as_table()
+            .map(|members| {
+                members
+                    .iter()
+                    .map(|(name, value)| (name.clone(), value))
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+}
+
+// Tags 0 and 1004 are RFC 3339 date-time and date strings, tag 1 is seconds
+// since the Unix epoch and tag 100 is days since the epoch. Other tags are
+// ignored in favour of the tagged value.
+impl JsonLike for &Cbor {
+    fn kind(&self) -> Kind {
+        match self {
+            Cbor::Null => Kind::Null,
+            Cbor::Bool(value) => Kind::Bool(*value),
+            Cbor::Integer(value) => {
+                let value = i128::from(*value);
+                i64::try_from(value).map_or(Kind::Float(value as f64), Kind::Int)
+            }
+            Cbor::Float(value) => Kind::Float(*value),
+            Cbor::Text(value) => Kind::String(value.clone()),
+            Cbor::Bytes(_) => Kind::Bytes,
+            Cbor::Array(items) => Kind::Array(items.len()),
+            Cbor::Map(_) => Kind::Object,
+            Cbor::Tag(0 | 1004, value) => match &**value {
+                Cbor::Text(value) => Kind::DateTime(value.clone()),
+                _ => Kind::Other,
+            },
+            Cbor::Tag(1, value) => match &**value {
+                Cbor::Integer(seconds) => i64::try_from(*seconds)
+                    .map_or(Kind::Other, |seconds| Kind::DateTime(timestamp(seconds, 0))),
+                Cbor::Float(seconds) if seconds.is_finite() => {
+                    let whole = seconds.floor();
+                    let nanos = ((seconds - whole) * 1e9).round().min(999_999_999.0);
+                    Kind::DateTime(timestamp(whole as i64, nanos as u32))
+                }
+                _ => Kind::Other,
+            },
+            Cbor::Tag(100, value) => match &**value {
+                Cbor::Integer(days) => {
+                    i64::try_from(*days).map_or(Kind::Other, |days| Kind::DateTime(date(days)))
+                }
+                _ => Kind::Other,
+            },
+            Cbor::Tag(_, value) => (&**value).kind(),
+            _ => Kind::Other,
+        }
+    }
+
+    fn get_index(&self, index: usize) -> Option<Self> {
+        match untag(self) {
+            Cbor::Array(items) => items.get(index),
+            _ => None,
+        }
+    }
+
+    fn get_member(&self, name: &str) -> Option<Self> {
+        match untag(self) {
+            Cbor::Map(entries) => entries
+                .iter()
+                .find(|(key, _)| key.as_text() == Some(name))
+                .map(|(_, value)| value),
+            _ => None,
+        }
+    }
+
+    fn items(&self) -> Vec<Self> {
+        match untag(self) {
+            Cbor::Array(items) => items.iter().collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    fn members(&self) -> Vec<(String, Self)> {
+        match untag(self) {
+            Cbor::Map(entries) => entries
+                .iter()
+                .filter_map(|(key, value)| Some((key.as_text()?.to_owned(), value)))
+                .collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    fn with_bytes<T>(&self, f: impl FnOnce(&[u8]) -> T) -> Option<T> {
+        match untag(self) {
+            Cbor::Bytes(value) => Some(f(value)),
+            _ => None,
+        }
+    }
+}
+
+fn untag(value: &Cbor) -> &Cbor {
+    match value {
+        Cbor::Tag(_, value) => untag(value),
+        value => value,
+    }
+}
+
+// Extension type -1 is a timestamp. Other extension types have no JSON
+// equivalent.
+impl JsonLike for &MessagePack {
+    fn kind(&self) -> Kind {
+        match self {
+            MessagePack::Nil => Kind::Null,
+            MessagePack::Boolean(value) => Kind::Bool(*value),
+            MessagePack::Integer(value) => value.as_i64().map_or_else(
+                || Kind::Float(value.as_f64().unwrap_or(f64::NAN)),
+                Kind::Int,
+            ),
+            MessagePack::F32(value) => Kind::Float(*value as f64),
+            MessagePack::F64(value) => Kind::Float(*value),
+            MessagePack::String(value) => value
+                .as_str()
+                .map_or(Kind::Other, |value| Kind::String(value.to_owned())),
+            MessagePack::Binary(_) => Kind::Bytes,
+            MessagePack::Array(items) => Kind::Array(items.len()),
+            MessagePack::Map(_) => Kind::Object,
+            MessagePack::Ext(-1, data) => {
+                let (seconds, nanos) = match data.len() {
+                    4 => (u32::from_be_bytes(data[..].try_into().unwrap()) as i64, 0),
+                    8 => {
+                        let value = u64::from_be_bytes(data[..].try_into().unwrap());
+                        ((value & 0x3_ffff_ffff) as i64, (value >> 34) as u32)
+                    }
+                    12 => (
+                        i64::from_be_bytes(data[4..].try_into().unwrap()),
+                        u32::from_be_bytes(data[..4].try_into().unwrap()),
+                    ),
+                    _ => return Kind::Other,
+                };
+                Kind::DateTime(timestamp(seconds, nanos))
+            }
+            MessagePack::Ext(..) => Kind::Other,
+        }
+    }
+
+    fn get_index(&self, index: usize) -> Option<Self> {
+        self.as_array()?.get(index)
+    }
+
+    fn get_member(&self, name: &str) -> Option<Self> {
+        self.as_map()?
+            .iter()
+            .find(|(key, _)| key.as_str() == Some(name))
+            .map(|(_, value)| value)
+    }
+
+    fn items(&self) -> Vec<Self> {
+        self.as_array()
+            .map(|items| items.iter().collect())
+            .unwrap_or_default()
+    }
+
+    fn members(&self) -> Vec<(String, Self)> {
+        self.as_map()
+            .map(|entries| {
+                entries
+                    .iter()
+                    .filter_map(|(key, value)| Some((key.as_str()?.to_owned(), value)))
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    fn with_bytes<T>(&self, f: impl FnOnce(&[u8]) -> T) -> Option<T> {
+        match self {
+            MessagePack::Binary(value) => Some(f(value)),
+            _ => None,
+        }
+    }
+}
+
+/// Return _seconds_ and _nanos_ since the Unix epoch as RFC 3339 UTC text.
+fn timestamp(seconds: i64, nanos: u32) -> String {
+    let (days, seconds) = (seconds.div_euclid(86_400), seconds.rem_euclid(86_400));
+    let mut text = format!(
+        "{}T{:02}:{:02}:{:02}",
+        date(days),
+        seconds / 3600,
+        seconds / 60 % 60,
+        seconds % 60
+    );
+
+    if nanos > 0 {
+        text.push_str(format!(".{nanos:09}").trim_end_matches('0'));
+    }
+
+    text.push('Z');
+    text
+}
+
+/// Return the date _days_ after the Unix epoch as RFC 3339 text.
+fn date(days: i64) -> String {
+    // Howard Hinnant's `civil_from_days`.
+    let days = days + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+    let month = if shifted_month < 10 {
+        shifted_month + 3
+    } else {
+        shifted_month - 9
+    };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    format!("{year:04}-{month:02}-{day:02}")
+}
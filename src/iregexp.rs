@@ -0,0 +1,407 @@
+//! I-Regexp (RFC 9485) patterns for the `match` and `search` functions
+//!
+//! Patterns are parsed following RFC 9485 and translated to the syntax of
+//! the `regex` crate, which matches in linear time. `.` matches any character
+//! other than `\n` and `\r`, and `^` and `$` are literal characters.
+//! `match` must match a whole string while `search` can match anywhere in it.
+
+use std::collections::{HashMap, VecDeque};
+use std::iter::Peekable;
+use std::str::CharIndices;
+use std::sync::{Arc, Mutex};
+
+use lazy_static::lazy_static;
+use regex::Regex;
+
+use crate::errors::LiquidError;
+
+/// The most compiled patterns kept by `cached`. Once full, the oldest pattern
+/// is discarded first.
+const CACHE_CAPACITY: usize = 256;
+
+/// The deepest that groups can nest, matching the `regex` crate's default
+/// nest limit. Parsing recurses once per group.
+const MAX_NESTING: usize = 250;
+
+type Cache = (
+    HashMap<(String, bool), Option<Arc<Regex>>>,
+    VecDeque<(String, bool)>,
+);
+
+lazy_static! {
+    static ref CACHE: Mutex<Cache> = Mutex::new(Default::default());
+}
+
+/// Return `Ok(())` if _pattern_ is a valid I-Regexp that compiles within the
+/// `regex` crate's size limits, or a syntax error describing the first
+/// problem otherwise. The compiled pattern is kept for `cached`.
+pub fn check(pattern: &str, full_match: bool) -> Result<(), LiquidError> {
+    let regex = Arc::new(compile(pattern, full_match)?);
+    remember((pattern.to_owned(), full_match), Some(regex));
+    Ok(())
+}
+
+/// Translate I-Regexp _pattern_ to `regex` crate syntax. If _full_match_ is
+/// `true`, the translated pattern only matches whole strings.
+pub fn translate(pattern: &str, full_match: bool) -> Result<String, LiquidError> {
+    let mut parser = Parser {
+        pattern,
+        chars: pattern.char_indices().peekable(),
+        out: String::with_capacity(pattern.len() + 8),
+        depth: 0,
+    };
+
+    if full_match {
+        parser.out.push_str(r"\A(?:");
+    }
+
+    parser.regexp()?;
+
+    if let Some((index, c)) = parser.chars.next() {
+        // Only an unbalanced `)` stops a top level regexp early.
+        return Err(parser.error(index, &format!("unexpected `{c}`")));
+    }
+
+    if full_match {
+        parser.out.push_str(r")\z");
+    }
+
+    Ok(parser.out)
+}
+
+/// Compile I-Regexp _pattern_.
+pub fn compile(pattern: &str, full_match: bool) -> Result<Regex, LiquidError> {
+    Regex::new(&translate(pattern, full_match)?)
+        .map_err(|err| LiquidError::syntax(format!("invalid I-Regexp pattern `{pattern}`, {err}")))
+}
+
+/// Return compiled I-Regexp _pattern_ from a shared cache, compiling it if
+/// necessary, or `None` if _pattern_ is not valid.
+pub fn cached(pattern: &str, full_match: bool) -> Option<Arc<Regex>> {
+    let key = (pattern.to_owned(), full_match);
+
+    if let Some(regex) = lock().0.get(&key) {
+        return regex.clone();
+    }
+
+    // Compile without holding the lock.
+    let regex = compile(pattern, full_match).ok().map(Arc::new);
+    remember(key, regex.clone());
+    regex
+}
+
+fn remember(key: (String, bool), regex: Option<Arc<Regex>>) {
+    let (patterns, order) = &mut *lock();
+
+    if !patterns.contains_key(&key) {
+        if order.len() >= CACHE_CAPACITY {
+            if let Some(oldest) = order.pop_front() {
+                patterns.remove(&oldest);
+            }
+        }
+        order.push_back(key.clone());
+        patterns.insert(key, regex);
+    }
+}
+
+fn lock() -> std::sync::MutexGuard<'static, Cache> {
+    // Nothing can panic while the lock is held, so a poisoned lock is fine.
+    CACHE.lock().unwrap_or_else(|err| err.into_inner())
+}
+
+struct Parser<'a> {
+    pattern: &'a str,
+    chars: Peekable<CharIndices<'a>>,
+    out: String,
+    // The number of groups open at the current position.
+    depth: usize,
+}
+
+impl Parser<'_> {
+    // i-regexp = branch *( "|" branch )
+    fn regexp(&mut self) -> Result<(), LiquidError> {
+        self.branch()?;
+        while self.eat('|') {
+            self.out.push('|');
+            self.branch()?;
+        }
+        Ok(())
+    }
+
+    // branch = *piece
+    fn branch(&mut self) -> Result<(), LiquidError> {
+        while let Some(&(_, c)) = self.chars.peek() {
+            if c == '|' || c == ')' {
+                break;
+            }
+            self.atom()?;
+            self.quantifier()?;
+        }
+        Ok(())
+    }
+
+    // atom = NormalChar / charClass / ( "(" i-regexp ")" )
+    fn atom(&mut self) -> Result<(), LiquidError> {
+        let (index, c) = self.next("an atom")?;
+        match c {
+            '(' => {
+                if self.depth >= MAX_NESTING {
+                    return Err(
+                        self.error(index, &format!("groups nest more than {MAX_NESTING} deep"))
+                    );
+                }
+                self.depth += 1;
+                self.out.push_str("(?:");
+                self.regexp()?;
+                self.depth -= 1;
+                match self.chars.next() {
+                    Some((_, ')')) => self.out.push(')'),
+                    _ => return Err(self.error(index, "unbalanced `(`")),
+                }
+            }
+            '.' => self.out.push_str(r"[^\n\r]"),
+            '[' => self.class_expression(index)?,
+            '\\' => {
+                self.escape(index)?;
+            }
+            '*' | '+' | '?' | '{' => {
+                return Err(self.error(index, &format!("nothing to repeat before `{c}`")))
+            }
+            ']' | '}' => return Err(self.error(index, &format!("unexpected `{c}`"))),
+            c => push_literal(&mut self.out, c),
+        }
+        Ok(())
+    }
+
+    // quantifier = ( "*" / "+" / "?" ) / range-quantifier
+    fn quantifier(&mut self) -> Result<(), LiquidError> {
+        match self.chars.peek() {
+            Some(&(_, c @ ('*' | '+' | '?'))) => {
+                self.chars.next();
+                self.out.push(c);
+            }
+            Some(&(index, '{')) => {
+                self.chars.next();
+                let min = self.digits(index)?;
+                let max = if self.eat(',') {
+                    match self.chars.peek() {
+                        Some((_, '}')) => None,
+                        _ => Some(self.digits(index)?),
+                    }
+                } else {
+                    Some(min)
+                };
+
+                if !self.eat('}') {
+                    return Err(self.error(index, "expected `}` to close a range quantifier"));
+                }
+
+                match max {
+                    Some(max) if max < min => {
+                        return Err(self.error(
+                            index,
+                            &format!("range quantifier `{{{min},{max}}}` is out of order"),
+                        ))
+                    }
+                    Some(max) if max == min => self.out.push_str(&format!("{{{min}}}")),
+                    Some(max) => self.out.push_str(&format!("{{{min},{max}}}")),
+                    None => self.out.push_str(&format!("{{{min},}}")),
+                }
+            }
+            _ => return Ok(()),
+        }
+
+        if let Some(&(index, c @ ('*' | '+' | '?' | '{'))) = self.chars.peek() {
+            return Err(self.error(index, &format!("nothing to repeat before `{c}`")));
+        }
+
+        Ok(())
+    }
+
+    // QuantExact = 1*%x30-39
+    fn digits(&mut self, start: usize) -> Result<u32, LiquidError> {
+        let mut digits = String::new();
+        while let Some(&(_, c)) = self.chars.peek() {
+            if !c.is_ascii_digit() {
+                break;
+            }
+            digits.push(c);
+            self.chars.next();
+        }
+
+        if digits.is_empty() {
+            return Err(self.error(start, "expected digits in a range quantifier"));
+        }
+
+        digits
+            .parse()
+            .map_err(|_| self.error(start, "range quantifier is too large"))
+    }
+
+    // charClassExpr = "[" [ "^" ] ( "-" / CCE1 ) *CCE1 [ "-" ] "]"
+    fn class_expression(&mut self, start: usize) -> Result<(), LiquidError> {
+        self.out.push('[');
+        if self.eat('^') {
+            self.out.push('^');
+        }
+
+        if self.eat('-') {
+            self.out.push_str(r"\-");
+        } else {
+            self.class_item(start)?;
+        }
+
+        loop {
+            match self.chars.peek() {
+                Some((_, ']')) => break,
+                Some((_, '-')) => {
+                    self.chars.next();
+                    if !matches!(self.chars.peek(), Some((_, ']'))) {
+                        return Err(self.error(start, "`-` must be escaped inside `[...]`"));
+                    }
+                    self.out.push_str(r"\-");
+                }
+                Some(_) => self.class_item(start)?,
+                None => return Err(self.error(start, "unbalanced `[`")),
+            }
+        }
+
+        self.chars.next();
+        self.out.push(']');
+        Ok(())
+    }
+
+    // CCE1 = ( CCchar [ "-" CCchar ] ) / charClassEsc
+    fn class_item(&mut self, start: usize) -> Result<(), LiquidError> {
+        let Some(first) = self.class_char()? else {
+            // A character class escape can't start a range.
+            return Ok(());
+        };
+
+        // A `-` followed by `]` is a literal `-` at the end of the class.
+        let mut lookahead = self.chars.clone();
+        if matches!(lookahead.next(), Some((_, '-')))
+            && !matches!(lookahead.next(), Some((_, ']')) | None)
+        {
+            self.chars.next();
+            self.out.push('-');
+            match self.class_char()? {
+                Some(last) if last < first => {
+                    return Err(self.error(
+                        start,
+                        &format!("character range `{first}-{last}` is out of order"),
+                    ))
+                }
+                Some(_) => (),
+                None => return Err(self.error(start, "invalid character range")),
+            }
+        }
+
+        Ok(())
+    }
+
+    // Writes one character class item, returning the character it stands for,
+    // or `None` for a multi-character escape like `\p{L}`.
+    //
+    // CCchar = ( %x00-2C / %x2E-5A / %x5E-D7FF / %xE000-10FFFF ) / SingleCharEsc
+    fn class_char(&mut self) -> Result<Option<char>, LiquidError> {
+        match self.next("a character class item")? {
+            (index, '\\') => self.escape(index),
+            (index, c @ ('[' | ']' | '-')) => {
+                Err(self.error(index, &format!("`{c}` must be escaped inside `[...]`")))
+            }
+            (_, c) => {
+                push_literal(&mut self.out, c);
+                Ok(Some(c))
+            }
+        }
+    }
+
+    // SingleCharEsc / catEsc / complEsc
+    fn escape(&mut self, start: usize) -> Result<Option<char>, LiquidError> {
+        let (_, c) = self.next("an escaped character")?;
+        let c = match c {
+            'n' => '\n',
+            'r' => '\r',
+            't' => '\t',
+            '(' | ')' | '*' | '+' | ',' | '-' | '.' | '?' | '[' | '\\' | ']' | '^' | '{' | '|'
+            | '}' => c,
+            'p' | 'P' => {
+                let category = self.category(start)?;
+                self.out.push('\\');
+                self.out.push(c);
+                self.out.push('{');
+                self.out.push_str(&category);
+                self.out.push('}');
+                return Ok(None);
+            }
+            c => return Err(self.error(start, &format!("invalid escape `\\{c}`"))),
+        };
+
+        push_literal(&mut self.out, c);
+        Ok(Some(c))
+    }
+
+    // "{" charProp "}", where charProp is a Unicode general category.
+    fn category(&mut self, start: usize) -> Result<String, LiquidError> {
+        if !self.eat('{') {
+            return Err(self.error(start, "expected `{` after `\\p`"));
+        }
+
+        let mut name = String::new();
+        while let Some((_, c)) = self.chars.next() {
+            if c == '}' {
+                let valid = match name.as_bytes() {
+                    [major] => b"LMNPZSC".contains(major),
+                    [b'L', minor] => b"lmotu".contains(minor),
+                    [b'M', minor] => b"cen".contains(minor),
+                    [b'N', minor] => b"dlo".contains(minor),
+                    [b'P', minor] => b"cdefios".contains(minor),
+                    [b'Z', minor] => b"lps".contains(minor),
+                    [b'S', minor] => b"ckmo".contains(minor),
+                    [b'C', minor] => b"cfno".contains(minor),
+                    _ => false,
+                };
+
+                if !valid {
+                    return Err(self.error(start, &format!("unknown character category `{name}`")));
+                }
+
+                return Ok(name);
+            }
+            name.push(c);
+        }
+
+        Err(self.error(start, "expected `}` to close a character category"))
+    }
+
+    fn next(&mut self, expected: &str) -> Result<(usize, char), LiquidError> {
+        self.chars.next().ok_or_else(|| {
+            self.error(
+                self.pattern.len(),
+                &format!("expected {expected}, found end of pattern"),
+            )
+        })
+    }
+
+    fn eat(&mut self, c: char) -> bool {
+        self.chars.next_if(|&(_, next)| next == c).is_some()
+    }
+
+    fn error(&self, index: usize, message: &str) -> LiquidError {
+        LiquidError::syntax(format!(
+            "invalid I-Regexp pattern `{}`, {} at position {}",
+            self.pattern, message, index
+        ))
+    }
+}
+
+// Writes _c_ so that it matches itself, in or out of a character class.
+fn push_literal(out: &mut String, c: char) {
+    match c {
+        '\n' => out.push_str(r"\n"),
+        '\r' => out.push_str(r"\r"),
+        '\t' => out.push_str(r"\t"),
+        c => out.push_str(&regex::escape(c.encode_utf8(&mut [0; 4]))),
+    }
+}
@@ -1,23 +1,72 @@
-use std::{collections::HashMap, ops::RangeInclusive};
+use std::{
+    collections::HashMap,
+    fs,
+    hash::{Hash, Hasher},
+    ops::RangeInclusive,
+    path::{Path, PathBuf},
+};
 
+use lazy_static::lazy_static;
 use pest::{iterators::Pair, iterators::Pairs, Parser};
 use pest_derive::Parser;
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use rayon::prelude::*;
 
 use crate::errors::LiquidError;
+use crate::iregexp;
 use crate::markup::{Markup, RangeArgument, Token, Whitespace};
+use crate::model::Span;
 use crate::query::{
     ComparisonOperator, FilterExpression, LogicalOperator, Query, Segment, Selector,
 };
+use crate::range::parse_int_string;
 use crate::unescape::unescape;
 
 #[derive(Parser)]
 #[grammar = "markup.pest"]
 struct Liquid;
 
+lazy_static! {
+    static ref DEFAULT_LEXER: Lexer = Lexer::new();
+}
+
+/// Return `true` if _value_ would be tokenized as a single `Token::Word`.
+pub fn is_word(value: &str) -> bool {
+    let matches = |rule| {
+        Liquid::parse(rule, value)
+            .is_ok_and(|mut pairs| pairs.next().is_some_and(|p| p.as_str() == value))
+    };
+    matches(Rule::word) && !matches(Rule::reserved_word)
+}
+
+/// Return `true` if _value_ can be written as a `.name` query segment.
+pub fn is_member_name(value: &str) -> bool {
+    Liquid::parse(Rule::member_name_shorthand, value)
+        .is_ok_and(|mut pairs| pairs.next().is_some_and(|p| p.as_str() == value))
+}
+
+/// Return a shared `Lexer` with default options.
+pub fn default_lexer() -> &'static Lexer {
+    &DEFAULT_LEXER
+}
+
+#[pyclass(frozen)]
 pub struct Lexer {
     pub query_parser: QueryParser,
 }
 
+impl Hash for Lexer {
+    /// Hash the options that change how templates are tokenized.
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        let mut functions: Vec<_> = self.query_parser.functions.iter().collect();
+        functions.sort_by(|a, b| a.0.cmp(b.0));
+        functions.hash(state);
+        self.query_parser.index_range.hash(state);
+        self.query_parser.int_range.hash(state);
+    }
+}
+
 impl Lexer {
     pub fn new() -> Self {
         Lexer {
@@ -38,6 +87,59 @@ impl Lexer {
         tokens
     }
 
+    /// Tokenize _source_ and apply whitespace control to `Content` markup.
+    ///
+    /// _default_trim_ is used wherever a tag, output statement or comment
+    /// does not specify whitespace control, and at the start and end of
+    /// the template. It must not be `Whitespace::Default`.
+    pub fn tokenize_trimmed(
+        &self,
+        source: &str,
+        default_trim: &Whitespace,
+    ) -> Result<Vec<Markup>, LiquidError> {
+        let mut tokens = self.tokenize(source)?;
+        apply_whitespace_control(&mut tokens, default_trim)?;
+        Ok(tokens)
+    }
+
+    /// Tokenize _source_, applying whitespace control if _trim_ is given.
+    pub fn tokenize_with(
+        &self,
+        source: &str,
+        trim: Option<&Whitespace>,
+    ) -> Result<Vec<Markup>, LiquidError> {
+        match trim {
+            Some(default_trim) => self.tokenize_trimmed(source, default_trim),
+            None => self.tokenize(source),
+        }
+    }
+
+    /// Tokenize each of _sources_ in parallel, returning results in the same
+    /// order as _sources_.
+    pub fn tokenize_many<S: AsRef<str> + Sync>(
+        &self,
+        sources: &[S],
+        trim: Option<&Whitespace>,
+    ) -> Vec<Result<Vec<Markup>, LiquidError>> {
+        sources
+            .par_iter()
+            .map(|source| self.tokenize_with(source.as_ref(), trim))
+            .collect()
+    }
+
+    /// Read and tokenize each of _paths_ in parallel, returning results in the
+    /// same order as _paths_.
+    pub fn tokenize_paths<P: AsRef<Path> + Sync>(
+        &self,
+        paths: &[P],
+        trim: Option<&Whitespace>,
+    ) -> Vec<Result<Vec<Markup>, LiquidError>> {
+        paths
+            .par_iter()
+            .map(|path| self.tokenize_with(&fs::read_to_string(path)?, trim))
+            .collect()
+    }
+
     pub fn parse_query(&self, path: &str) -> Result<Query, LiquidError> {
         let mut pairs =
             Liquid::parse(Rule::query, path).map_err(|err| LiquidError::syntax(err.to_string()))?;
@@ -71,7 +173,8 @@ impl Lexer {
     fn parse_content(&self, pair: Pair<Rule>) -> Result<Markup, LiquidError> {
         let span = pair.as_span();
         Ok(Markup::Content {
-            span: (span.start(), span.end()),
+            span: Some((span.start(), span.end())),
+            trimmed_span: Some((span.start(), span.end())),
             text: pair.as_str().to_owned(),
         })
     }
@@ -85,7 +188,7 @@ impl Lexer {
         let end_wc_left = Whitespace::from_str(it.next().unwrap().as_str());
         let end_wc_right = Whitespace::from_str(it.next().unwrap().as_str());
         Ok(Markup::Raw {
-            span: (span.start(), span.end()),
+            span: Some((span.start(), span.end())),
             wc: (wc_left, wc_right, end_wc_left, end_wc_right),
             text,
         })
@@ -100,7 +203,7 @@ impl Lexer {
         let wc_right = Whitespace::from_str(it.next().unwrap().as_str());
 
         Ok(Markup::Comment {
-            span: (span.start(), span.end()),
+            span: Some((span.start(), span.end())),
             wc: (wc_left, wc_right),
             hashes,
             text,
@@ -120,7 +223,7 @@ impl Lexer {
         let wc_right = Whitespace::from_str(it.next().unwrap().as_str());
 
         Ok(Markup::Output {
-            span: (span.start(), span.end()),
+            span: Some((span.start(), span.end())),
             wc: (wc_left, wc_right),
             expression: tokens,
         })
@@ -145,7 +248,7 @@ impl Lexer {
         let wc_right = Whitespace::from_str(it.next().unwrap().as_str());
 
         Ok(Markup::Tag {
-            span: (span.start(), span.end()),
+            span: Some((span.start(), span.end())),
             name,
             wc: (wc_left, wc_right),
             expression: tokens,
@@ -168,7 +271,7 @@ impl Lexer {
             wc: (wc_left, wc_right),
             name: "liquid".to_owned(),
             statements,
-            span: (span.start(), span.end()),
+            span: Some((span.start(), span.end())),
         })
     }
 
@@ -186,11 +289,10 @@ impl Lexer {
                 let name = it.next().unwrap().as_str().to_owned();
                 let tokens: Result<Vec<_>, _> =
                     it.map(|token| self.parse_expr_token(token)).collect();
-                let expression =
-                    tokens.and_then(|v| if v.len() == 0 { Ok(None) } else { Ok(Some(v)) })?;
+                let expression = tokens.map(|v| if v.is_empty() { None } else { Some(v) })?;
 
                 Ok(Markup::Tag {
-                    span: (span.start(), span.end()),
+                    span: Some((span.start(), span.end())),
                     name,
                     wc: (Whitespace::Default, Whitespace::Default),
                     expression,
@@ -200,7 +302,7 @@ impl Lexer {
                 wc: (Whitespace::Default, Whitespace::Default),
                 hashes: "#".to_owned(),
                 text: pair.into_inner().next().unwrap().as_str().to_owned(),
-                span: (span.start(), span.end()),
+                span: Some((span.start(), span.end())),
             }),
             _ => unreachable!("{:#?}", pair),
         }
@@ -243,17 +345,16 @@ impl Lexer {
                 "for" => Token::For { span },
                 _ => unreachable!(),
             },
-            Rule::multiline_double_quoted | Rule::double_quoted => Token::StringLiteral {
-                span,
-                value: unescape(pair.as_str(), &span)?,
-            },
-            Rule::multiline_single_quoted | Rule::single_quoted => Token::StringLiteral {
+            Rule::multiline_double_quoted
+            | Rule::double_quoted
+            | Rule::multiline_single_quoted
+            | Rule::single_quoted => Token::StringLiteral {
                 span,
-                value: unescape(&pair.as_str().replace("\\'", "'"), &span)?,
+                value: self.parse_string(pair)?,
             },
             Rule::number => self.parse_number(pair)?,
             Rule::range => self.parse_range(pair)?,
-            Rule::query => Token::Query {
+            Rule::query | Rule::line_query => Token::Query {
                 span,
                 path: self.query_parser.parse(pair.into_inner())?,
             },
@@ -267,56 +368,10 @@ impl Lexer {
 
     fn parse_number(&self, expr: Pair<Rule>) -> Result<Token, LiquidError> {
         let span = self.as_span(&expr);
-
-        if expr.as_str() == "-0" {
-            return Ok(Token::IntegerLiteral { span, value: 0 });
-        }
-
-        let mut it = expr.into_inner();
-        let mut is_float = false;
-        let mut n = it.next().unwrap().as_str().to_string(); // int
-
-        if let Some(pair) = it.next() {
-            match pair.as_rule() {
-                Rule::frac => {
-                    is_float = true;
-                    n.push_str(pair.as_str());
-                }
-                Rule::exp => {
-                    let exp_str = pair.as_str();
-                    if exp_str.contains('-') {
-                        is_float = true;
-                    }
-                    n.push_str(exp_str);
-                }
-                _ => unreachable!(),
-            }
-        }
-
-        if let Some(pair) = it.next() {
-            let exp_str = pair.as_str();
-            if exp_str.contains('-') {
-                is_float = true;
-            }
-            n.push_str(exp_str);
-        }
-
-        if is_float {
-            Ok(Token::FloatLiteral {
-                span,
-                value: n
-                    .parse::<f64>()
-                    .map_err(|_| LiquidError::syntax(String::from("invalid float literal")))?,
-            })
-        } else {
-            Ok(Token::IntegerLiteral {
-                span,
-                value: n
-                    .parse::<f64>()
-                    .map_err(|_| LiquidError::syntax(String::from("invalid integer literal")))?
-                    as i64,
-            })
-        }
+        Ok(match self.query_parser.parse_number_literal(expr)? {
+            NumberLiteral::Int(value) => Token::IntegerLiteral { span, value },
+            NumberLiteral::Float(value) => Token::FloatLiteral { span, value },
+        })
     }
 
     fn parse_range(&self, expr: Pair<Rule>) -> Result<Token, LiquidError> {
@@ -339,28 +394,231 @@ impl Lexer {
                 }
                 _ => unreachable!(),
             },
-            Rule::query => Ok(RangeArgument::Query {
+            Rule::range_query => Ok(RangeArgument::Query {
                 span,
                 path: self.query_parser.parse(pair.into_inner())?,
             }),
-            Rule::string_literal | Rule::multiline_string_literal => {
-                Ok(RangeArgument::StringLiteral {
-                    span,
-                    value: pair.as_str().to_owned(),
-                })
+            Rule::double_quoted | Rule::single_quoted => {
+                let value = self.parse_string(pair)?;
+                self.validate_numeric_string(&value, span)?;
+                Ok(RangeArgument::StringLiteral { span, value })
             }
             _ => unreachable!("{:#?}", pair),
         }
     }
 
-    fn as_span(&self, pair: &Pair<Rule>) -> (usize, usize) {
+    fn parse_string(&self, pair: Pair<Rule>) -> Result<String, LiquidError> {
+        let span = self.as_span(&pair);
+        match pair.as_rule() {
+            Rule::multiline_double_quoted | Rule::double_quoted => unescape(pair.as_str(), &span),
+            Rule::multiline_single_quoted | Rule::single_quoted => {
+                unescape(&pair.as_str().replace("\\'", "'"), &span)
+            }
+            _ => unreachable!("{:#?}", pair),
+        }
+    }
+
+    /// Range endpoints given as strings are cast to integers when the range is
+    /// resolved. If such a string is an integer, it must be one we can
+    /// represent.
+    fn validate_numeric_string(&self, value: &str, span: Option<Span>) -> Result<(), LiquidError> {
+        match parse_int_string(value) {
+            Some(Ok(int)) if self.query_parser.int_range.contains(&int) => Ok(()),
+            Some(_) => Err(LiquidError::syntax(format!(
+                "integer literal out of range `{}`",
+                value.trim()
+            ))
+            .with_span(span)),
+            None => Ok(()),
+        }
+    }
+
+    fn as_span(&self, pair: &Pair<Rule>) -> Option<Span> {
         let _span = pair.as_span();
-        return (_span.start(), _span.end());
+        Some((_span.start(), _span.end()))
+    }
+}
+
+/// Trim `Content` text in _tokens_ according to the whitespace control of
+/// neighbouring markup.
+pub fn apply_whitespace_control(
+    tokens: &mut [Markup],
+    default_trim: &Whitespace,
+) -> Result<(), LiquidError> {
+    if *default_trim == Whitespace::Default {
+        return Err(LiquidError::typ(String::from(
+            "the default trim mode can't be Whitespace.Default",
+        )));
+    }
+
+    let mut left_trim = Whitespace::Default;
+
+    for i in 0..tokens.len() {
+        let right_trim = tokens
+            .get(i + 1)
+            .map(|t| t.left_wc())
+            .unwrap_or(Whitespace::Default);
+
+        if let Markup::Content {
+            text,
+            span,
+            trimmed_span,
+        } = &mut tokens[i]
+        {
+            let (start, end) = trim_bounds(
+                text,
+                resolve_trim(&left_trim, default_trim),
+                resolve_trim(&right_trim, default_trim),
+            );
+            *trimmed_span = span.map(|span| (span.0 + start, span.0 + end));
+            *text = text[start..end].to_owned();
+            left_trim = Whitespace::Default;
+        } else {
+            left_trim = tokens[i].right_wc();
+        }
+    }
+
+    Ok(())
+}
+
+fn resolve_trim<'a>(wc: &'a Whitespace, default_trim: &'a Whitespace) -> &'a Whitespace {
+    if *wc == Whitespace::Default {
+        default_trim
+    } else {
+        wc
+    }
+}
+
+/// Return the byte range of _text_ that remains after trimming.
+fn trim_bounds(text: &str, left_trim: &Whitespace, right_trim: &Whitespace) -> (usize, usize) {
+    let start = match left_trim {
+        Whitespace::Minus => text.len() - text.trim_start().len(),
+        Whitespace::Smart => text.len() - text.trim_start_matches(['\r', '\n']).len(),
+        Whitespace::Plus | Whitespace::Default => 0,
+    };
+
+    let rest = &text[start..];
+    let end = start
+        + match right_trim {
+            Whitespace::Minus => rest.trim_end().len(),
+            Whitespace::Smart => rest.trim_end_matches(['\r', '\n']).len(),
+            Whitespace::Plus | Whitespace::Default => rest.len(),
+        };
+
+    (start, end)
+}
+
+impl Default for Lexer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[pymethods]
+impl Lexer {
+    /// _functions_ are JSONPath function extensions available to filter
+    /// selectors, in addition to the standard functions unless
+    /// _standard_functions_ is `False`. Array indices in queries must be
+    /// between -_max_index_ and _max_index_, and integer literals, in
+    /// templates and in filter selectors, between -_max_int_ and _max_int_.
+    #[new]
+    #[pyo3(signature = (
+        *,
+        functions=None,
+        standard_functions=true,
+        max_index=2_i64.pow(53) - 1,
+        max_int=2_i64.pow(53) - 1,
+    ))]
+    fn py_new(
+        functions: Option<HashMap<String, FunctionSignature>>,
+        standard_functions: bool,
+        max_index: i64,
+        max_int: i64,
+    ) -> PyResult<Self> {
+        if max_index < 0 {
+            return Err(PyValueError::new_err("max_index must not be negative"));
+        }
+
+        if max_int < 0 {
+            return Err(PyValueError::new_err("max_int must not be negative"));
+        }
+
+        let mut all_functions = if standard_functions {
+            self::standard_functions()
+        } else {
+            HashMap::new()
+        };
+        all_functions.extend(functions.unwrap_or_default());
+
+        Ok(Lexer {
+            query_parser: QueryParser {
+                index_range: (-max_index..=max_index),
+                int_range: (-max_int..=max_int),
+                functions: all_functions,
+            },
+        })
+    }
+
+    #[pyo3(name = "tokenize", signature = (source, *, trim=None))]
+    fn py_tokenize(
+        &self,
+        py: Python<'_>,
+        source: &str,
+        trim: Option<Whitespace>,
+    ) -> PyResult<Vec<Markup>> {
+        Ok(py.allow_threads(|| self.tokenize_with(source, trim.as_ref()))?)
+    }
+
+    /// Tokenize many templates in parallel. The result for a template that
+    /// fails to tokenize is an exception instead of a list of markup.
+    #[pyo3(name = "tokenize_many", signature = (sources, *, trim=None))]
+    pub(crate) fn py_tokenize_many(
+        &self,
+        py: Python<'_>,
+        sources: Vec<String>,
+        trim: Option<Whitespace>,
+    ) -> Vec<PyObject> {
+        let results = py.allow_threads(|| self.tokenize_many(&sources, trim.as_ref()));
+        results_into_py(py, results)
+    }
+
+    /// Read and tokenize many template files in parallel. The result for a
+    /// template that fails to load or tokenize is an exception instead of a
+    /// list of markup.
+    #[pyo3(name = "tokenize_paths", signature = (paths, *, trim=None))]
+    pub(crate) fn py_tokenize_paths(
+        &self,
+        py: Python<'_>,
+        paths: Vec<PathBuf>,
+        trim: Option<Whitespace>,
+    ) -> Vec<PyObject> {
+        let results = py.allow_threads(|| self.tokenize_paths(&paths, trim.as_ref()));
+        results_into_py(py, results)
+    }
+
+    #[pyo3(name = "parse_query")]
+    fn py_parse_query(&self, path: &str) -> PyResult<Query> {
+        Ok(self.parse_query(path)?)
+    }
+
+    #[pyo3(name = "parse_jsonpath_query")]
+    fn py_parse_jsonpath_query(&self, path: &str) -> PyResult<Query> {
+        Ok(self.parse_jsonpath_query(path)?)
+    }
+
+    /// Return the names of JSONPath function extensions known to this lexer.
+    fn function_names(&self) -> Vec<String> {
+        let mut names: Vec<String> = self.query_parser.functions.keys().cloned().collect();
+        names.sort();
+        names
     }
 }
 
 pub struct QueryParser {
+    /// The range of array indices and slice arguments in queries.
     pub index_range: RangeInclusive<i64>,
+    /// The range of integer literals.
+    pub int_range: RangeInclusive<i64>,
     pub functions: HashMap<String, FunctionSignature>,
 }
 
@@ -368,6 +626,7 @@ impl QueryParser {
     pub fn new() -> Self {
         QueryParser {
             index_range: ((-2_i64).pow(53) + 1..=2_i64.pow(53) - 1),
+            int_range: ((-2_i64).pow(53) + 1..=2_i64.pow(53) - 1),
             functions: standard_functions(),
         }
     }
@@ -554,7 +813,7 @@ impl QueryParser {
                 left: Box::new(and_expr),
                 operator: LogicalOperator::And,
                 right: Box::new(right),
-                span: span,
+                span,
             };
         }
 
@@ -663,58 +922,92 @@ impl QueryParser {
 
     fn parse_number(&self, expr: Pair<Rule>) -> Result<FilterExpression, LiquidError> {
         let span = self.as_span(&expr);
-        if expr.as_str() == "-0" {
-            return Ok(FilterExpression::Int { value: 0, span });
-        }
-
-        // TODO: change pest grammar to indicate positive or negative exponent?
-        let mut it = expr.into_inner();
-        let mut is_float = false;
-        let mut n = it.next().unwrap().as_str().to_string(); // int
+        Ok(match self.parse_number_literal(expr)? {
+            NumberLiteral::Int(value) => FilterExpression::Int { value, span },
+            NumberLiteral::Float(value) => FilterExpression::Float { value, span },
+        })
+    }
 
-        if let Some(pair) = it.next() {
+    /// Parse a `number` pair into an exact integer or a float.
+    ///
+    /// Numbers with a fractional part or a negative exponent are floats.
+    /// Everything else is an integer, which must be representable without
+    /// loss of precision and fall within `int_range`.
+    fn parse_number_literal(&self, expr: Pair<Rule>) -> Result<NumberLiteral, LiquidError> {
+        let literal = expr.as_str();
+        let offset = expr.as_span().start();
+        let mut int = literal;
+        let mut frac: Option<&str> = None;
+        let mut exp: Option<&str> = None;
+
+        for pair in expr.into_inner() {
             match pair.as_rule() {
+                Rule::int => (),
                 Rule::frac => {
-                    is_float = true;
-                    n.push_str(pair.as_str());
+                    int = &literal[..pair.as_span().start() - offset];
+                    frac = Some(pair.as_str());
                 }
                 Rule::exp => {
-                    let exp_str = pair.as_str();
-                    if exp_str.contains('-') {
-                        is_float = true;
+                    if frac.is_none() {
+                        int = &literal[..pair.as_span().start() - offset];
                     }
-                    n.push_str(exp_str);
+                    exp = Some(pair.as_str());
                 }
                 _ => unreachable!(),
             }
         }
 
-        if let Some(pair) = it.next() {
-            let exp_str = pair.as_str();
-            if exp_str.contains('-') {
-                is_float = true;
+        if frac.is_some() || exp.is_some_and(|e| e.contains('-')) {
+            let value = literal
+                .parse::<f64>()
+                .map_err(|_| LiquidError::syntax(format!("invalid float literal `{}`", literal)))?;
+
+            if !value.is_finite() {
+                return Err(LiquidError::syntax(format!(
+                    "float literal out of range `{}`",
+                    literal
+                )));
             }
-            n.push_str(exp_str);
-        }
 
-        if is_float {
-            Ok(FilterExpression::Float {
-                value: n
-                    .parse::<f64>()
-                    .map_err(|_| LiquidError::syntax(String::from("invalid float literal")))?,
-                span,
-            })
+            Ok(NumberLiteral::Float(value))
         } else {
-            Ok(FilterExpression::Int {
-                value: n
-                    .parse::<f64>()
-                    .map_err(|_| LiquidError::syntax(String::from("invalid integer literal")))?
-                    as i64,
-                span,
-            })
+            self.parse_integer_literal(int, exp).map(NumberLiteral::Int)
         }
     }
 
+    /// Parse an integer with an optional non-negative exponent, like `1e3`,
+    /// without going through a float.
+    fn parse_integer_literal(&self, int: &str, exp: Option<&str>) -> Result<i64, LiquidError> {
+        let out_of_range = || {
+            LiquidError::syntax(format!(
+                "integer literal out of range `{}{}`",
+                int,
+                exp.unwrap_or_default()
+            ))
+        };
+
+        let mut value = int.parse::<i64>().map_err(|_| out_of_range())?;
+
+        // Zero is zero whatever the exponent, even one that doesn't fit in a u32.
+        if let Some(exp) = exp.filter(|_| value != 0) {
+            let power = exp[1..]
+                .trim_start_matches('+')
+                .parse::<u32>()
+                .map_err(|_| out_of_range())?;
+
+            value = 10_i64
+                .checked_pow(power)
+                .and_then(|m| value.checked_mul(m))
+                .ok_or_else(out_of_range)?;
+        }
+
+        if !self.int_range.contains(&value) {
+            return Err(out_of_range());
+        }
+
+        Ok(value)
+    }
+
     fn parse_test_expression(&self, expr: Pair<Rule>) -> Result<FilterExpression, LiquidError> {
         let mut it = expr.into_inner();
         let pair = it.next().unwrap();
@@ -770,10 +1063,20 @@ impl QueryParser {
         let span = self.as_span(&pair);
         let name = pair.as_str();
         let args: Result<Vec<_>, _> = it.map(|ex| self.parse_function_argument(ex)).collect();
+        let args = self.assert_well_typed(name, args?)?;
+
+        // Catch bad regular expressions, including those too big to compile,
+        // now rather than when a template is rendered. Patterns that come
+        // from data are checked when used.
+        if let ("match" | "search", [_, FilterExpression::StringLiteral { value, span }]) =
+            (name, args.as_slice())
+        {
+            iregexp::check(value, name == "match").map_err(|err| err.with_span(*span))?;
+        }
 
         Ok(FilterExpression::Function {
             name: name.to_string(),
-            args: self.assert_well_typed(name, args?)?,
+            args,
             span,
         })
     }
@@ -890,7 +1193,7 @@ impl QueryParser {
         }
     }
 
-    fn assert_well_typed(
+    pub(crate) fn assert_well_typed(
         &self,
         func_name: &str,
         args: Vec<FilterExpression>,
@@ -1001,24 +1304,65 @@ impl QueryParser {
         }
     }
 
-    fn as_span(&self, pair: &Pair<Rule>) -> (usize, usize) {
+    fn as_span(&self, pair: &Pair<Rule>) -> Option<Span> {
         let _span = pair.as_span();
-        return (_span.start(), _span.end());
+        Some((_span.start(), _span.end()))
     }
 }
 
-#[derive(Debug)]
+impl Default for QueryParser {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum NumberLiteral {
+    Int(i64),
+    Float(f64),
+}
+
+fn results_into_py(
+    py: Python<'_>,
+    results: Vec<Result<Vec<Markup>, LiquidError>>,
+) -> Vec<PyObject> {
+    results
+        .into_iter()
+        .map(|result| match result {
+            Ok(tokens) => tokens.into_py(py),
+            Err(err) => PyErr::from(err).into_value(py).into_py(py),
+        })
+        .collect()
+}
+
+#[pyclass(eq, eq_int)]
+#[derive(Debug, Clone, PartialEq, Hash)]
 pub enum ExpressionType {
     Logical,
     Nodes,
     Value,
 }
 
+#[pyclass(frozen)]
+#[derive(Debug, Clone, Hash)]
 pub struct FunctionSignature {
+    #[pyo3(get)]
     pub param_types: Vec<ExpressionType>,
+    #[pyo3(get)]
     pub return_type: ExpressionType,
 }
 
+#[pymethods]
+impl FunctionSignature {
+    #[new]
+    fn py_new(param_types: Vec<ExpressionType>, return_type: ExpressionType) -> Self {
+        FunctionSignature {
+            param_types,
+            return_type,
+        }
+    }
+}
+
 pub fn standard_functions() -> HashMap<String, FunctionSignature> {
     let mut functions = HashMap::new();
 
@@ -1,42 +1,110 @@
+// pyo3 0.22 trips `useless_conversion` for methods returning `PyResult`.
+#![allow(clippy::useless_conversion)]
+
+pub mod analysis;
+pub mod block;
+pub mod build;
+pub mod cache;
+pub mod compile;
+pub mod dependency;
 pub mod errors;
+pub mod eval;
+pub mod formats;
+pub mod iregexp;
 pub mod lexer;
+pub mod loader;
 pub mod markup;
+pub mod model;
+pub mod mutate;
+pub mod pointer;
 pub mod query;
+pub mod range;
+pub mod serialize;
+pub mod stream;
 pub mod unescape;
+pub mod visit;
+pub mod yaml;
 
+use analysis::TemplateAnalysis;
+use block::BlockNode;
 use errors::LiquidError;
-use markup::Markup;
+use markup::{Markup, Whitespace};
 use pyo3::prelude::*;
 use query::Query;
+use std::path::PathBuf;
+
+#[pyfunction]
+#[pyo3(signature = (source, *, trim=None))]
+fn tokenize(
+    py: Python<'_>,
+    source: &str,
+    trim: Option<Whitespace>,
+) -> Result<Vec<Markup>, LiquidError> {
+    py.allow_threads(|| lexer::default_lexer().tokenize_with(source, trim.as_ref()))
+}
+
+#[pyfunction]
+#[pyo3(signature = (sources, *, trim=None))]
+fn tokenize_many(py: Python<'_>, sources: Vec<String>, trim: Option<Whitespace>) -> Vec<PyObject> {
+    lexer::default_lexer().py_tokenize_many(py, sources, trim)
+}
+
+#[pyfunction]
+#[pyo3(signature = (paths, *, trim=None))]
+fn tokenize_paths(py: Python<'_>, paths: Vec<PathBuf>, trim: Option<Whitespace>) -> Vec<PyObject> {
+    lexer::default_lexer().py_tokenize_paths(py, paths, trim)
+}
 
 #[pyfunction]
-fn tokenize(source: &str) -> Result<Vec<Markup>, LiquidError> {
-    lexer::Lexer::new().tokenize(source)
+fn parse_blocks(tokens: Vec<Markup>) -> Result<Vec<BlockNode>, LiquidError> {
+    block::parse_blocks(&tokens, &block::standard_blocks())
+}
+
+#[pyfunction]
+fn analyze(tokens: Vec<Markup>) -> Result<TemplateAnalysis, LiquidError> {
+    analysis::analyze(&tokens)
 }
 
 #[pyfunction]
 fn parse_query(path: &str) -> Result<Query, LiquidError> {
-    lexer::Lexer::new().parse_query(path)
+    lexer::default_lexer().parse_query(path)
 }
 
 #[pyfunction]
 fn parse_jsonpath_query(path: &str) -> Result<Query, LiquidError> {
-    lexer::Lexer::new().parse_jsonpath_query(path)
+    lexer::default_lexer().parse_jsonpath_query(path)
+}
+
+/// Return template source text for _tokens_. Unless _validate_ is `False`,
+/// raise an error if any markup would not be read back by _lexer_ as the
+/// same markup.
+#[pyfunction]
+#[pyo3(signature = (tokens, *, validate=true, lexer=None))]
+fn to_source(
+    tokens: Vec<Markup>,
+    validate: bool,
+    lexer: Option<PyRef<'_, lexer::Lexer>>,
+) -> Result<String, LiquidError> {
+    if validate {
+        build::to_source(&tokens, lexer.as_deref().unwrap_or(lexer::default_lexer()))
+    } else {
+        Ok(build::source_text(&tokens))
+    }
 }
 
 #[pyfunction]
 fn dump(source: &str) {
-    lexer::Lexer::new().dump(source);
+    lexer::default_lexer().dump(source);
 }
 
 #[pyfunction]
 fn dump_query(path: &str) {
-    lexer::Lexer::new().dump_query(path);
+    lexer::default_lexer().dump_query(path);
 }
 
 #[pyfunction]
 fn unescape_string(s: &str) -> Result<String, LiquidError> {
-    unescape::unescape(s, &(0, 0))
+    unescape::unescape(s, &None)
 }
 
 #[pymodule]
@@ -61,12 +129,36 @@ fn _liquid2(m: &Bound<'_, PyModule>) -> PyResult<()> {
         "LiquidExtensionError",
         m.py().get_type_bound::<errors::LiquidExtensionError>(),
     )?;
+    m.add(
+        "TemplateNotFoundError",
+        m.py().get_type_bound::<errors::TemplateNotFoundError>(),
+    )?;
+    m.add(
+        "LiquidSerializationError",
+        m.py().get_type_bound::<errors::LiquidSerializationError>(),
+    )?;
     m.add_function(wrap_pyfunction!(dump, m)?)?;
     m.add_function(wrap_pyfunction!(tokenize, m)?)?;
+    m.add_function(wrap_pyfunction!(tokenize_many, m)?)?;
+    m.add_function(wrap_pyfunction!(tokenize_paths, m)?)?;
+    m.add_function(wrap_pyfunction!(parse_blocks, m)?)?;
+    m.add_function(wrap_pyfunction!(analyze, m)?)?;
     m.add_function(wrap_pyfunction!(parse_query, m)?)?;
     m.add_function(wrap_pyfunction!(parse_jsonpath_query, m)?)?;
     m.add_function(wrap_pyfunction!(unescape_string, m)?)?;
     m.add_function(wrap_pyfunction!(dump_query, m)?)?;
+    m.add_function(wrap_pyfunction!(to_source, m)?)?;
+    m.add_function(wrap_pyfunction!(visit::py_walk, m)?)?;
+    m.add_function(wrap_pyfunction!(compile::py_compile, m)?)?;
+    m.add_function(wrap_pyfunction!(compile::query_cache_stats, m)?)?;
+    m.add_function(wrap_pyfunction!(compile::clear_query_cache, m)?)?;
+    m.add_function(wrap_pyfunction!(serialize::py_dumps, m)?)?;
+    m.add_function(wrap_pyfunction!(serialize::py_loads, m)?)?;
+    m.add_function(wrap_pyfunction!(serialize::py_is_current, m)?)?;
+    m.add_class::<lexer::Lexer>()?;
+    m.add_class::<lexer::ExpressionType>()?;
+    m.add_class::<lexer::FunctionSignature>()?;
+    m.add_class::<query::Query>()?;
     m.add_class::<query::Segment>()?;
     m.add_class::<query::Selector>()?;
     m.add_class::<query::ComparisonOperator>()?;
@@ -76,5 +168,25 @@ fn _liquid2(m: &Bound<'_, PyModule>) -> PyResult<()> {
     m.add_class::<markup::Token>()?;
     m.add_class::<markup::RangeArgument>()?;
     m.add_class::<markup::Whitespace>()?;
+    m.add_class::<visit::NodeIterator>()?;
+    m.add_class::<range::Range>()?;
+    m.add_class::<block::BlockNode>()?;
+    m.add_class::<block::Branch>()?;
+    m.add_class::<analysis::Variable>()?;
+    m.add_class::<analysis::TemplateAnalysis>()?;
+    m.add_class::<dependency::Dependency>()?;
+    m.add_class::<dependency::DependencyGraph>()?;
+    m.add_class::<cache::TemplateCache>()?;
+    m.add_class::<cache::CacheStats>()?;
+    m.add_class::<compile::CompiledQuery>()?;
+    m.add_class::<compile::QueryMatch>()?;
+    m.add_class::<compile::QueryMatchIterator>()?;
+    m.add_class::<compile::StreamIterator>()?;
+    m.add_class::<loader::TemplateSource>()?;
+    m.add_class::<loader::PyLoader>()?;
+    m.add_class::<loader::PyFileSystemLoader>()?;
+    m.add_class::<loader::PyMemoryLoader>()?;
+    m.add_class::<loader::PyChoiceLoader>()?;
+    build::add_constructors(m)?;
     Ok(())
 }
@@ -0,0 +1,293 @@
+//! Template loaders
+//!
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Component, PathBuf};
+use std::sync::Arc;
+use std::time::SystemTime;
+
+use pyo3::exceptions::PyKeyError;
+use pyo3::prelude::*;
+use pyo3::types::PyMapping;
+
+use crate::errors::{LiquidError, LiquidErrorType};
+use crate::lexer::default_lexer;
+use crate::markup::Markup;
+
+/// Template source text and where it came from.
+#[pyclass(frozen)]
+#[derive(Debug, Clone)]
+pub struct TemplateSource {
+    /// The name used to load the template.
+    #[pyo3(get)]
+    pub name: String,
+    /// The path to the template file, or the template name if it was not
+    /// loaded from a file.
+    #[pyo3(get)]
+    pub filename: String,
+    #[pyo3(get)]
+    pub source: String,
+    path: Option<PathBuf>,
+    mtime: Option<SystemTime>,
+}
+
+impl TemplateSource {
+    pub fn new(name: &str, source: String) -> Self {
+        Self {
+            name: name.to_owned(),
+            filename: name.to_owned(),
+            source,
+            path: None,
+            mtime: None,
+        }
+    }
+
+    /// Return `false` if the file this template was loaded from has been
+    /// modified or removed since it was loaded. Templates not loaded from a
+    /// file are always up to date.
+    pub fn uptodate(&self) -> bool {
+        match (&self.path, &self.mtime) {
+            (Some(path), Some(mtime)) => fs::metadata(path)
+                .and_then(|m| m.modified())
+                .is_ok_and(|modified| modified == *mtime),
+            _ => true,
+        }
+    }
+}
+
+pub trait Loader: Send + Sync {
+    /// Return the source text for template _name_, or an error of kind
+    /// `TemplateNotFoundError` if it does not exist.
+    fn get_source(&self, name: &str) -> Result<TemplateSource, LiquidError>;
+
+    /// Find and tokenize template _name_.
+    fn load(&self, name: &str) -> Result<(TemplateSource, Vec<Markup>), LiquidError> {
+        let source = self.get_source(name)?;
+        let tokens = default_lexer().tokenize(&source.source)?;
+        Ok((source, tokens))
+    }
+}
+
+/// A loader that reads templates from files in one or more directories.
+///
+/// Directories are searched in order. Template names that are absolute or
+/// contain `..` are not found, as are files that resolve to a location
+/// outside the search path.
+pub struct FileSystemLoader {
+    search_path: Vec<PathBuf>,
+    default_extension: Option<String>,
+}
+
+impl FileSystemLoader {
+    /// _default_extension_ is appended to template names that don't have
+    /// an extension, like `".liquid"`.
+    pub fn new(search_path: Vec<PathBuf>, default_extension: Option<String>) -> Self {
+        Self {
+            search_path,
+            default_extension,
+        }
+    }
+
+    /// Return the path to template _name_, if it exists.
+    pub fn resolve(&self, name: &str) -> Option<PathBuf> {
+        let mut relative = PathBuf::from(name);
+        if !relative
+            .components()
+            .all(|c| matches!(c, Component::Normal(_) | Component::CurDir))
+        {
+            return None;
+        }
+
+        if let Some(ext) = &self.default_extension {
+            if relative.extension().is_none() {
+                relative.set_extension(ext.trim_start_matches('.'));
+            }
+        }
+
+        self.search_path.iter().find_map(|dir| {
+            let path = dir.join(&relative);
+            let canonical = path.canonicalize().ok()?;
+            (canonical.is_file() && canonical.starts_with(dir.canonicalize().ok()?)).then_some(path)
+        })
+    }
+}
+
+impl Loader for FileSystemLoader {
+    fn get_source(&self, name: &str) -> Result<TemplateSource, LiquidError> {
+        let path = self
+            .resolve(name)
+            .ok_or_else(|| LiquidError::template_not_found(name.to_owned()))?;
+
+        let mtime = fs::metadata(&path)?.modified().ok();
+        let source = fs::read_to_string(&path)?;
+
+        Ok(TemplateSource {
+            name: name.to_owned(),
+            filename: path.to_string_lossy().into_owned(),
+            source,
+            path: Some(path),
+            mtime,
+        })
+    }
+}
+
+/// A loader that loads templates from a map of template names to source
+/// text.
+pub struct MemoryLoader {
+    templates: HashMap<String, String>,
+}
+
+impl MemoryLoader {
+    pub fn new(templates: HashMap<String, String>) -> Self {
+        Self { templates }
+    }
+}
+
+impl Loader for MemoryLoader {
+    fn get_source(&self, name: &str) -> Result<TemplateSource, LiquidError> {
+        self.templates
+            .get(name)
+            .map(|source| TemplateSource::new(name, source.to_owned()))
+            .ok_or_else(|| LiquidError::template_not_found(name.to_owned()))
+    }
+}
+
+/// A loader that looks up templates in a Python mapping each time one is
+/// requested, so changes made to the mapping after the loader was created
+/// are seen.
+struct MappingLoader {
+    templates: Py<PyMapping>,
+}
+
+impl Loader for MappingLoader {
+    fn get_source(&self, name: &str) -> Result<TemplateSource, LiquidError> {
+        Python::with_gil(|py| match self.templates.bind(py).get_item(name) {
+            Ok(source) => source
+                .extract::<String>()
+                .map(|source| TemplateSource::new(name, source))
+                .map_err(|_| LiquidError::typ(format!("template `{name}` is not a string"))),
+            Err(err) if err.is_instance_of::<PyKeyError>(py) => {
+                Err(LiquidError::template_not_found(name.to_owned()))
+            }
+            Err(err) => Err(LiquidError::typ(err.to_string())),
+        })
+    }
+}
+
+/// A loader that tries each of its loaders in turn, returning the first
+/// template found.
+pub struct ChoiceLoader {
+    loaders: Vec<Arc<dyn Loader>>,
+}
+
+impl ChoiceLoader {
+    pub fn new(loaders: Vec<Arc<dyn Loader>>) -> Self {
+        Self { loaders }
+    }
+}
+
+impl Loader for ChoiceLoader {
+    fn get_source(&self, name: &str) -> Result<TemplateSource, LiquidError> {
+        for loader in &self.loaders {
+            match loader.get_source(name) {
+                Err(LiquidError {
+                    kind: LiquidErrorType::TemplateNotFoundError,
+                    ..
+                }) => continue,
+                result => return result,
+            }
+        }
+        Err(LiquidError::template_not_found(name.to_owned()))
+    }
+}
+
+/// Base class for template loaders implemented in Rust.
+#[pyclass(frozen, subclass, name = "Loader")]
+pub struct PyLoader {
+    pub loader: Arc<dyn Loader>,
+}
+
+#[pymethods]
+impl PyLoader {
+    fn get_source(&self, name: &str) -> PyResult<TemplateSource> {
+        Ok(self.loader.get_source(name)?)
+    }
+
+    fn load(&self, name: &str) -> PyResult<(TemplateSource, Vec<Markup>)> {
+        Ok(self.loader.load(name)?)
+    }
+}
+
+#[pyclass(frozen, extends = PyLoader, name = "FileSystemLoader")]
+pub struct PyFileSystemLoader;
+
+#[derive(FromPyObject)]
+enum SearchPath {
+    One(PathBuf),
+    Many(Vec<PathBuf>),
+}
+
+#[pymethods]
+impl PyFileSystemLoader {
+    #[new]
+    #[pyo3(signature = (search_path, *, default_extension=None))]
+    fn new(search_path: SearchPath, default_extension: Option<String>) -> (Self, PyLoader) {
+        let search_path = match search_path {
+            SearchPath::One(path) => vec![path],
+            SearchPath::Many(paths) => paths,
+        };
+
+        (
+            PyFileSystemLoader,
+            PyLoader {
+                loader: Arc::new(FileSystemLoader::new(search_path, default_extension)),
+            },
+        )
+    }
+}
+
+/// A loader that reads templates from a mapping of template names to source
+/// text. The mapping is not copied, so later changes to it are seen.
+#[pyclass(frozen, extends = PyLoader, name = "MemoryLoader")]
+pub struct PyMemoryLoader;
+
+#[pymethods]
+impl PyMemoryLoader {
+    #[new]
+    fn new(templates: Bound<'_, PyMapping>) -> (Self, PyLoader) {
+        (
+            PyMemoryLoader,
+            PyLoader {
+                loader: Arc::new(MappingLoader {
+                    templates: templates.unbind(),
+                }),
+            },
+        )
+    }
+}
+
+#[pyclass(frozen, extends = PyLoader, name = "ChoiceLoader")]
+pub struct PyChoiceLoader;
+
+#[pymethods]
+impl PyChoiceLoader {
+    #[new]
+    fn new(loaders: Vec<PyRef<'_, PyLoader>>) -> (Self, PyLoader) {
+        let loaders = loaders.iter().map(|l| l.loader.clone()).collect();
+        (
+            PyChoiceLoader,
+            PyLoader {
+                loader: Arc::new(ChoiceLoader::new(loaders)),
+            },
+        )
+    }
+}
+
+#[pymethods]
+impl TemplateSource {
+    #[pyo3(name = "uptodate")]
+    fn py_uptodate(&self) -> bool {
+        self.uptodate()
+    }
+}
@@ -1,46 +1,98 @@
 use pyo3::prelude::*;
+use serde::{Deserialize, Serialize};
 use std::fmt::{self};
 
-use crate::query::Query;
+use crate::model::{node_methods, simple_enum_methods, Span};
+use crate::query::{float_literal, Query};
+use crate::unescape::quote;
 
-#[pyclass(frozen)]
-#[derive(Debug, Clone)]
+#[pyclass(frozen, eq, module = "_liquid2")]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum Markup {
+    #[pyo3(constructor = (text, span=None, trimmed_span=None))]
     Content {
         text: String,
-        span: (usize, usize),
+        span: Option<Span>,
+        trimmed_span: Option<Span>,
     },
+    #[pyo3(constructor = (wc, text, span=None))]
     Raw {
         wc: (Whitespace, Whitespace, Whitespace, Whitespace),
         text: String,
-        span: (usize, usize),
+        span: Option<Span>,
     },
+    #[pyo3(constructor = (wc, hashes, text, span=None))]
     Comment {
         wc: (Whitespace, Whitespace),
         hashes: String,
         text: String,
-        span: (usize, usize),
+        span: Option<Span>,
     },
+    #[pyo3(constructor = (wc, expression, span=None))]
     Output {
         wc: (Whitespace, Whitespace),
         expression: Vec<Token>,
-        span: (usize, usize),
+        span: Option<Span>,
     },
+    #[pyo3(constructor = (wc, name, expression, span=None))]
     Tag {
         wc: (Whitespace, Whitespace),
         name: String,
         expression: Option<Vec<Token>>,
-        span: (usize, usize),
+        span: Option<Span>,
     },
+    #[pyo3(constructor = (wc, name, statements, span=None))]
     Lines {
         wc: (Whitespace, Whitespace),
         name: String,
         statements: Vec<Markup>,
-        span: (usize, usize),
+        span: Option<Span>,
     },
     EOI {},
 }
 
+impl Markup {
+    /// Return the start and end index of this markup in template source, or
+    /// `None` for the end of input marker.
+    pub fn span(&self) -> Option<Span> {
+        match self {
+            Markup::Content { span, .. }
+            | Markup::Raw { span, .. }
+            | Markup::Comment { span, .. }
+            | Markup::Output { span, .. }
+            | Markup::Tag { span, .. }
+            | Markup::Lines { span, .. } => *span,
+            Markup::EOI {} => None,
+        }
+    }
+
+    /// Return the whitespace control on the left of this markup, or
+    /// `Whitespace::Default` if it has none.
+    pub fn left_wc(&self) -> Whitespace {
+        match self {
+            Markup::Raw { wc, .. } => wc.0.clone(),
+            Markup::Comment { wc, .. }
+            | Markup::Output { wc, .. }
+            | Markup::Tag { wc, .. }
+            | Markup::Lines { wc, .. } => wc.0.clone(),
+            Markup::Content { .. } | Markup::EOI {} => Whitespace::Default,
+        }
+    }
+
+    /// Return the whitespace control on the right of this markup, or
+    /// `Whitespace::Default` if it has none.
+    pub fn right_wc(&self) -> Whitespace {
+        match self {
+            Markup::Raw { wc, .. } => wc.3.clone(),
+            Markup::Comment { wc, .. }
+            | Markup::Output { wc, .. }
+            | Markup::Tag { wc, .. }
+            | Markup::Lines { wc, .. } => wc.1.clone(),
+            Markup::Content { .. } | Markup::EOI {} => Whitespace::Default,
+        }
+    }
+}
+
 impl fmt::Display for Markup {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
@@ -57,7 +109,7 @@ impl fmt::Display for Markup {
             }
             Markup::Output { wc, expression, .. } => {
                 let expr = expression
-                    .into_iter()
+                    .iter()
                     .map(|e| e.to_string())
                     .collect::<Vec<String>>()
                     .join(" ");
@@ -88,12 +140,12 @@ impl fmt::Display for Markup {
             }
             Markup::Lines { wc, statements, .. } => {
                 let lines = statements
-                    .into_iter()
-                    .map(|t| tag_as_line_statement(t))
+                    .iter()
+                    .map(tag_as_line_statement)
                     .collect::<Vec<String>>()
                     .join("\n");
 
-                if lines.len() == 0 {
+                if lines.is_empty() {
                     write!(f, "{{%{} liquid {}%}}", wc.0, wc.1)
                 } else {
                     write!(f, "{{%{} liquid {} {}%}}", wc.0, lines, wc.1)
@@ -104,10 +156,10 @@ impl fmt::Display for Markup {
     }
 }
 
-fn tokens_string(tokens: &Vec<Token>) -> String {
+fn tokens_string(tokens: &[Token]) -> String {
     // TODO: Smarter join. No space after some symbols
     tokens
-        .into_iter()
+        .iter()
         .map(|e| e.to_string())
         .collect::<Vec<String>>()
         .join(" ")
@@ -124,126 +176,166 @@ fn tag_as_line_statement(tag: &Markup) -> String {
                 name.to_owned()
             }
         }
+        Markup::Comment { text, .. } => format!("#{}", text),
         _ => String::new(),
     }
 }
 
-#[pymethods]
-impl Markup {
-    fn __str__(&self) -> String {
-        self.to_string()
-    }
-}
+node_methods!(Markup {});
 
-#[pyclass(frozen)]
-#[derive(Debug, Clone)]
+#[pyclass(frozen, eq, module = "_liquid2")]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum Token {
-    True_ {
-        span: (usize, usize),
-    },
-    False_ {
-        span: (usize, usize),
-    },
-    And {
-        span: (usize, usize),
-    },
-    Or {
-        span: (usize, usize),
-    },
-    In {
-        span: (usize, usize),
-    },
-    Not {
-        span: (usize, usize),
-    },
-    Contains {
-        span: (usize, usize),
-    },
-    Null {
-        span: (usize, usize),
-    },
-    If {
-        span: (usize, usize),
-    },
-    Else {
-        span: (usize, usize),
-    },
-    With {
-        span: (usize, usize),
-    },
-    Required {
-        span: (usize, usize),
-    },
-    As {
-        span: (usize, usize),
-    },
-    For {
-        span: (usize, usize),
-    },
-    Eq {
-        span: (usize, usize),
-    },
-    Ne {
-        span: (usize, usize),
-    },
-    Ge {
-        span: (usize, usize),
-    },
-    Gt {
-        span: (usize, usize),
-    },
-    Le {
-        span: (usize, usize),
-    },
-    Lt {
-        span: (usize, usize),
-    },
-    Colon {
-        span: (usize, usize),
-    },
-    Pipe {
-        span: (usize, usize),
-    },
-    DoublePipe {
-        span: (usize, usize),
-    },
-    Comma {
-        span: (usize, usize),
-    },
-    LeftParen {
-        span: (usize, usize),
-    },
-    RightParen {
-        span: (usize, usize),
-    },
-    Assign {
-        span: (usize, usize),
-    },
-    StringLiteral {
-        value: String,
-        span: (usize, usize),
-    },
-    IntegerLiteral {
-        value: i64,
-        span: (usize, usize),
-    },
-    FloatLiteral {
-        value: f64,
-        span: (usize, usize),
-    },
-    Word {
-        value: String,
-        span: (usize, usize),
-    },
+    #[pyo3(constructor = (span=None))]
+    True_ { span: Option<Span> },
+    #[pyo3(constructor = (span=None))]
+    False_ { span: Option<Span> },
+    #[pyo3(constructor = (span=None))]
+    And { span: Option<Span> },
+    #[pyo3(constructor = (span=None))]
+    Or { span: Option<Span> },
+    #[pyo3(constructor = (span=None))]
+    In { span: Option<Span> },
+    #[pyo3(constructor = (span=None))]
+    Not { span: Option<Span> },
+    #[pyo3(constructor = (span=None))]
+    Contains { span: Option<Span> },
+    #[pyo3(constructor = (span=None))]
+    Null { span: Option<Span> },
+    #[pyo3(constructor = (span=None))]
+    If { span: Option<Span> },
+    #[pyo3(constructor = (span=None))]
+    Else { span: Option<Span> },
+    #[pyo3(constructor = (span=None))]
+    With { span: Option<Span> },
+    #[pyo3(constructor = (span=None))]
+    Required { span: Option<Span> },
+    #[pyo3(constructor = (span=None))]
+    As { span: Option<Span> },
+    #[pyo3(constructor = (span=None))]
+    For { span: Option<Span> },
+    #[pyo3(constructor = (span=None))]
+    Eq { span: Option<Span> },
+    #[pyo3(constructor = (span=None))]
+    Ne { span: Option<Span> },
+    #[pyo3(constructor = (span=None))]
+    Ge { span: Option<Span> },
+    #[pyo3(constructor = (span=None))]
+    Gt { span: Option<Span> },
+    #[pyo3(constructor = (span=None))]
+    Le { span: Option<Span> },
+    #[pyo3(constructor = (span=None))]
+    Lt { span: Option<Span> },
+    #[pyo3(constructor = (span=None))]
+    Colon { span: Option<Span> },
+    #[pyo3(constructor = (span=None))]
+    Pipe { span: Option<Span> },
+    #[pyo3(constructor = (span=None))]
+    DoublePipe { span: Option<Span> },
+    #[pyo3(constructor = (span=None))]
+    Comma { span: Option<Span> },
+    #[pyo3(constructor = (span=None))]
+    LeftParen { span: Option<Span> },
+    #[pyo3(constructor = (span=None))]
+    RightParen { span: Option<Span> },
+    #[pyo3(constructor = (span=None))]
+    Assign { span: Option<Span> },
+    #[pyo3(constructor = (value, span=None))]
+    StringLiteral { value: String, span: Option<Span> },
+    #[pyo3(constructor = (value, span=None))]
+    IntegerLiteral { value: i64, span: Option<Span> },
+    #[pyo3(constructor = (value, span=None))]
+    FloatLiteral { value: f64, span: Option<Span> },
+    #[pyo3(constructor = (value, span=None))]
+    Word { value: String, span: Option<Span> },
+    #[pyo3(constructor = (start, stop, span=None))]
     RangeLiteral {
         start: RangeArgument,
         stop: RangeArgument,
-        span: (usize, usize),
-    },
-    Query {
-        path: Query,
-        span: (usize, usize),
+        span: Option<Span>,
     },
+    #[pyo3(constructor = (path, span=None))]
+    Query { path: Query, span: Option<Span> },
+}
+
+impl Token {
+    /// Return the start and end index of this token in template source.
+    pub fn span(&self) -> Option<Span> {
+        match self {
+            Token::True_ { span, .. }
+            | Token::False_ { span, .. }
+            | Token::And { span, .. }
+            | Token::Or { span, .. }
+            | Token::In { span, .. }
+            | Token::Not { span, .. }
+            | Token::Contains { span, .. }
+            | Token::Null { span, .. }
+            | Token::If { span, .. }
+            | Token::Else { span, .. }
+            | Token::With { span, .. }
+            | Token::Required { span, .. }
+            | Token::As { span, .. }
+            | Token::For { span, .. }
+            | Token::Eq { span, .. }
+            | Token::Ne { span, .. }
+            | Token::Ge { span, .. }
+            | Token::Gt { span, .. }
+            | Token::Le { span, .. }
+            | Token::Lt { span, .. }
+            | Token::Colon { span, .. }
+            | Token::Pipe { span, .. }
+            | Token::DoublePipe { span, .. }
+            | Token::Comma { span, .. }
+            | Token::LeftParen { span, .. }
+            | Token::RightParen { span, .. }
+            | Token::Assign { span, .. }
+            | Token::StringLiteral { span, .. }
+            | Token::IntegerLiteral { span, .. }
+            | Token::FloatLiteral { span, .. }
+            | Token::Word { span, .. }
+            | Token::RangeLiteral { span, .. }
+            | Token::Query { span, .. } => *span,
+        }
+    }
+
+    /// Return a mutable reference to the span of this token.
+    pub fn span_mut(&mut self) -> &mut Option<Span> {
+        match self {
+            Token::True_ { span, .. }
+            | Token::False_ { span, .. }
+            | Token::And { span, .. }
+            | Token::Or { span, .. }
+            | Token::In { span, .. }
+            | Token::Not { span, .. }
+            | Token::Contains { span, .. }
+            | Token::Null { span, .. }
+            | Token::If { span, .. }
+            | Token::Else { span, .. }
+            | Token::With { span, .. }
+            | Token::Required { span, .. }
+            | Token::As { span, .. }
+            | Token::For { span, .. }
+            | Token::Eq { span, .. }
+            | Token::Ne { span, .. }
+            | Token::Ge { span, .. }
+            | Token::Gt { span, .. }
+            | Token::Le { span, .. }
+            | Token::Lt { span, .. }
+            | Token::Colon { span, .. }
+            | Token::Pipe { span, .. }
+            | Token::DoublePipe { span, .. }
+            | Token::Comma { span, .. }
+            | Token::LeftParen { span, .. }
+            | Token::RightParen { span, .. }
+            | Token::Assign { span, .. }
+            | Token::StringLiteral { span, .. }
+            | Token::IntegerLiteral { span, .. }
+            | Token::FloatLiteral { span, .. }
+            | Token::Word { span, .. }
+            | Token::RangeLiteral { span, .. }
+            | Token::Query { span, .. } => span,
+        }
+    }
 }
 
 impl fmt::Display for Token {
@@ -276,64 +368,57 @@ impl fmt::Display for Token {
             Token::LeftParen { .. } => f.write_str("("),
             Token::RightParen { .. } => f.write_str(")"),
             Token::Assign { .. } => f.write_str("="),
-            Token::StringLiteral { value, .. } => write!(f, "'{value}'"),
+            Token::StringLiteral { value, .. } => f.write_str(&quote(value)),
             Token::IntegerLiteral { value, .. } => write!(f, "{value}"),
-            Token::FloatLiteral { value, .. } => write!(f, "{value}"),
+            Token::FloatLiteral { value, .. } => f.write_str(&float_literal(*value)),
             Token::Word { value, .. } => write!(f, "{value}"),
             Token::RangeLiteral { start, stop, .. } => write!(f, "({start}..{stop})"),
-            Token::Query { path, .. } => {
-                if let Some(word) = path.as_word() {
-                    write!(f, "{word}")
-                } else {
-                    write!(f, "{path}")
-                }
-            }
+            Token::Query { path, .. } => write!(f, "{}", path.shorthand()),
         }
     }
 }
 
-#[pymethods]
-impl Token {
-    fn __str__(&self) -> String {
-        self.to_string()
-    }
-}
+node_methods!(Token {});
 
-#[pyclass(frozen)]
-#[derive(Debug, Clone)]
+#[pyclass(frozen, eq, module = "_liquid2")]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum RangeArgument {
-    StringLiteral { value: String, span: (usize, usize) },
-    IntegerLiteral { value: i64, span: (usize, usize) },
-    FloatLiteral { value: f64, span: (usize, usize) },
-    Query { path: Query, span: (usize, usize) },
+    #[pyo3(constructor = (value, span=None))]
+    StringLiteral { value: String, span: Option<Span> },
+    #[pyo3(constructor = (value, span=None))]
+    IntegerLiteral { value: i64, span: Option<Span> },
+    #[pyo3(constructor = (value, span=None))]
+    FloatLiteral { value: f64, span: Option<Span> },
+    #[pyo3(constructor = (path, span=None))]
+    Query { path: Query, span: Option<Span> },
+}
+
+impl RangeArgument {
+    pub fn span(&self) -> Option<Span> {
+        match self {
+            RangeArgument::StringLiteral { span, .. }
+            | RangeArgument::IntegerLiteral { span, .. }
+            | RangeArgument::FloatLiteral { span, .. }
+            | RangeArgument::Query { span, .. } => *span,
+        }
+    }
 }
 
 impl fmt::Display for RangeArgument {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
-            RangeArgument::StringLiteral { value, .. } => write!(f, "'{value}'"),
+            RangeArgument::StringLiteral { value, .. } => f.write_str(&quote(value)),
             RangeArgument::IntegerLiteral { value, .. } => write!(f, "{value}"),
-            RangeArgument::FloatLiteral { value, .. } => write!(f, "{value}"),
-            RangeArgument::Query { path, .. } => {
-                if let Some(word) = path.as_word() {
-                    write!(f, "{word}")
-                } else {
-                    write!(f, "{path}")
-                }
-            }
+            RangeArgument::FloatLiteral { value, .. } => f.write_str(&float_literal(*value)),
+            RangeArgument::Query { path, .. } => write!(f, "{}", path.shorthand()),
         }
     }
 }
 
-#[pymethods]
-impl RangeArgument {
-    fn __str__(&self) -> String {
-        self.to_string()
-    }
-}
+node_methods!(RangeArgument {});
 
-#[pyclass(eq, eq_int)]
-#[derive(Debug, Clone, PartialEq)]
+#[pyclass(frozen, eq, eq_int, hash, module = "_liquid2")]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum Whitespace {
     Plus,
     Minus,
@@ -342,6 +427,7 @@ pub enum Whitespace {
 }
 
 impl Whitespace {
+    #[allow(clippy::should_implement_trait)]
     pub fn from_str(s: &str) -> Self {
         match s {
             "+" => Self::Plus,
@@ -364,9 +450,4 @@ impl fmt::Display for Whitespace {
     }
 }
 
-#[pymethods]
-impl Whitespace {
-    fn __str__(&self) -> String {
-        self.to_string()
-    }
-}
+simple_enum_methods!(Whitespace);
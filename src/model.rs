@@ -0,0 +1,443 @@
+//! The Python data model for syntax tree nodes
+//!
+//! Markup, tokens and queries compare equal if they have the same variant
+//! and fields, including spans. `equals()` and `without_spans()` compare
+//! structure alone, and every node can be hashed, pickled and copied.
+//!
+//! Structural comparison also treats equivalent forms as equal, like `[0:]`
+//! and `[::1]`. In Rust, wrap a node in `Structural` to use it as a key.
+
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+
+use pyo3::prelude::*;
+use pyo3::types::{PyBytes, PyType};
+use pyo3::PyTypeInfo;
+use serde::Serialize;
+
+use crate::markup::{Markup, RangeArgument, Token};
+use crate::query::{FilterExpression, Query, Segment, Selector};
+use crate::serialize;
+
+/// The start and end byte index of a node in template source. Nodes that
+/// were not read from source text, like those built with the constructors in
+/// `build`, have no span.
+pub type Span = (usize, usize);
+
+/// Syntax tree nodes that can be compared and hashed without spans.
+pub trait Normalize: Clone {
+    /// Rewrite equivalent forms to a canonical form and, if _strip_spans_ is
+    /// `true`, remove every span.
+    ///
+    /// Negative zero float literals become zero, slice selectors drop a
+    /// default start and step, and queries drop their end of input marker.
+    fn normalize(&mut self, strip_spans: bool);
+
+    /// Return a copy of this node without spans.
+    fn without_spans(&self) -> Self {
+        let mut node = self.clone();
+        node.normalize(true);
+        node
+    }
+}
+
+fn normalize_span(span: &mut Option<Span>, strip_spans: bool) {
+    if strip_spans {
+        *span = None;
+    }
+}
+
+fn normalize_float(value: &mut f64) {
+    if *value == 0.0 {
+        *value = 0.0;
+    }
+}
+
+impl Normalize for Markup {
+    fn normalize(&mut self, strip_spans: bool) {
+        match self {
+            Markup::Content {
+                span, trimmed_span, ..
+            } => {
+                normalize_span(span, strip_spans);
+                normalize_span(trimmed_span, strip_spans);
+            }
+            Markup::Raw { span, .. } | Markup::Comment { span, .. } => {
+                normalize_span(span, strip_spans)
+            }
+            Markup::Output {
+                expression, span, ..
+            } => {
+                expression.normalize(strip_spans);
+                normalize_span(span, strip_spans);
+            }
+            Markup::Tag {
+                expression, span, ..
+            } => {
+                if let Some(expression) = expression {
+                    expression.normalize(strip_spans);
+                }
+                normalize_span(span, strip_spans);
+            }
+            Markup::Lines {
+                statements, span, ..
+            } => {
+                statements.normalize(strip_spans);
+                normalize_span(span, strip_spans);
+            }
+            Markup::EOI {} => (),
+        }
+    }
+}
+
+impl Normalize for Token {
+    fn normalize(&mut self, strip_spans: bool) {
+        match self {
+            Token::FloatLiteral { value, .. } => normalize_float(value),
+            Token::RangeLiteral { start, stop, .. } => {
+                start.normalize(strip_spans);
+                stop.normalize(strip_spans);
+            }
+            Token::Query { path, .. } => path.normalize(strip_spans),
+            _ => (),
+        }
+
+        normalize_span(self.span_mut(), strip_spans);
+    }
+}
+
+impl Normalize for RangeArgument {
+    fn normalize(&mut self, strip_spans: bool) {
+        match self {
+            RangeArgument::StringLiteral { span, .. }
+            | RangeArgument::IntegerLiteral { span, .. } => normalize_span(span, strip_spans),
+            RangeArgument::FloatLiteral { value, span } => {
+                normalize_float(value);
+                normalize_span(span, strip_spans);
+            }
+            RangeArgument::Query { path, span } => {
+                path.normalize(strip_spans);
+                normalize_span(span, strip_spans);
+            }
+        }
+    }
+}
+
+impl Normalize for Query {
+    fn normalize(&mut self, strip_spans: bool) {
+        self.segments
+            .retain(|segment| !matches!(segment, Segment::Eoi {}));
+        self.segments.normalize(strip_spans);
+    }
+}
+
+impl Normalize for Segment {
+    fn normalize(&mut self, strip_spans: bool) {
+        match self {
+            Segment::Child { selectors, span } | Segment::Recursive { selectors, span } => {
+                selectors.normalize(strip_spans);
+                normalize_span(span, strip_spans);
+            }
+            Segment::Eoi {} => (),
+        }
+    }
+}
+
+impl Normalize for Selector {
+    fn normalize(&mut self, strip_spans: bool) {
+        match self {
+            Selector::Name { span, .. }
+            | Selector::Index { span, .. }
+            | Selector::Wild { span } => normalize_span(span, strip_spans),
+            Selector::Slice {
+                start, step, span, ..
+            } => {
+                // A start of zero is only the default for a positive step.
+                if *step == Some(1) {
+                    *step = None;
+                }
+                if step.is_none() && *start == Some(0) {
+                    *start = None;
+                }
+                normalize_span(span, strip_spans);
+            }
+            Selector::Filter { expression, span } => {
+                expression.normalize(strip_spans);
+                normalize_span(span, strip_spans);
+            }
+            Selector::SingularQuery { query, span } => {
+                query.normalize(strip_spans);
+                normalize_span(span, strip_spans);
+            }
+        }
+    }
+}
+
+impl Normalize for FilterExpression {
+    fn normalize(&mut self, strip_spans: bool) {
+        match self {
+            FilterExpression::True_ { span }
+            | FilterExpression::False_ { span }
+            | FilterExpression::Null { span }
+            | FilterExpression::StringLiteral { span, .. }
+            | FilterExpression::Int { span, .. } => normalize_span(span, strip_spans),
+            FilterExpression::Float { value, span } => {
+                normalize_float(value);
+                normalize_span(span, strip_spans);
+            }
+            FilterExpression::Not { expression, span } => {
+                expression.normalize(strip_spans);
+                normalize_span(span, strip_spans);
+            }
+            FilterExpression::Logical {
+                left, right, span, ..
+            }
+            | FilterExpression::Comparison {
+                left, right, span, ..
+            } => {
+                left.normalize(strip_spans);
+                right.normalize(strip_spans);
+                normalize_span(span, strip_spans);
+            }
+            FilterExpression::RelativeQuery { query, span }
+            | FilterExpression::RootQuery { query, span } => {
+                query.normalize(strip_spans);
+                normalize_span(span, strip_spans);
+            }
+            FilterExpression::Function { args, span, .. } => {
+                args.normalize(strip_spans);
+                normalize_span(span, strip_spans);
+            }
+        }
+    }
+}
+
+impl<T: Normalize> Normalize for Vec<T> {
+    fn normalize(&mut self, strip_spans: bool) {
+        for item in self.iter_mut() {
+            item.normalize(strip_spans);
+        }
+    }
+}
+
+impl<T: Normalize> Normalize for Box<T> {
+    fn normalize(&mut self, strip_spans: bool) {
+        self.as_mut().normalize(strip_spans);
+    }
+}
+
+/// Return `true` if _left_ and _right_ are structurally equal, optionally
+/// ignoring spans.
+pub fn equals<T: Normalize + PartialEq>(left: &T, right: &T, spans: bool) -> bool {
+    if spans {
+        left == right
+    } else {
+        left.without_spans() == right.without_spans()
+    }
+}
+
+/// Return a hash of _node_ that is consistent with `equals`.
+pub fn hash<T: Normalize + Serialize>(node: &T, spans: bool) -> u64 {
+    let mut node = node.clone();
+    node.normalize(!spans);
+    let mut hasher = DefaultHasher::new();
+    // Encoding a syntax tree node can't fail.
+    hasher.write(&bincode::serialize(&node).unwrap_or_default());
+    hasher.finish()
+}
+
+/// A syntax tree node that compares and hashes by structure alone, ignoring
+/// spans and equivalent forms, for use as a `HashMap` or `HashSet` key.
+#[derive(Debug, Clone)]
+pub struct Structural<T> {
+    node: T,
+    key: Vec<u8>,
+}
+
+impl<T: Normalize + Serialize> Structural<T> {
+    pub fn new(node: &T) -> Self {
+        let node = node.without_spans();
+        // Encoding a syntax tree node can't fail.
+        let key = bincode::serialize(&node).unwrap_or_default();
+        Self { node, key }
+    }
+
+    /// The normalized node, without spans.
+    pub fn get(&self) -> &T {
+        &self.node
+    }
+
+    pub fn into_inner(self) -> T {
+        self.node
+    }
+}
+
+impl<T> PartialEq for Structural<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.key == other.key
+    }
+}
+
+impl<T> Eq for Structural<T> {}
+
+impl<T> Hash for Structural<T> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.key.hash(state);
+    }
+}
+
+/// A callable and its arguments, as returned by `__reduce__`.
+pub type Reduce<'py, A> = (Bound<'py, PyAny>, A);
+
+/// Return arguments for `__reduce__`, reconstructing _node_ with the
+/// `from_bytes` class method of _T_.
+pub fn reduce<'py, T: PyTypeInfo + Serialize>(
+    py: Python<'py>,
+    node: &T,
+) -> PyResult<Reduce<'py, (Bound<'py, PyBytes>,)>> {
+    let from_bytes = py.get_type_bound::<T>().getattr("from_bytes")?;
+    Ok((from_bytes, (to_bytes(py, node)?,)))
+}
+
+pub fn to_bytes<'py, T: Serialize>(py: Python<'py>, node: &T) -> PyResult<Bound<'py, PyBytes>> {
+    Ok(PyBytes::new_bound(py, &serialize::encode_node(node)?))
+}
+
+/// Return arguments for `__reduce__` of a simple enum, reconstructing it
+/// with `getattr(cls, name)`.
+pub fn reduce_simple_enum<'py>(
+    cls: Bound<'py, PyType>,
+    name: &str,
+) -> PyResult<Reduce<'py, (Bound<'py, PyType>, String)>> {
+    let getattr = PyModule::import_bound(cls.py(), "builtins")?.getattr("getattr")?;
+    Ok((getattr, (cls, name.to_owned())))
+}
+
+/// Implement `__str__`, `__repr__`, `__hash__`, pickling, validation and
+/// span-insensitive comparison for syntax tree node _ty_, alongside any other Python methods
+/// given. Equality comes from `#[pyclass(eq)]`.
+macro_rules! node_methods {
+    ($ty:ident { $($methods:tt)* }) => {
+        #[pymethods]
+        impl $ty {
+            $($methods)*
+
+            fn __str__(&self) -> String {
+                self.to_string()
+            }
+
+            fn __repr__(slf: &Bound<'_, Self>) -> PyResult<String> {
+                $crate::model::repr(slf.as_any())
+            }
+
+            fn __hash__(&self) -> u64 {
+                $crate::model::hash(self, true)
+            }
+
+            /// Return `true` if _other_ has the same structure as this node,
+            /// optionally ignoring spans.
+            #[pyo3(signature = (other, *, spans=true))]
+            fn equals(&self, other: &Bound<'_, PyAny>, spans: bool) -> bool {
+                other
+                    .downcast::<Self>()
+                    .is_ok_and(|other| $crate::model::equals(self, other.get(), spans))
+            }
+
+            /// Raise an error if this node's source text would not be read
+            /// back by _lexer_ as the same node.
+            #[pyo3(name = "validate", signature = (*, lexer=None))]
+            fn py_validate(
+                &self,
+                lexer: Option<PyRef<'_, $crate::lexer::Lexer>>,
+            ) -> Result<(), $crate::errors::LiquidError> {
+                let lexer = lexer.as_deref().unwrap_or($crate::lexer::default_lexer());
+                $crate::build::Validate::validate(self, lexer)
+            }
+
+            #[pyo3(name = "without_spans")]
+            fn py_without_spans(&self) -> Self {
+                $crate::model::Normalize::without_spans(self)
+            }
+
+            /// Return a copy of this node with equivalent forms rewritten to a
+            /// canonical form, without spans unless _spans_ is `true`.
+            #[pyo3(name = "normalize", signature = (*, spans=false))]
+            fn py_normalize(&self, spans: bool) -> Self {
+                let mut node = self.clone();
+                $crate::model::Normalize::normalize(&mut node, !spans);
+                node
+            }
+
+            fn to_bytes<'py>(
+                &self,
+                py: Python<'py>,
+            ) -> PyResult<Bound<'py, pyo3::types::PyBytes>> {
+                $crate::model::to_bytes(py, self)
+            }
+
+            #[classmethod]
+            fn from_bytes(
+                _cls: &Bound<'_, pyo3::types::PyType>,
+                data: &[u8],
+            ) -> Result<Self, $crate::errors::LiquidError> {
+                $crate::serialize::decode_node(data)
+            }
+
+            fn __reduce__<'py>(
+                &self,
+                py: Python<'py>,
+            ) -> PyResult<$crate::model::Reduce<'py, (Bound<'py, pyo3::types::PyBytes>,)>> {
+                $crate::model::reduce(py, self)
+            }
+        }
+    };
+}
+
+pub(crate) use node_methods;
+
+/// Implement `__str__` and pickling for simple enum _ty_.
+macro_rules! simple_enum_methods {
+    ($ty:ident) => {
+        #[pymethods]
+        impl $ty {
+            fn __str__(&self) -> String {
+                self.to_string()
+            }
+
+            fn __reduce__<'py>(
+                slf: &Bound<'py, Self>,
+            ) -> PyResult<$crate::model::Reduce<'py, (Bound<'py, pyo3::types::PyType>, String)>>
+            {
+                $crate::model::reduce_simple_enum(
+                    slf.py().get_type_bound::<Self>(),
+                    &format!("{:?}", slf.get()),
+                )
+            }
+        }
+    };
+}
+
+pub(crate) use simple_enum_methods;
+
+/// Return a Python-like representation of syntax tree node _node_, showing
+/// its variant and fields, like `Token.Word(value='x', span=(3, 4))`.
+///
+/// Fields are those listed in `__match_args__`, and each field value uses
+/// its own `repr`.
+pub fn repr(node: &Bound<'_, PyAny>) -> PyResult<String> {
+    let cls = node.get_type();
+    // Complex enum variants are subclasses named like `Token_Word`.
+    let name = cls
+        .getattr("__name__")?
+        .extract::<String>()?
+        .replacen('_', ".", 1);
+    // Variants without fields have no `__match_args__`.
+    let fields = cls
+        .getattr("__match_args__")?
+        .extract::<Option<Vec<String>>>()?
+        .unwrap_or_default()
+        .iter()
+        .map(|field| Ok(format!("{field}={}", node.getattr(field.as_str())?.repr()?)))
+        .collect::<PyResult<Vec<_>>>()?;
+    Ok(format!("{name}({})", fields.join(", ")))
+}
@@ -0,0 +1,477 @@
+//! Writing to JSON-like data at the locations matched by a query
+//!
+//! `set` replaces every value a query matches, `delete` removes them, and
+//! `upsert` also creates missing values, along with any intermediate objects
+//! and arrays a singular query passes through. Each operation returns the
+//! normalized paths of the locations it changed.
+
+use std::collections::HashSet;
+
+use pyo3::prelude::*;
+use pyo3::types::{PyDict, PyList};
+
+use crate::errors::LiquidError;
+use crate::eval::{find, normalize_index, normalized_path, JsonLike, Kind, PathElement};
+use crate::query::{Query, Segment, Selector};
+
+/// The most nulls `upsert` pads an array with, so that an index in a query
+/// can't grow an array without bound.
+const MAX_PADDING: usize = 1_000;
+
+/// A mutable JSON-like document.
+pub trait Store {
+    /// The type of values written to the document.
+    type Value: Clone;
+
+    /// A read-only view of the document, for evaluating queries.
+    type Node<'a>: JsonLike
+    where
+        Self: 'a;
+
+    fn root(&self) -> Self::Node<'_>;
+
+    fn replace_root(&mut self, value: Self::Value) -> Result<(), LiquidError>;
+
+    /// Set _key_ of the object or array at _parent_ to _value_. An index equal
+    /// to the length of the array appends _value_.
+    fn set(
+        &mut self,
+        parent: &[PathElement],
+        key: &PathElement,
+        value: Self::Value,
+    ) -> Result<(), LiquidError>;
+
+    /// Remove _key_ from the object or array at _parent_.
+    fn remove(&mut self, parent: &[PathElement], key: &PathElement) -> Result<(), LiquidError>;
+
+    /// Return an empty object for a name, or an empty array for an index.
+    fn container(&self, key: &PathElement) -> Self::Value;
+
+    fn null(&self) -> Self::Value;
+}
+
+impl Query {
+    /// Replace the value at every location matched by this query with _value_.
+    pub fn set<S: Store>(&self, doc: &mut S, value: S::Value) -> Result<Vec<Query>, LiquidError> {
+        let mut changed: Vec<Vec<PathElement>> = Vec::new();
+
+        for path in self.locations(doc)? {
+            // A location inside a value we've already replaced no longer exists.
+            if changed.iter().any(|done| path.starts_with(done)) {
+                continue;
+            }
+
+            match path.split_last() {
+                Some((key, parent)) => doc.set(parent, key, value.clone())?,
+                None => doc.replace_root(value.clone())?,
+            }
+            changed.push(path);
+        }
+
+        Ok(changed.iter().map(|path| normalized_path(path)).collect())
+    }
+
+    /// Remove every value matched by this query from its object or array.
+    pub fn delete<S: Store>(&self, doc: &mut S) -> Result<Vec<Query>, LiquidError> {
+        let paths = self.locations(doc)?;
+
+        if paths.iter().any(Vec::is_empty) {
+            return Err(LiquidError::typ(format!(
+                "can't delete the root value with `{}`",
+                self.as_written()
+            )));
+        }
+
+        // Values inside other deleted values go with them.
+        let matched: HashSet<&[PathElement]> = paths.iter().map(Vec::as_slice).collect();
+        let mut paths: Vec<&Vec<PathElement>> = paths
+            .iter()
+            .filter(|path| !(1..path.len()).any(|end| matched.contains(&path[..end])))
+            .collect();
+
+        // Remove later array items first so earlier indices stay valid.
+        let mut order = paths.clone();
+        order.sort_unstable_by(|a, b| b.cmp(a));
+        for path in order {
+            let (key, parent) = path.split_last().expect("not the root");
+            doc.remove(parent, key)?;
+        }
+
+        paths.dedup();
+        Ok(paths
+            .into_iter()
+            .map(|path| normalized_path(path))
+            .collect())
+    }
+
+    /// Like `set`, but create missing values too. A singular query creates
+    /// intermediate objects for names and arrays for indices. Other queries
+    /// ending in a single name or index create it on every match of their
+    /// parent.
+    ///
+    /// Either way, an index past the end of an array pads the array with
+    /// nulls, so `$.a[2]` on `{"a": []}` sets `a` to `[null, null, value]`.
+    /// An index more than 1,000 past the end is an error.
+    pub fn upsert<S: Store>(
+        &self,
+        doc: &mut S,
+        value: S::Value,
+    ) -> Result<Vec<Query>, LiquidError> {
+        if let Some(singular) = self.to_singular() {
+            return Ok(vec![self.upsert_singular(&singular, doc, value)?]);
+        }
+
+        let Some((Segment::Child { selectors, .. }, parent)) = self.path().split_last() else {
+            return self.set(doc, value);
+        };
+
+        let key = match selectors.as_slice() {
+            [Selector::Name { name, .. }] => PathElement::Name(name.clone()),
+            [Selector::Index { index, .. }] if *index >= 0 => PathElement::Index(*index as usize),
+            _ => return self.set(doc, value),
+        };
+
+        let parent = Query {
+            segments: parent.to_vec(),
+        };
+
+        // Find every parent that fits _key_ and check padding before writing
+        // anything.
+        let mut parents = Vec::new();
+        for path in parent.locations(doc)? {
+            match (&key, lookup(&doc.root(), &path).map(|node| node.kind())) {
+                (PathElement::Name(_), Some(Kind::Object)) => parents.push((path, 0)),
+                (PathElement::Index(index), Some(Kind::Array(length))) => {
+                    self.check_padding(*index, length, &path)?;
+                    parents.push((path, length));
+                }
+                _ => (),
+            }
+        }
+
+        let mut changed = Vec::new();
+        for (path, length) in parents {
+            if let PathElement::Index(index) = key {
+                for pad in length..index {
+                    doc.set(&path, &PathElement::Index(pad), doc.null())?;
+                }
+            }
+            doc.set(&path, &key, value.clone())?;
+            changed.push(normalized_path(&[path, vec![key.clone()]].concat()));
+        }
+
+        Ok(changed)
+    }
+
+    fn check_padding(
+        &self,
+        index: usize,
+        length: usize,
+        path: &[PathElement],
+    ) -> Result<(), LiquidError> {
+        if index.saturating_sub(length) > MAX_PADDING {
+            return Err(LiquidError::typ(format!(
+                "can't upsert `{}`, index {} is more than {} past the end of the array at `{}`",
+                self.as_written(),
+                index,
+                MAX_PADDING,
+                normalized_path(path)
+            )));
+        }
+        Ok(())
+    }
+
+    fn upsert_singular<S: Store>(
+        &self,
+        singular: &Query,
+        doc: &mut S,
+        value: S::Value,
+    ) -> Result<Query, LiquidError> {
+        // Check the whole path before writing anything, so a query that
+        // doesn't fit _doc_ fails without changing it. A write can still fail
+        // part way, like setting an item of a Python tuple, leaving the
+        // containers created so far in place.
+        self.upsert_path(singular, doc, None)?;
+        self.upsert_path(singular, doc, Some(value))
+    }
+
+    /// Walk _singular_ through _doc_, creating missing containers and
+    /// setting _value_ at the end. If _value_ is `None`, report any error
+    /// without writing to _doc_.
+    fn upsert_path<S: Store>(
+        &self,
+        singular: &Query,
+        doc: &mut S,
+        value: Option<S::Value>,
+    ) -> Result<Query, LiquidError> {
+        let mut path: Vec<PathElement> = Vec::new();
+        let mut segments = singular.segments.iter().peekable();
+        // The kind of the empty container created, or to be created, at
+        // `path`.
+        let mut created: Option<Kind> = None;
+
+        while let Some(segment) = segments.next() {
+            let Segment::Child { selectors, .. } = segment else {
+                unreachable!("singular queries only have child segments");
+            };
+
+            let kind = created
+                .take()
+                .or_else(|| lookup(&doc.root(), &path).map(|node| node.kind()));
+            let key = match (selectors.as_slice(), kind) {
+                ([Selector::Name { name, .. }], Some(Kind::Object)) => {
+                    PathElement::Name(name.clone())
+                }
+                ([Selector::Index { index, .. }], Some(Kind::Array(length))) => {
+                    match normalize_index(*index, length) {
+                        Some(index) => PathElement::Index(index),
+                        None if *index >= 0 => {
+                            self.check_padding(*index as usize, length, &path)?;
+                            if value.is_some() {
+                                for pad in length..*index as usize {
+                                    doc.set(&path, &PathElement::Index(pad), doc.null())?;
+                                }
+                            }
+                            PathElement::Index(*index as usize)
+                        }
+                        None => {
+                            return Err(LiquidError::typ(format!(
+                                "can't upsert `{}`, index {} is out of range at `{}`",
+                                self.as_written(),
+                                index,
+                                normalized_path(&path)
+                            )))
+                        }
+                    }
+                }
+                ([selector], _) => {
+                    let expected = match selector {
+                        Selector::Name { .. } => "an object",
+                        _ => "an array",
+                    };
+                    return Err(LiquidError::typ(format!(
+                        "can't upsert `{}`, expected {} at `{}`",
+                        self.as_written(),
+                        expected,
+                        normalized_path(&path)
+                    )));
+                }
+                _ => unreachable!("singular queries have one name or index per segment"),
+            };
+
+            match (segments.peek(), &value) {
+                (Some(Segment::Child { selectors, .. }), _) => {
+                    let child = [path.as_slice(), std::slice::from_ref(&key)].concat();
+                    if lookup(&doc.root(), &child).is_none() {
+                        let next = match selectors.first() {
+                            Some(Selector::Name { .. }) => PathElement::Name(String::new()),
+                            _ => PathElement::Index(0),
+                        };
+                        if value.is_some() {
+                            doc.set(&path, &key, doc.container(&next))?;
+                        }
+                        created = Some(match next {
+                            PathElement::Name(_) => Kind::Object,
+                            PathElement::Index(_) => Kind::Array(0),
+                        });
+                    }
+                }
+                (_, Some(value)) => doc.set(&path, &key, value.clone())?,
+                _ => (),
+            }
+
+            path.push(key);
+        }
+
+        if let (true, Some(value)) = (path.is_empty(), value) {
+            doc.replace_root(value)?;
+        }
+
+        Ok(normalized_path(&path))
+    }
+
+    // Returns the distinct locations matched by this query in _doc_, in
+    // document order.
+    fn locations<S: Store>(&self, doc: &S) -> Result<Vec<Vec<PathElement>>, LiquidError> {
+        let mut seen = HashSet::new();
+        Ok(find(self, &doc.root())?
+            .into_iter()
+            .map(|node| node.location.to_vec())
+            .filter(|path| seen.insert(path.clone()))
+            .collect())
+    }
+}
+
+fn lookup<V: JsonLike>(root: &V, path: &[PathElement]) -> Option<V> {
+    path.iter()
+        .try_fold(root.clone(), |value, element| match element {
+            PathElement::Name(name) => value.get_member(name),
+            PathElement::Index(index) => value.get_index(*index),
+        })
+}
+
+impl Store for serde_json::Value {
+    type Value = serde_json::Value;
+    type Node<'a> = &'a serde_json::Value;
+
+    fn root(&self) -> Self::Node<'_> {
+        self
+    }
+
+    fn replace_root(&mut self, value: Self::Value) -> Result<(), LiquidError> {
+        *self = value;
+        Ok(())
+    }
+
+    fn set(
+        &mut self,
+        parent: &[PathElement],
+        key: &PathElement,
+        value: Self::Value,
+    ) -> Result<(), LiquidError> {
+        match (get_mut(self, parent), key) {
+            (Some(serde_json::Value::Object(members)), PathElement::Name(name)) => {
+                members.insert(name.clone(), value);
+            }
+            (Some(serde_json::Value::Array(items)), PathElement::Index(index))
+                if *index == items.len() =>
+            {
+                items.push(value);
+            }
+            (Some(serde_json::Value::Array(items)), PathElement::Index(index))
+                if *index < items.len() =>
+            {
+                items[*index] = value;
+            }
+            _ => return Err(missing(parent, key)),
+        }
+        Ok(())
+    }
+
+    fn remove(&mut self, parent: &[PathElement], key: &PathElement) -> Result<(), LiquidError> {
+        match (get_mut(self, parent), key) {
+            (Some(serde_json::Value::Object(members)), PathElement::Name(name))
+                if members.contains_key(name) =>
+            {
+                members.shift_remove(name);
+            }
+            (Some(serde_json::Value::Array(items)), PathElement::Index(index))
+                if *index < items.len() =>
+            {
+                items.remove(*index);
+            }
+            _ => return Err(missing(parent, key)),
+        }
+        Ok(())
+    }
+
+    fn container(&self, key: &PathElement) -> Self::Value {
+        match key {
+            PathElement::Name(_) => serde_json::Value::Object(serde_json::Map::new()),
+            PathElement::Index(_) => serde_json::Value::Array(Vec::new()),
+        }
+    }
+
+    fn null(&self) -> Self::Value {
+        serde_json::Value::Null
+    }
+}
+
+fn get_mut<'a>(
+    value: &'a mut serde_json::Value,
+    path: &[PathElement],
+) -> Option<&'a mut serde_json::Value> {
+    path.iter().try_fold(value, |value, element| match element {
+        PathElement::Name(name) => value.as_object_mut()?.get_mut(name),
+        PathElement::Index(index) => value.as_array_mut()?.get_mut(*index),
+    })
+}
+
+fn missing(parent: &[PathElement], key: &PathElement) -> LiquidError {
+    LiquidError::typ(format!(
+        "no value at `{}`",
+        normalized_path(&[parent, std::slice::from_ref(key)].concat())
+    ))
+}
+
+/// A Python dict or list, changed in place. The root value itself can't be
+/// replaced.
+pub struct PyDocument<'py> {
+    root: Bound<'py, PyAny>,
+}
+
+impl<'py> PyDocument<'py> {
+    pub fn new(root: Bound<'py, PyAny>) -> Self {
+        Self { root }
+    }
+
+    fn container_at(&self, path: &[PathElement]) -> Result<Bound<'py, PyAny>, LiquidError> {
+        match path.split_last() {
+            Some((key, parent)) => lookup(&self.root, path).ok_or_else(|| missing(parent, key)),
+            None => Ok(self.root.clone()),
+        }
+    }
+}
+
+impl<'py> Store for PyDocument<'py> {
+    type Value = Bound<'py, PyAny>;
+    type Node<'a>
+        = Bound<'py, PyAny>
+    where
+        Self: 'a;
+
+    fn root(&self) -> Self::Node<'_> {
+        self.root.clone()
+    }
+
+    fn replace_root(&mut self, _value: Self::Value) -> Result<(), LiquidError> {
+        Err(LiquidError::typ(
+            "can't replace the root value of Python data".to_owned(),
+        ))
+    }
+
+    fn set(
+        &mut self,
+        parent: &[PathElement],
+        key: &PathElement,
+        value: Self::Value,
+    ) -> Result<(), LiquidError> {
+        let container = self.container_at(parent)?;
+        let result = match key {
+            PathElement::Index(index) if Some(*index) == container.len().ok() => {
+                container.call_method1("append", (value,)).map(|_| ())
+            }
+            PathElement::Index(index) => container.set_item(index, value),
+            PathElement::Name(name) => container.set_item(name, value),
+        };
+        result.map_err(|err| python_error(parent, key, err))
+    }
+
+    fn remove(&mut self, parent: &[PathElement], key: &PathElement) -> Result<(), LiquidError> {
+        let container = self.container_at(parent)?;
+        let result = match key {
+            PathElement::Index(index) => container.del_item(index),
+            PathElement::Name(name) => container.del_item(name),
+        };
+        result.map_err(|err| python_error(parent, key, err))
+    }
+
+    fn container(&self, key: &PathElement) -> Self::Value {
+        match key {
+            PathElement::Name(_) => PyDict::new_bound(self.root.py()).into_any(),
+            PathElement::Index(_) => PyList::empty_bound(self.root.py()).into_any(),
+        }
+    }
+
+    fn null(&self) -> Self::Value {
+        self.root.py().None().into_bound(self.root.py())
+    }
+}
+
+fn python_error(parent: &[PathElement], key: &PathElement, err: PyErr) -> LiquidError {
+    LiquidError::typ(format!(
+        "can't change `{}`: {}",
+        normalized_path(&[parent, std::slice::from_ref(key)].concat()),
+        err
+    ))
+}
@@ -0,0 +1,142 @@
+//! Conversion between singular queries and RFC 6901 JSON Pointers
+//!
+//! A JSON Pointer doesn't say if a reference token is an array index or an
+//! object member name. Tokens that look like array indices, like `0` or `12`,
+//! become index selectors if they are in the lexer's index range. Everything
+//! else becomes a name selector.
+
+use crate::errors::LiquidError;
+use crate::lexer::Lexer;
+use crate::query::{Query, Segment, Selector};
+
+impl Query {
+    /// Return this query as a JSON Pointer, like `/a/0/b` for `a[0].b`.
+    ///
+    /// The query must be singular, or equivalent to a singular query, and
+    /// can't contain negative indices.
+    pub fn to_pointer(&self) -> Result<String, LiquidError> {
+        let query = self.to_singular().ok_or_else(|| {
+            LiquidError::typ(format!(
+                "can't convert `{}` to a JSON Pointer, expected a singular query",
+                self.shorthand()
+            ))
+        })?;
+
+        let mut pointer = String::new();
+
+        for segment in &query.segments {
+            pointer.push('/');
+            match segment {
+                Segment::Child { selectors, .. } => match selectors.as_slice() {
+                    [Selector::Name { name, .. }] => escape_into(&mut pointer, name),
+                    [Selector::Index { index, span }] if *index < 0 => {
+                        return Err(LiquidError::typ(format!(
+                            "can't convert `{}` to a JSON Pointer, negative index {}",
+                            self.shorthand(),
+                            index
+                        ))
+                        .with_span(*span))
+                    }
+                    [Selector::Index { index, .. }] => pointer.push_str(&index.to_string()),
+                    _ => unreachable!("singular queries have one name or index per segment"),
+                },
+                _ => unreachable!("singular queries only have child segments"),
+            }
+        }
+
+        Ok(pointer)
+    }
+
+    /// Parse JSON Pointer _pointer_ into a singular query, with indices
+    /// limited by _lexer_. Spans are byte offsets into _pointer_.
+    pub fn from_pointer(pointer: &str, lexer: &Lexer) -> Result<Query, LiquidError> {
+        if pointer.is_empty() {
+            return Ok(Query {
+                segments: Vec::new(),
+            });
+        }
+
+        let Some(tokens) = pointer.strip_prefix('/') else {
+            return Err(LiquidError::syntax(format!(
+                "invalid JSON Pointer `{pointer}`, expected a leading slash"
+            )));
+        };
+
+        let mut segments = Vec::new();
+        let mut start = 0;
+
+        for token in tokens.split('/') {
+            let span = (start + 1, start + 1 + token.len());
+            let selector = match array_index(token, lexer) {
+                Some(index) => Selector::Index {
+                    index,
+                    span: Some(span),
+                },
+                None => Selector::Name {
+                    name: unescape(token).map_err(|err| err.with_span(span))?,
+                    span: Some(span),
+                },
+            };
+
+            segments.push(Segment::Child {
+                selectors: vec![selector],
+                span: Some((start, span.1)),
+            });
+
+            start = span.1;
+        }
+
+        Ok(Query { segments })
+    }
+}
+
+fn escape_into(pointer: &mut String, name: &str) {
+    for c in name.chars() {
+        match c {
+            '~' => pointer.push_str("~0"),
+            '/' => pointer.push_str("~1"),
+            c => pointer.push(c),
+        }
+    }
+}
+
+fn unescape(token: &str) -> Result<String, LiquidError> {
+    let mut name = String::with_capacity(token.len());
+    let mut chars = token.chars();
+
+    while let Some(c) = chars.next() {
+        if c == '~' {
+            match chars.next() {
+                Some('0') => name.push('~'),
+                Some('1') => name.push('/'),
+                _ => {
+                    return Err(LiquidError::syntax(format!(
+                        "invalid JSON Pointer escape in `{token}`, expected `~0` or `~1`"
+                    )))
+                }
+            }
+        } else {
+            name.push(c);
+        }
+    }
+
+    Ok(name)
+}
+
+/// Return the index for RFC 6901 `array-index` _token_, or `None` if _token_
+/// is not an array index or is outside _lexer_'s index range.
+fn array_index(token: &str, lexer: &Lexer) -> Option<i64> {
+    let is_index = token == "0"
+        || (!token.starts_with('0')
+            && !token.is_empty()
+            && token.bytes().all(|b| b.is_ascii_digit()));
+
+    if !is_index {
+        return None;
+    }
+
+    token
+        .parse::<i64>()
+        .ok()
+        .filter(|index| lexer.query_parser.index_range.contains(index))
+}
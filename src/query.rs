@@ -4,9 +4,16 @@
 use std::fmt::{self, Write};
 
 use pyo3::prelude::*;
+use serde::{Deserialize, Serialize};
 
-#[pyclass]
-#[derive(Debug, Clone)]
+use crate::errors::LiquidError;
+use crate::lexer::{default_lexer, is_member_name, is_word, Lexer};
+use crate::model::{equals, node_methods, simple_enum_methods, Span};
+use crate::mutate::PyDocument;
+use crate::unescape::quote;
+
+#[pyclass(frozen, eq, module = "_liquid2")]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Query {
     #[pyo3(get)]
     pub segments: Vec<Segment>,
@@ -18,9 +25,17 @@ impl Query {
         self.segments.is_empty()
     }
 
+    // Returns the start and end index of this query, from the start of its
+    // first segment to the end of its last, or `None` if it has no segments.
+    pub fn span(&self) -> Option<Span> {
+        let first = self.segments.iter().find_map(|s| s.span())?;
+        let last = self.segments.iter().rev().find_map(|s| s.span())?;
+        Some((first.0, last.1))
+    }
+
     // Returns `true` if this query can resolve to at most one node, or `false` otherwise.
     pub fn is_singular(&self) -> bool {
-        self.segments.iter().all(|segment| {
+        self.path().iter().all(|segment| {
             if let Segment::Child { selectors, .. } = segment {
                 return selectors.len() == 1
                     && selectors.first().is_some_and(|selector| {
@@ -30,21 +45,108 @@ impl Query {
             false
         })
     }
+
+    // Returns this query's segments, without an end of input marker.
+    pub(crate) fn path(&self) -> &[Segment] {
+        match self.segments.split_last() {
+            Some((Segment::Eoi {}, path)) => path,
+            _ => &self.segments,
+        }
+    }
+
+    // Returns a query with all but the last segment of this one, or `None` if
+    // this query has no segments.
+    pub fn parent(&self) -> Option<Query> {
+        let (_, path) = self.path().split_last()?;
+        Some(Query {
+            segments: path.to_vec(),
+        })
+    }
+
+    // Returns `true` if this query begins with the segments of _prefix_,
+    // ignoring spans and equivalent forms. Segments are compared by syntax
+    // alone, so `a[0, 1]` does not start with `a[0]`.
+    pub fn starts_with(&self, prefix: &Query) -> bool {
+        self.strip_prefix(prefix).is_some()
+    }
+
+    // Returns the segments of this query that follow _prefix_, or `None` if
+    // this query does not start with _prefix_.
+    pub fn strip_prefix(&self, prefix: &Query) -> Option<Query> {
+        let prefix = prefix.path();
+        let path = self.path();
+
+        if prefix.len() > path.len() {
+            return None;
+        }
+
+        let (head, tail) = path.split_at(prefix.len());
+        head.iter()
+            .zip(prefix)
+            .all(|(left, right)| equals(left, right, false))
+            .then(|| Query {
+                segments: tail.to_vec(),
+            })
+    }
+
+    // Returns a query with the segments of _other_ following those of this
+    // query, like a loop variable's path substituted into a reference to it.
+    pub fn join(&self, other: &Query) -> Query {
+        Query {
+            segments: self.path().iter().chain(other.path()).cloned().collect(),
+        }
+    }
+
+    // Returns an equivalent singular query, rewriting slices that select a
+    // single index, or `None` if this query can resolve to more than one node.
+    pub fn to_singular(&self) -> Option<Query> {
+        let segments: Option<Vec<Segment>> = self
+            .path()
+            .iter()
+            .map(|segment| match segment {
+                Segment::Child { selectors, span } => match selectors.as_slice() {
+                    [Selector::Name { .. } | Selector::Index { .. }] => Some(segment.clone()),
+                    [selector @ Selector::Slice { .. }] => Some(Segment::Child {
+                        selectors: vec![Selector::Index {
+                            index: selector.single_index()?,
+                            span: selector.span(),
+                        }],
+                        span: *span,
+                    }),
+                    _ => None,
+                },
+                _ => None,
+            })
+            .collect();
+
+        Some(Query {
+            segments: segments?,
+        })
+    }
 }
 
-#[pymethods]
-impl Query {
+node_methods!(Query {
+    #[new]
+    fn py_new(segments: Vec<Segment>) -> Self {
+        Query { segments }
+    }
+
+    #[classattr]
+    fn __match_args__() -> (&'static str,) {
+        ("segments",)
+    }
+
     pub fn as_word(&self) -> Option<String> {
         if self.segments.len() != 1 {
             return None;
         }
 
-        if let Some(Segment::Child { selectors, .. }) = self.segments.get(0) {
+        if let Some(Segment::Child { selectors, .. }) = self.segments.first() {
             if selectors.len() != 1 {
                 return None;
             }
 
-            if let Some(Selector::Name { name, .. }) = selectors.get(0) {
+            if let Some(Selector::Name { name, .. }) = selectors.first() {
                 Some(name.to_owned())
             } else {
                 None
@@ -53,100 +155,323 @@ impl Query {
             None
         }
     }
+
+    #[pyo3(name = "shorthand")]
+    fn py_shorthand(&self) -> String {
+        self.shorthand().to_string()
+    }
+
+    /// Return `True` if this query can resolve to at most one node.
+    #[pyo3(name = "is_singular")]
+    fn py_is_singular(&self) -> bool {
+        self.is_singular()
+    }
+
+    /// Return this query without its last segment, or `None` if it has no
+    /// segments.
+    #[pyo3(name = "parent")]
+    fn py_parent(&self) -> Option<Query> {
+        self.parent()
+    }
+
+    /// Return `True` if this query begins with the segments of _prefix_,
+    /// ignoring spans.
+    #[pyo3(name = "starts_with")]
+    fn py_starts_with(&self, prefix: &Query) -> bool {
+        self.starts_with(prefix)
+    }
+
+    /// Return the segments of this query that follow _prefix_, or `None` if
+    /// this query does not start with _prefix_.
+    #[pyo3(name = "strip_prefix")]
+    fn py_strip_prefix(&self, prefix: &Query) -> Option<Query> {
+        self.strip_prefix(prefix)
+    }
+
+    /// Return a new query with the segments of _other_ appended to this one.
+    #[pyo3(name = "join")]
+    fn py_join(&self, other: &Query) -> Query {
+        self.join(other)
+    }
+
+    /// Return an equivalent singular query, or `None` if this query can
+    /// resolve to more than one node.
+    #[pyo3(name = "to_singular")]
+    fn py_to_singular(&self) -> Option<Query> {
+        self.to_singular()
+    }
+
+    /// Return this singular query as a JSON Pointer, like `/a/0/b`.
+    #[pyo3(name = "to_pointer")]
+    fn py_to_pointer(&self) -> Result<String, LiquidError> {
+        self.to_pointer()
+    }
+
+    /// Parse a JSON Pointer into a singular query. Reference tokens that look
+    /// like array indices in _lexer_'s index range become index selectors.
+    #[staticmethod]
+    #[pyo3(name = "from_pointer", signature = (pointer, *, lexer=None))]
+    fn py_from_pointer(
+        pointer: &str,
+        lexer: Option<PyRef<'_, Lexer>>,
+    ) -> Result<Query, LiquidError> {
+        Query::from_pointer(pointer, lexer.as_deref().unwrap_or(default_lexer()))
+    }
+
+    /// Replace the value at every location matched by this query in _data_
+    /// with _value_, returning the normalized paths that changed.
+    #[pyo3(name = "set")]
+    fn py_set(
+        &self,
+        data: &Bound<'_, PyAny>,
+        value: &Bound<'_, PyAny>,
+    ) -> Result<Vec<Query>, LiquidError> {
+        self.set(&mut PyDocument::new(data.clone()), value.clone())
+    }
+
+    /// Remove every value matched by this query from _data_, returning the
+    /// normalized paths that were removed.
+    #[pyo3(name = "delete")]
+    fn py_delete(&self, data: &Bound<'_, PyAny>) -> Result<Vec<Query>, LiquidError> {
+        self.delete(&mut PyDocument::new(data.clone()))
+    }
+
+    /// Like `set`, but create missing values, and any intermediate dicts and
+    /// lists a singular query needs. Lists that are too short for an index
+    /// are padded with `None`.
+    #[pyo3(name = "upsert")]
+    fn py_upsert(
+        &self,
+        data: &Bound<'_, PyAny>,
+        value: &Bound<'_, PyAny>,
+    ) -> Result<Vec<Query>, LiquidError> {
+        self.upsert(&mut PyDocument::new(data.clone()), value.clone())
+    }
+});
+
+impl Query {
+    /// Return a value that formats this query the way Liquid authors write
+    /// it, with an implicit root and dot shorthand wherever names allow.
+    pub fn shorthand(&self) -> Shorthand<'_> {
+        Shorthand(self)
+    }
+
+    /// Return a value that formats this query in the notation it was read
+    /// with. JSONPath queries, which end with an end of input marker, keep
+    /// their explicit root. Other queries are written in shorthand.
+    pub fn as_written(&self) -> AsWritten<'_> {
+        AsWritten(self)
+    }
+
+    fn write(&self, f: &mut fmt::Formatter<'_>, notation: Notation) -> fmt::Result {
+        match self.segments.first() {
+            Some(first @ Segment::Child { .. }) if notation == Notation::Shorthand => {
+                first.write(f, notation, true)?;
+                self.segments[1..]
+                    .iter()
+                    .try_for_each(|segment| segment.write(f, notation, false))
+            }
+            _ => self.write_rooted(f, '$', notation),
+        }
+    }
+
+    // Write this query's segments following an explicit _root_ identifier.
+    fn write_rooted(
+        &self,
+        f: &mut fmt::Formatter<'_>,
+        root: char,
+        notation: Notation,
+    ) -> fmt::Result {
+        f.write_char(root)?;
+        self.segments
+            .iter()
+            .try_for_each(|segment| segment.write(f, notation, false))
+    }
+}
+
+/// How to write a query as source text.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Notation {
+    /// An explicit root and bracketed, quoted names, like `$['a']['b'][0]`.
+    Canonical,
+    /// An implicit root and dot shorthand, like `a.b[0]`.
+    Shorthand,
 }
 
 impl fmt::Display for Query {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(
-            f,
-            "${}",
-            self.segments
-                .iter()
-                .map(|s| s.to_string())
-                .collect::<Vec<String>>()
-                .join("")
-        )
+        self.write(f, Notation::Canonical)
     }
 }
 
-#[pyclass]
-#[derive(Debug, Clone)]
+/// A query formatted in Liquid style shorthand, from `Query::shorthand()`.
+pub struct Shorthand<'a>(&'a Query);
+
+impl fmt::Display for Shorthand<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.write(f, Notation::Shorthand)
+    }
+}
+
+/// A query formatted in the notation it was read with, from
+/// `Query::as_written()`.
+pub struct AsWritten<'a>(&'a Query);
+
+impl fmt::Display for AsWritten<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.0.segments.last() {
+            Some(Segment::Eoi {}) => self.0.write_rooted(f, '$', Notation::Shorthand),
+            _ => self.0.write(f, Notation::Shorthand),
+        }
+    }
+}
+
+#[pyclass(frozen, eq, module = "_liquid2")]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum Segment {
+    #[pyo3(constructor = (selectors, span=None))]
     Child {
         selectors: Vec<Selector>,
-        span: (usize, usize),
+        span: Option<Span>,
     },
+    #[pyo3(constructor = (selectors, span=None))]
     Recursive {
         selectors: Vec<Selector>,
-        span: (usize, usize),
+        span: Option<Span>,
     },
     Eoi {}, // Is this needed?
 }
 
-impl fmt::Display for Segment {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+impl Segment {
+    pub fn span(&self) -> Option<Span> {
         match self {
-            Segment::Child { selectors, .. } => {
-                write!(
-                    f,
-                    "[{}]",
-                    selectors
-                        .iter()
-                        .map(|s| s.to_string())
-                        .collect::<Vec<String>>()
-                        .join(", ")
-                )
+            Segment::Child { span, .. } | Segment::Recursive { span, .. } => *span,
+            Segment::Eoi {} => None,
+        }
+    }
+}
+
+impl Segment {
+    // Write this segment as source text. _implicit_root_ is `true` for the
+    // first segment of a query written without a leading `$`.
+    fn write(
+        &self,
+        f: &mut fmt::Formatter<'_>,
+        notation: Notation,
+        implicit_root: bool,
+    ) -> fmt::Result {
+        let (prefix, selectors) = match self {
+            Segment::Child { selectors, .. } if implicit_root => ("", selectors),
+            Segment::Child { selectors, .. } => (".", selectors),
+            Segment::Recursive { selectors, .. } => ("..", selectors),
+            Segment::Eoi {} => return Ok(()),
+        };
+
+        if notation == Notation::Shorthand {
+            match selectors.as_slice() {
+                // A reserved word at the start of a template query would be
+                // read as a keyword.
+                [Selector::Name { name, .. }]
+                    if is_member_name(name) && (!implicit_root || is_word(name)) =>
+                {
+                    return write!(f, "{prefix}{name}");
+                }
+                [Selector::Wild { .. }] if !implicit_root => return write!(f, "{prefix}*"),
+                _ => (),
             }
-            Segment::Recursive { selectors, .. } => {
-                write!(
-                    f,
-                    "..[{}]",
-                    selectors
-                        .iter()
-                        .map(|s| s.to_string())
-                        .collect::<Vec<String>>()
-                        .join(", ")
-                )
+        }
+
+        if let Segment::Recursive { .. } = self {
+            f.write_str("..")?;
+        }
+
+        f.write_char('[')?;
+        for (i, selector) in selectors.iter().enumerate() {
+            if i > 0 {
+                f.write_str(", ")?;
             }
-            Segment::Eoi {} => Ok(()),
+            selector.write(f, notation)?;
         }
+        f.write_char(']')
     }
 }
 
-#[pyclass]
-#[derive(Debug, Clone)]
+impl fmt::Display for Segment {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.write(f, Notation::Canonical, false)
+    }
+}
+
+node_methods!(Segment {});
+
+#[pyclass(frozen, eq, module = "_liquid2")]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum Selector {
-    Name {
-        name: String,
-        span: (usize, usize),
-    },
-    Index {
-        index: i64,
-        span: (usize, usize),
-    },
+    #[pyo3(constructor = (name, span=None))]
+    Name { name: String, span: Option<Span> },
+    #[pyo3(constructor = (index, span=None))]
+    Index { index: i64, span: Option<Span> },
+    #[pyo3(constructor = (start=None, stop=None, step=None, span=None))]
     Slice {
         start: Option<i64>,
         stop: Option<i64>,
         step: Option<i64>,
-        span: (usize, usize),
-    },
-    Wild {
-        span: (usize, usize),
+        span: Option<Span>,
     },
+    #[pyo3(constructor = (span=None))]
+    Wild { span: Option<Span> },
+    #[pyo3(constructor = (expression, span=None))]
     Filter {
         expression: Box<FilterExpression>,
-        span: (usize, usize),
+        span: Option<Span>,
     },
+    #[pyo3(constructor = (query, span=None))]
     SingularQuery {
         query: Box<Query>,
-        span: (usize, usize),
+        span: Option<Span>,
     },
 }
 
-impl fmt::Display for Selector {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+impl Selector {
+    pub fn span(&self) -> Option<Span> {
         match self {
-            Selector::Name { name, .. } => write!(f, "'{name}'"),
+            Selector::Name { span, .. }
+            | Selector::Index { span, .. }
+            | Selector::Slice { span, .. }
+            | Selector::Wild { span }
+            | Selector::Filter { span, .. }
+            | Selector::SingularQuery { span, .. } => *span,
+        }
+    }
+}
+
+impl Selector {
+    // Returns the only index selected by a slice, like `1` for `[1:2]`, or
+    // `None` if this is not a slice of exactly one index.
+    fn single_index(&self) -> Option<i64> {
+        let Selector::Slice {
+            start,
+            stop,
+            step: None | Some(1),
+            ..
+        } = self
+        else {
+            return None;
+        };
+
+        let start = start.unwrap_or(0);
+        let next = start.checked_add(1)?;
+        match stop {
+            // `[-1:0]` is empty, `[-1:]` is the last item.
+            Some(stop) if *stop == next && next != 0 => Some(start),
+            None if start == -1 => Some(start),
+            _ => None,
+        }
+    }
+
+    fn write(&self, f: &mut fmt::Formatter<'_>, notation: Notation) -> fmt::Result {
+        match self {
+            Selector::Name { name, .. } => f.write_str(&quote(name)),
             Selector::Index {
                 index: array_index, ..
             } => write!(f, "{array_index}"),
@@ -155,79 +480,104 @@ impl fmt::Display for Selector {
             } => {
                 write!(
                     f,
-                    "{}:{}:{}",
-                    start
-                        .and_then(|i| Some(i.to_string()))
-                        .unwrap_or(String::from("")),
-                    stop.and_then(|i| Some(i.to_string()))
-                        .unwrap_or(String::from("")),
-                    step.and_then(|i| Some(i.to_string()))
-                        .unwrap_or(String::from("1")),
-                )
+                    "{}:{}",
+                    start.map(|i| i.to_string()).unwrap_or_default(),
+                    stop.map(|i| i.to_string()).unwrap_or_default(),
+                )?;
+                match step {
+                    Some(step) => write!(f, ":{step}"),
+                    None => Ok(()),
+                }
             }
             Selector::Wild { .. } => f.write_char('*'),
-            Selector::Filter { expression, .. } => write!(f, "?{expression}"),
-            Selector::SingularQuery { query, .. } => write!(f, "{query}"),
+            Selector::Filter { expression, .. } => {
+                f.write_char('?')?;
+                expression.write(f, notation)
+            }
+            Selector::SingularQuery { query, .. } => query.write(f, notation),
         }
     }
 }
 
-#[pyclass]
-#[derive(Debug, Clone)]
+impl fmt::Display for Selector {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.write(f, Notation::Canonical)
+    }
+}
+
+node_methods!(Selector {});
+
+#[pyclass(frozen, eq, module = "_liquid2")]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum FilterExpression {
-    True_ {
-        span: (usize, usize),
-    },
-    False_ {
-        span: (usize, usize),
-    },
-    Null {
-        span: (usize, usize),
-    },
-    StringLiteral {
-        value: String,
-        span: (usize, usize),
-    },
-    Int {
-        value: i64,
-        span: (usize, usize),
-    },
-    Float {
-        value: f64,
-        span: (usize, usize),
-    },
+    #[pyo3(constructor = (span=None))]
+    True_ { span: Option<Span> },
+    #[pyo3(constructor = (span=None))]
+    False_ { span: Option<Span> },
+    #[pyo3(constructor = (span=None))]
+    Null { span: Option<Span> },
+    #[pyo3(constructor = (value, span=None))]
+    StringLiteral { value: String, span: Option<Span> },
+    #[pyo3(constructor = (value, span=None))]
+    Int { value: i64, span: Option<Span> },
+    #[pyo3(constructor = (value, span=None))]
+    Float { value: f64, span: Option<Span> },
+    #[pyo3(constructor = (expression, span=None))]
     Not {
         expression: Box<FilterExpression>,
-        span: (usize, usize),
+        span: Option<Span>,
     },
+    #[pyo3(constructor = (left, operator, right, span=None))]
     Logical {
         left: Box<FilterExpression>,
         operator: LogicalOperator,
         right: Box<FilterExpression>,
-        span: (usize, usize),
+        span: Option<Span>,
     },
+    #[pyo3(constructor = (left, operator, right, span=None))]
     Comparison {
         left: Box<FilterExpression>,
         operator: ComparisonOperator,
         right: Box<FilterExpression>,
-        span: (usize, usize),
+        span: Option<Span>,
     },
+    #[pyo3(constructor = (query, span=None))]
     RelativeQuery {
         query: Box<Query>,
-        span: (usize, usize),
+        span: Option<Span>,
     },
+    #[pyo3(constructor = (query, span=None))]
     RootQuery {
         query: Box<Query>,
-        span: (usize, usize),
+        span: Option<Span>,
     },
+    #[pyo3(constructor = (name, args, span=None))]
     Function {
         name: String,
         args: Vec<FilterExpression>,
-        span: (usize, usize),
+        span: Option<Span>,
     },
 }
 
 impl FilterExpression {
+    pub fn span(&self) -> Option<Span> {
+        use FilterExpression::*;
+        match self {
+            True_ { span }
+            | False_ { span }
+            | Null { span }
+            | StringLiteral { span, .. }
+            | Int { span, .. }
+            | Float { span, .. }
+            | Not { span, .. }
+            | Logical { span, .. }
+            | Comparison { span, .. }
+            | RelativeQuery { span, .. }
+            | RootQuery { span, .. }
+            | Function { span, .. } => *span,
+        }
+    }
+
     pub fn is_literal(&self) -> bool {
         matches!(
             self,
@@ -241,70 +591,125 @@ impl FilterExpression {
     }
 }
 
-impl fmt::Display for FilterExpression {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+impl FilterExpression {
+    fn write(&self, f: &mut fmt::Formatter<'_>, notation: Notation) -> fmt::Result {
         use FilterExpression::*;
         match self {
             True_ { .. } => f.write_str("true"),
             False_ { .. } => f.write_str("false"),
             Null { .. } => f.write_str("null"),
-            StringLiteral { value, .. } => write!(f, "\"{value}\""),
+            StringLiteral { value, .. } => f.write_str(&quote(value)),
             Int { value, .. } => write!(f, "{value}"),
-            Float { value, .. } => write!(f, "{value}"),
-            Not { expression, .. } => write!(f, "!{expression}"),
+            Float { value, .. } => f.write_str(&float_literal(*value)),
+            Not { expression, .. } => {
+                f.write_char('!')?;
+                expression.write_operand(
+                    f,
+                    notation,
+                    matches!(
+                        **expression,
+                        Logical { .. } | Comparison { .. } | Not { .. }
+                    ),
+                )
+            }
             Logical {
                 left,
                 operator,
                 right,
                 ..
-            } => write!(f, "({left} {operator} {right})"),
+            } => {
+                // Logical operators are left associative, so a right operand
+                // of equal precedence needs parentheses too.
+                let precedence = self.precedence();
+                left.write_operand(f, notation, left.precedence() < precedence)?;
+                write!(f, " {operator} ")?;
+                right.write_operand(f, notation, right.precedence() <= precedence)
+            }
             Comparison {
                 left,
                 operator,
                 right,
                 ..
-            } => write!(f, "{left} {operator} {right}"),
-            RelativeQuery { query, .. } => {
-                write!(
-                    f,
-                    "@{}",
-                    query
-                        .segments
-                        .iter()
-                        .map(|s| s.to_string())
-                        .collect::<Vec<String>>()
-                        .join("")
-                )
-            }
-            RootQuery { query, .. } => {
-                write!(
-                    f,
-                    "${}",
-                    query
-                        .segments
-                        .iter()
-                        .map(|s| s.to_string())
-                        .collect::<Vec<String>>()
-                        .join("")
-                )
+            } => {
+                left.write(f, notation)?;
+                write!(f, " {operator} ")?;
+                right.write(f, notation)
             }
+            // Filter queries always have an explicit root, so they can't be
+            // mistaken for literals or function names.
+            RelativeQuery { query, .. } => query.write_rooted(f, '@', notation),
+            RootQuery { query, .. } => query.write_rooted(f, '$', notation),
             Function { name, args, .. } => {
-                write!(
-                    f,
-                    "{}({})",
-                    name,
-                    args.iter()
-                        .map(|a| a.to_string())
-                        .collect::<Vec<String>>()
-                        .join(", ")
-                )
+                write!(f, "{name}(")?;
+                for (i, arg) in args.iter().enumerate() {
+                    if i > 0 {
+                        f.write_str(", ")?;
+                    }
+                    // A bare query argument is never read as the left hand
+                    // side of a logical or comparison expression.
+                    arg.write_operand(
+                        f,
+                        notation,
+                        matches!(arg, Logical { .. } | Comparison { .. }),
+                    )?;
+                }
+                f.write_char(')')
             }
         }
     }
+
+    fn write_operand(
+        &self,
+        f: &mut fmt::Formatter<'_>,
+        notation: Notation,
+        parenthesize: bool,
+    ) -> fmt::Result {
+        if parenthesize {
+            f.write_char('(')?;
+            self.write(f, notation)?;
+            f.write_char(')')
+        } else {
+            self.write(f, notation)
+        }
+    }
+
+    // Returns the binding strength of this expression's top level operator.
+    // Anything that isn't a logical expression binds tighter than `&&`.
+    fn precedence(&self) -> u8 {
+        match self {
+            FilterExpression::Logical {
+                operator: LogicalOperator::Or,
+                ..
+            } => 1,
+            FilterExpression::Logical {
+                operator: LogicalOperator::And,
+                ..
+            } => 2,
+            _ => 3,
+        }
+    }
+}
+
+impl fmt::Display for FilterExpression {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.write(f, Notation::Canonical)
+    }
 }
 
-#[pyclass(eq, eq_int)]
-#[derive(Debug, Clone, PartialEq)]
+node_methods!(FilterExpression {});
+
+/// Format _value_ so that it reads back as a float, not an integer.
+pub fn float_literal(value: f64) -> String {
+    let literal = value.to_string();
+    if literal.contains('.') {
+        literal
+    } else {
+        format!("{literal}.0")
+    }
+}
+
+#[pyclass(frozen, eq, eq_int, hash, module = "_liquid2")]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum LogicalOperator {
     And,
     Or,
@@ -319,15 +724,10 @@ impl fmt::Display for LogicalOperator {
     }
 }
 
-#[pymethods]
-impl LogicalOperator {
-    fn __str__(&self) -> String {
-        self.to_string()
-    }
-}
+simple_enum_methods!(LogicalOperator);
 
-#[pyclass(eq, eq_int)]
-#[derive(Debug, Clone, PartialEq)]
+#[pyclass(frozen, eq, eq_int, hash, module = "_liquid2")]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum ComparisonOperator {
     Eq,
     Ne,
@@ -350,12 +750,7 @@ impl fmt::Display for ComparisonOperator {
     }
 }
 
-#[pymethods]
-impl ComparisonOperator {
-    fn __str__(&self) -> String {
-        self.to_string()
-    }
-}
+simple_enum_methods!(ComparisonOperator);
 
 impl<'py> pyo3::FromPyObject<'py> for Box<Query> {
     fn extract_bound(ob: &Bound<'py, PyAny>) -> PyResult<Self> {
@@ -0,0 +1,222 @@
+//! Range literal resolution
+//!
+
+use std::fmt;
+use std::ops::RangeInclusive;
+
+use pyo3::exceptions::PyTypeError;
+use pyo3::prelude::*;
+use pyo3::types::{PyBool, PyFloat, PyLong, PyString};
+
+use crate::errors::LiquidError;
+use crate::markup::{RangeArgument, Token};
+use crate::model::Span;
+use crate::query::Query;
+
+/// A range literal, like `(1..5)` or `(a..b.size)`, with its endpoints.
+#[pyclass(frozen)]
+#[derive(Debug, Clone)]
+pub struct Range {
+    #[pyo3(get)]
+    pub start: RangeArgument,
+    #[pyo3(get)]
+    pub stop: RangeArgument,
+    #[pyo3(get)]
+    pub span: Option<Span>,
+}
+
+/// The value of a range endpoint after resolving a query.
+#[derive(Debug, Clone)]
+pub enum RangeValue {
+    Int(i64),
+    Float(f64),
+    String(String),
+}
+
+/// Resolve queries in range endpoints to values.
+pub trait RangeContext {
+    /// Return the value at _path_, or `None` if it is undefined or is not
+    /// something that can be cast to an integer.
+    fn resolve(&self, path: &Query) -> Option<RangeValue>;
+}
+
+impl<F> RangeContext for F
+where
+    F: Fn(&Query) -> Option<RangeValue>,
+{
+    fn resolve(&self, path: &Query) -> Option<RangeValue> {
+        self(path)
+    }
+}
+
+impl Range {
+    /// Return the range literal in _token_, or `None` if _token_ is not a
+    /// range literal.
+    pub fn from_token(token: &Token) -> Option<Self> {
+        match token {
+            Token::RangeLiteral { start, stop, span } => Some(Range {
+                start: start.clone(),
+                stop: stop.clone(),
+                span: *span,
+            }),
+            _ => None,
+        }
+    }
+
+    /// Resolve this range's endpoints to integers.
+    ///
+    /// Floats are truncated. Strings are read like Python's `int()` reads
+    /// them, and strings that are not integers and undefined queries are cast
+    /// to `0`, matching the Python renderer. It is an error if an endpoint
+    /// doesn't fit in an `i64`.
+    pub fn resolve(&self, context: &impl RangeContext) -> Result<(i64, i64), LiquidError> {
+        Ok((
+            self.resolve_argument(&self.start, context)?,
+            self.resolve_argument(&self.stop, context)?,
+        ))
+    }
+
+    /// Return an iterator over this range's integers, including the stop
+    /// value. A range with a start greater than its stop is empty.
+    pub fn iter(&self, context: &impl RangeContext) -> Result<RangeInclusive<i64>, LiquidError> {
+        let (start, stop) = self.resolve(context)?;
+        Ok(start..=stop)
+    }
+
+    fn resolve_argument(
+        &self,
+        argument: &RangeArgument,
+        context: &impl RangeContext,
+    ) -> Result<i64, LiquidError> {
+        match argument {
+            RangeArgument::IntegerLiteral { value, .. } => Ok(*value),
+            RangeArgument::FloatLiteral { value, .. } => RangeValue::Float(*value).to_int(),
+            RangeArgument::StringLiteral { value, .. } => {
+                RangeValue::String(value.to_owned()).to_int()
+            }
+            RangeArgument::Query { path, .. } => {
+                context.resolve(path).map_or(Ok(0), |v| v.to_int())
+            }
+        }
+        .map_err(|err| err.with_span(argument.span()))
+    }
+}
+
+impl RangeValue {
+    /// Cast this value to an integer, truncating floats. Strings that are not
+    /// integers, like `"1.5"` or `"1e3"`, are `0`.
+    pub fn to_int(&self) -> Result<i64, LiquidError> {
+        match self {
+            RangeValue::Int(value) => Ok(*value),
+            RangeValue::Float(value) => float_to_int(*value),
+            RangeValue::String(value) => parse_int_string(value).unwrap_or(Ok(0)),
+        }
+    }
+
+    fn from_py(obj: &Bound<'_, PyAny>) -> Result<Option<Self>, LiquidError> {
+        Ok(if obj.is_instance_of::<PyBool>() {
+            obj.extract::<bool>()
+                .ok()
+                .map(|v| RangeValue::Int(v as i64))
+        } else if obj.is_instance_of::<PyLong>() {
+            Some(RangeValue::Int(
+                obj.extract::<i64>().map_err(|_| out_of_range(obj))?,
+            ))
+        } else if obj.is_instance_of::<PyFloat>() {
+            obj.extract::<f64>().ok().map(RangeValue::Float)
+        } else if obj.is_instance_of::<PyString>() {
+            obj.extract::<String>().ok().map(RangeValue::String)
+        } else {
+            None
+        })
+    }
+}
+
+/// Read _value_ the way Python's `int()` reads a string: an optional sign and
+/// decimal digits, with single underscores allowed between digits and
+/// surrounding whitespace ignored. Return `None` if _value_ is not an integer,
+/// or an error if it doesn't fit in an `i64`.
+pub fn parse_int_string(value: &str) -> Option<Result<i64, LiquidError>> {
+    let value = value.trim();
+    let digits = value.strip_prefix(['+', '-']).unwrap_or(value);
+    let valid = digits
+        .split('_')
+        .all(|part| !part.is_empty() && part.bytes().all(|b| b.is_ascii_digit()));
+
+    valid.then(|| {
+        value
+            .replace('_', "")
+            .parse::<i64>()
+            .map_err(|_| out_of_range(value))
+    })
+}
+
+fn float_to_int(value: f64) -> Result<i64, LiquidError> {
+    let value = value.trunc();
+    // `i64::MAX as f64` rounds up to 2^63, which is out of range.
+    if value >= i64::MIN as f64 && value < i64::MAX as f64 {
+        Ok(value as i64)
+    } else {
+        Err(out_of_range(value))
+    }
+}
+
+fn out_of_range(value: impl fmt::Display) -> LiquidError {
+    LiquidError::typ(format!("range endpoint out of range `{value}`"))
+}
+
+impl fmt::Display for Range {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "({}..{})", self.start, self.stop)
+    }
+}
+
+#[pymethods]
+impl Range {
+    #[new]
+    fn py_new(token: Token) -> PyResult<Self> {
+        Range::from_token(&token)
+            .ok_or_else(|| PyTypeError::new_err(format!("expected a range literal, found {token}")))
+    }
+
+    /// Resolve endpoints using _resolver_, a callable that accepts a `Query`
+    /// and returns its value.
+    #[pyo3(name = "resolve")]
+    fn py_resolve(&self, resolver: &Bound<'_, PyAny>) -> PyResult<(i64, i64)> {
+        Ok((
+            self.py_resolve_argument(&self.start, resolver)?,
+            self.py_resolve_argument(&self.stop, resolver)?,
+        ))
+    }
+
+    /// Like `resolve`, but return a Python `range` that includes the stop value.
+    fn evaluate<'py>(&self, resolver: &Bound<'py, PyAny>) -> PyResult<Bound<'py, PyAny>> {
+        let (start, stop) = self.py_resolve(resolver)?;
+        // `i64::MAX + 1` is fine as a Python int.
+        PyModule::import_bound(resolver.py(), "builtins")?
+            .getattr("range")?
+            .call1((start, i128::from(stop) + 1))
+    }
+
+    fn __str__(&self) -> String {
+        self.to_string()
+    }
+}
+
+impl Range {
+    fn py_resolve_argument(
+        &self,
+        argument: &RangeArgument,
+        resolver: &Bound<'_, PyAny>,
+    ) -> PyResult<i64> {
+        match argument {
+            RangeArgument::Query { path, span } => {
+                let value = resolver.call1((path.clone(),))?;
+                Ok(RangeValue::from_py(&value)
+                    .and_then(|v| v.map_or(Ok(0), |v| v.to_int()))
+                    .map_err(|err| err.with_span(*span))?)
+            }
+            _ => Ok(self.resolve_argument(argument, &|_: &Query| None)?),
+        }
+    }
+}
@@ -0,0 +1,185 @@
+//! Versioned binary encoding of token streams
+//!
+//! An encoded token stream starts with a fixed size header followed by the
+//! bincode encoding of `Vec<Markup>`.
+//!
+//! ```text
+//! magic (4 bytes) | format version (u16) | grammar hash (u64) | source hash (u64)
+//! ```
+//!
+//! Integers are little endian. The grammar hash changes whenever
+//! `markup.pest` changes, so token streams produced by a different lexer are
+//! rejected. The source hash is used to detect token streams that are stale
+//! with respect to their template source text.
+//!
+//! The grammar hash doesn't cover the layout of syntax tree types, so
+//! `FORMAT_VERSION` must be bumped whenever their encoding changes.
+//! `python/tests/test_serialize.py` pins the encoding of a sample template
+//! for each format version and fails if it changes without a bump.
+
+use lazy_static::lazy_static;
+use pyo3::prelude::*;
+use pyo3::types::PyBytes;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+use crate::errors::LiquidError;
+use crate::markup::Markup;
+
+pub const MAGIC: &[u8; 4] = b"LQ2T";
+pub const FORMAT_VERSION: u16 = 2;
+const HEADER_LENGTH: usize = 4 + 2 + 8 + 8;
+
+lazy_static! {
+    static ref GRAMMAR_HASH: u64 = stable_hash(include_bytes!("markup.pest"));
+}
+
+/// Return a hash of the lexer's grammar.
+pub fn grammar_hash() -> u64 {
+    *GRAMMAR_HASH
+}
+
+/// Return a hash of template source text that is stable across processes
+/// and platforms.
+pub fn source_hash(source: &str) -> u64 {
+    stable_hash(source.as_bytes())
+}
+
+/// 64-bit FNV-1a.
+fn stable_hash(data: &[u8]) -> u64 {
+    data.iter().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ u64::from(*byte)).wrapping_mul(0x100000001b3)
+    })
+}
+
+/// Header fields of an encoded token stream.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Header {
+    pub format_version: u16,
+    pub grammar_hash: u64,
+    pub source_hash: u64,
+}
+
+impl Header {
+    fn read(data: &[u8]) -> Result<Self, LiquidError> {
+        if data.len() < HEADER_LENGTH || &data[..4] != MAGIC {
+            return Err(LiquidError::serialization(
+                "not an encoded token stream".to_owned(),
+            ));
+        }
+
+        Ok(Header {
+            format_version: u16::from_le_bytes(data[4..6].try_into().unwrap()),
+            grammar_hash: u64::from_le_bytes(data[6..14].try_into().unwrap()),
+            source_hash: u64::from_le_bytes(data[14..22].try_into().unwrap()),
+        })
+    }
+
+    fn write(&self, buf: &mut Vec<u8>) {
+        buf.extend_from_slice(MAGIC);
+        buf.extend_from_slice(&self.format_version.to_le_bytes());
+        buf.extend_from_slice(&self.grammar_hash.to_le_bytes());
+        buf.extend_from_slice(&self.source_hash.to_le_bytes());
+    }
+}
+
+/// Encode _tokens_, produced by tokenizing _source_.
+pub fn dumps(tokens: &[Markup], source: &str) -> Result<Vec<u8>, LiquidError> {
+    encode(tokens, source_hash(source))
+}
+
+/// Decode a token stream produced by `dumps`.
+///
+/// If _source_ is given, it must be the same template source text that was
+/// used when encoding, otherwise the token stream is considered stale and an
+/// error is returned.
+pub fn loads(data: &[u8], source: Option<&str>) -> Result<Vec<Markup>, LiquidError> {
+    let header = Header::read(data)?;
+
+    if header.format_version != FORMAT_VERSION {
+        return Err(LiquidError::serialization(format!(
+            "unsupported format version {}, expected {}",
+            header.format_version, FORMAT_VERSION
+        )));
+    }
+
+    if header.grammar_hash != grammar_hash() {
+        return Err(LiquidError::serialization(
+            "token stream was encoded with a different grammar".to_owned(),
+        ));
+    }
+
+    if source.is_some_and(|s| source_hash(s) != header.source_hash) {
+        return Err(LiquidError::serialization(
+            "token stream is stale, template source has changed".to_owned(),
+        ));
+    }
+
+    bincode::deserialize(&data[HEADER_LENGTH..])
+        .map_err(|err| LiquidError::serialization(format!("invalid token stream: {err}")))
+}
+
+/// Return `true` if _data_ is a token stream that can be decoded by this
+/// version of the lexer and was encoded from _source_.
+pub fn is_current(data: &[u8], source: &str) -> bool {
+    Header::read(data).is_ok_and(|header| {
+        header.format_version == FORMAT_VERSION
+            && header.grammar_hash == grammar_hash()
+            && header.source_hash == source_hash(source)
+    })
+}
+
+fn encode<T: Serialize + ?Sized>(value: &T, hash: u64) -> Result<Vec<u8>, LiquidError> {
+    let mut buf = Vec::new();
+    Header {
+        format_version: FORMAT_VERSION,
+        grammar_hash: grammar_hash(),
+        source_hash: hash,
+    }
+    .write(&mut buf);
+
+    bincode::serialize_into(&mut buf, value)
+        .map_err(|err| LiquidError::serialization(err.to_string()))?;
+    Ok(buf)
+}
+
+/// Encode a single syntax tree node using the same format as `dumps`,
+/// without a source hash.
+pub fn encode_node<T: Serialize>(node: &T) -> Result<Vec<u8>, LiquidError> {
+    encode(node, 0)
+}
+
+/// Decode a single syntax tree node produced by `encode_node`.
+pub fn decode_node<T: DeserializeOwned>(data: &[u8]) -> Result<T, LiquidError> {
+    let header = Header::read(data)?;
+    if header.format_version != FORMAT_VERSION || header.grammar_hash != grammar_hash() {
+        return Err(LiquidError::serialization(
+            "node was encoded with a different format or grammar".to_owned(),
+        ));
+    }
+
+    bincode::deserialize(&data[HEADER_LENGTH..])
+        .map_err(|err| LiquidError::serialization(format!("invalid node: {err}")))
+}
+
+#[pyfunction]
+#[pyo3(name = "dumps")]
+pub fn py_dumps<'py>(
+    py: Python<'py>,
+    tokens: Vec<Markup>,
+    source: &str,
+) -> PyResult<Bound<'py, PyBytes>> {
+    Ok(PyBytes::new_bound(py, &dumps(&tokens, source)?))
+}
+
+#[pyfunction]
+#[pyo3(name = "loads", signature = (data, *, source=None))]
+pub fn py_loads(data: &[u8], source: Option<&str>) -> PyResult<Vec<Markup>> {
+    Ok(loads(data, source)?)
+}
+
+#[pyfunction]
+#[pyo3(name = "is_current")]
+pub fn py_is_current(data: &[u8], source: &str) -> bool {
+    is_current(data, source)
+}
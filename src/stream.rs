@@ -0,0 +1,735 @@
+//! JSONPath evaluation over streaming JSON
+//!
+//! `stream` evaluates a query while reading a JSON document, without loading
+//! the document into memory. Only values that are matched, or that a filter
+//! selector needs to test, are read in full. Everything else is skipped as it
+//! is read.
+//!
+//! Queries can use name, index, wildcard and filter selectors, in child and
+//! descendant segments. Index selectors can't be negative, and filters can't
+//! refer to the root value, because both would need more of the document
+//! than has been read.
+//!
+//! Matches are reported in the same order as `find`, once for each time a
+//! node is selected. An object with duplicate member names that is streamed
+//! through has a match for every occurrence of a selected name, where parsing
+//! the whole document would keep only the last. Objects that are read in
+//! full, like matches and the values a filter tests, keep only the last
+//! occurrence, like parsing the whole document does.
+//!
+//! A match is held back until every match that comes before it has been
+//! found, so queries like `$[1, 0]` or `$..a`, whose matches are not in
+//! document order, can hold matches until the end of an enclosing array or
+//! object.
+
+use std::collections::{HashMap, VecDeque};
+use std::io::{BufRead, BufReader, Read};
+
+use lazy_static::lazy_static;
+use regex::Regex;
+
+use crate::errors::LiquidError;
+use crate::eval::{find, normalized_path, test_filter, PathElement};
+use crate::query::{FilterExpression, Query, Segment, Selector};
+use crate::visit::{walk_filter_expression, walk_selector, Visitor};
+
+/// The deepest nesting of arrays and objects we'll read.
+const MAX_DEPTH: usize = 512;
+
+lazy_static! {
+    // The number grammar from RFC 8259.
+    static ref NUMBER: Regex =
+        Regex::new(r"^-?(?:0|[1-9][0-9]*)(?:\.[0-9]+)?(?:[eE][-+]?[0-9]+)?$").unwrap();
+}
+
+/// Return an iterator over matches of _query_ in the JSON document read from
+/// _reader_, or an error if _query_ can't be evaluated without reading the
+/// whole document first.
+pub fn stream<R: Read>(query: &Query, reader: R) -> Result<StreamMatches<R>, LiquidError> {
+    check_streamable(query)?;
+    let segments = query.path().to_vec();
+
+    Ok(StreamMatches {
+        suffixes: (0..=segments.len())
+            .map(|k| Query {
+                segments: segments[k..].to_vec(),
+            })
+            .collect(),
+        segments,
+        reader: Reader {
+            inner: BufReader::new(reader),
+            offset: 0,
+        },
+        stack: Vec::new(),
+        path: Vec::new(),
+        slots: Slots::default(),
+        pending: VecDeque::new(),
+        started: false,
+        done: false,
+    })
+}
+
+/// Return an error explaining why _query_ can't be streamed, if it can't.
+pub fn check_streamable(query: &Query) -> Result<(), LiquidError> {
+    let unsupported = |reason: &str, span| {
+        Err(LiquidError::typ(format!("can't stream `{query}`, {reason}")).with_span(span))
+    };
+
+    for segment in query.path() {
+        let (Segment::Child { selectors, .. } | Segment::Recursive { selectors, .. }) = segment
+        else {
+            continue;
+        };
+
+        for selector in selectors {
+            match selector {
+                Selector::Name { .. } | Selector::Wild { .. } => (),
+                Selector::Index { index, span } if *index < 0 => {
+                    return unsupported("negative indices need the length of an array", *span)
+                }
+                Selector::Index { .. } => (),
+                Selector::Slice { span, .. } => {
+                    return unsupported("slice selectors are not supported", *span)
+                }
+                Selector::SingularQuery { span, .. } => {
+                    return unsupported("embedded queries need the root value", *span)
+                }
+                Selector::Filter { expression, span } => {
+                    let mut finder = RootFinder { found: false };
+                    finder.visit_filter_expression(expression);
+                    if finder.found {
+                        return unsupported("filters can't refer to the root value", *span);
+                    }
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+// Looks for anything in a filter expression that is evaluated against the
+// root value.
+struct RootFinder {
+    found: bool,
+}
+
+impl Visitor for RootFinder {
+    fn visit_selector(&mut self, selector: &Selector) {
+        self.found |= matches!(selector, Selector::SingularQuery { .. });
+        walk_selector(self, selector);
+    }
+
+    fn visit_filter_expression(&mut self, expression: &FilterExpression) {
+        self.found |= matches!(expression, FilterExpression::RootQuery { .. });
+        walk_filter_expression(self, expression);
+    }
+}
+
+/// An iterator over the normalized paths and values of matches found while
+/// reading a JSON document. See `stream`.
+pub struct StreamMatches<R> {
+    segments: Vec<Segment>,
+    // The query made of each segment onwards, for finishing evaluation of a
+    // value read in full.
+    suffixes: Vec<Query>,
+    reader: Reader<R>,
+    stack: Vec<Frame>,
+    // The location of the innermost open array or object.
+    path: Vec<PathElement>,
+    slots: Slots,
+    pending: VecDeque<(Query, serde_json::Value)>,
+    started: bool,
+    done: bool,
+}
+
+// A value that is an input to a segment, and the slot for the matches that
+// come from it. A candidate for a filter selector has the index of the
+// segment and selector it must pass first.
+struct Input {
+    segment: usize,
+    slot: usize,
+    filter: Option<(usize, usize)>,
+}
+
+// An input to a segment whose children are being read, with a slot for the
+// matches that come from each of the segment's selectors, and for a
+// descendant segment, a slot for matches that come from its children.
+struct OpenInput {
+    segment: usize,
+    slot: usize,
+    selectors: Vec<usize>,
+    descendants: Option<usize>,
+}
+
+// An array or object that is being read.
+struct Frame {
+    array: bool,
+    inputs: Vec<OpenInput>,
+    length: usize,
+}
+
+impl<R: Read> Iterator for StreamMatches<R> {
+    type Item = Result<(Query, serde_json::Value), LiquidError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(item) = self.pending.pop_front() {
+                return Some(Ok(item));
+            }
+
+            if self.done {
+                return None;
+            }
+
+            if let Err(err) = self.step() {
+                self.done = true;
+                return Some(Err(err));
+            }
+
+            self.slots.drain(ROOT_SLOT, &mut self.pending);
+        }
+    }
+}
+
+impl<R: Read> StreamMatches<R> {
+    // Reads the next value, or the end of an array or object.
+    fn step(&mut self) -> Result<(), LiquidError> {
+        if !self.started {
+            self.started = true;
+            let root = Input {
+                segment: 0,
+                slot: self.slots.open(None),
+                filter: None,
+            };
+            return self.enter(vec![root], None);
+        }
+
+        let Some(frame) = self.stack.last_mut() else {
+            if self.reader.peek_non_whitespace()?.is_some() {
+                return Err(self.reader.error("expected end of input"));
+            }
+            self.done = true;
+            return Ok(());
+        };
+
+        let close = if frame.array { b']' } else { b'}' };
+        let mut next = self.reader.peek_non_whitespace()?;
+
+        if next == Some(close) {
+            self.reader.bump();
+            if let Some(frame) = self.stack.pop() {
+                for input in frame.inputs {
+                    for slot in input.selectors.into_iter().chain(input.descendants) {
+                        self.slots.close(slot);
+                    }
+                    self.slots.close(input.slot);
+                }
+            }
+            if !self.stack.is_empty() {
+                self.path.pop();
+            }
+            return Ok(());
+        }
+
+        if frame.length > 0 {
+            if next != Some(b',') {
+                return Err(self
+                    .reader
+                    .error(&format!("expected `,` or `{}`", close as char)));
+            }
+            self.reader.bump();
+            next = self.reader.peek_non_whitespace()?;
+        }
+
+        let key = if frame.array {
+            PathElement::Index(frame.length)
+        } else {
+            if next != Some(b'"') {
+                return Err(self.reader.error("expected a member name"));
+            }
+            let name = self.reader.string(true)?;
+            self.reader.expect(b':')?;
+            PathElement::Name(name)
+        };
+
+        frame.length += 1;
+        let inputs = self.transition(&key);
+        self.enter(inputs, Some(key))
+    }
+
+    // Returns the inputs a child of the innermost open value, at _key_, is.
+    // Each gets a slot after the slots of earlier children, so matches come
+    // out in the same order as they do from `find`.
+    fn transition(&mut self, key: &PathElement) -> Vec<Input> {
+        let mut next = Vec::new();
+        let Some(frame) = self.stack.last() else {
+            return next;
+        };
+
+        for input in &frame.inputs {
+            let k = input.segment;
+            let selectors = match &self.segments[k] {
+                Segment::Child { selectors, .. } | Segment::Recursive { selectors, .. } => {
+                    selectors
+                }
+                Segment::Eoi {} => continue,
+            };
+
+            for (i, selector) in selectors.iter().enumerate() {
+                let (selected, filter) = match (selector, key) {
+                    (Selector::Name { name, .. }, PathElement::Name(key)) => (name == key, None),
+                    (Selector::Index { index, .. }, PathElement::Index(key)) => {
+                        (*index == *key as i64, None)
+                    }
+                    (Selector::Wild { .. }, _) => (true, None),
+                    (Selector::Filter { .. }, _) => (true, Some((k, i))),
+                    _ => (false, None),
+                };
+
+                if selected {
+                    next.push(Input {
+                        segment: k + 1,
+                        slot: self.slots.open(Some(input.selectors[i])),
+                        filter,
+                    });
+                }
+            }
+
+            // The selectors of a descendant segment apply to every
+            // descendant of its input.
+            if let Some(descendants) = input.descendants {
+                next.push(Input {
+                    segment: k,
+                    slot: self.slots.open(Some(descendants)),
+                    filter: None,
+                });
+            }
+        }
+
+        next
+    }
+
+    fn enter(&mut self, inputs: Vec<Input>, key: Option<PathElement>) -> Result<(), LiquidError> {
+        let keyed = key.is_some();
+        self.path.extend(key);
+
+        let complete = inputs
+            .iter()
+            .any(|input| input.segment == self.segments.len() || input.filter.is_some());
+
+        if complete {
+            let value = self.reader.value(true, 0)?;
+            self.evaluate(inputs, &value)?;
+        } else if !inputs.is_empty() {
+            let array = match self.reader.peek_non_whitespace()? {
+                Some(b'[') => true,
+                Some(b'{') => false,
+                _ => {
+                    // Scalars have no children to select.
+                    self.reader.value(false, 0)?;
+                    for input in inputs {
+                        self.slots.close(input.slot);
+                    }
+                    if keyed {
+                        self.path.pop();
+                    }
+                    return Ok(());
+                }
+            };
+
+            if self.stack.len() >= MAX_DEPTH {
+                return Err(self
+                    .reader
+                    .error("arrays and objects are nested too deeply"));
+            }
+
+            self.reader.bump();
+            let inputs = inputs.into_iter().map(|input| self.open(input)).collect();
+            self.stack.push(Frame {
+                array,
+                inputs,
+                length: 0,
+            });
+            return Ok(());
+        } else {
+            self.reader.value(false, 0)?;
+        }
+
+        if keyed {
+            self.path.pop();
+        }
+        Ok(())
+    }
+
+    // Makes slots for the matches that come from each selector of the
+    // segment _input_ is an input to.
+    fn open(&mut self, input: Input) -> OpenInput {
+        let (selectors, recursive) = match &self.segments[input.segment] {
+            Segment::Child { selectors, .. } => (selectors.len(), false),
+            Segment::Recursive { selectors, .. } => (selectors.len(), true),
+            Segment::Eoi {} => (0, false),
+        };
+
+        OpenInput {
+            segment: input.segment,
+            slot: input.slot,
+            selectors: (0..selectors)
+                .map(|_| self.slots.open(Some(input.slot)))
+                .collect(),
+            descendants: recursive.then(|| self.slots.open(Some(input.slot))),
+        }
+    }
+
+    // Finishes evaluating the query against _value_, which has been read in
+    // full, for each of _inputs_.
+    fn evaluate(
+        &mut self,
+        inputs: Vec<Input>,
+        value: &serde_json::Value,
+    ) -> Result<(), LiquidError> {
+        for input in inputs {
+            let selected = match input.filter {
+                Some((k, i)) => match &self.segments[k] {
+                    Segment::Child { selectors, .. } | Segment::Recursive { selectors, .. } => {
+                        match &selectors[i] {
+                            Selector::Filter { expression, .. } => {
+                                test_filter(expression, &value, &value)?
+                            }
+                            _ => true,
+                        }
+                    }
+                    Segment::Eoi {} => true,
+                },
+                None => true,
+            };
+
+            if selected {
+                for node in find(&self.suffixes[input.segment], &value)? {
+                    let path = [self.path.as_slice(), &node.location.to_vec()].concat();
+                    self.slots
+                        .push(input.slot, (normalized_path(&path), node.value.clone()));
+                }
+            }
+
+            self.slots.close(input.slot);
+        }
+
+        Ok(())
+    }
+}
+
+const ROOT_SLOT: usize = 0;
+
+// Matches in the order `find` reports them. A slot holds the matches that
+// come from one input to a segment, in order, mixed with the slots of inputs
+// that come after it. Matches are released from the front of the root slot
+// once every slot before them is closed, and closed slots are removed once
+// they are empty.
+#[derive(Default)]
+struct Slots {
+    slots: HashMap<usize, Slot>,
+    next: usize,
+}
+
+struct Slot {
+    items: VecDeque<Item>,
+    open: bool,
+}
+
+enum Item {
+    Match((Query, serde_json::Value)),
+    Slot(usize),
+}
+
+impl Slots {
+    // Returns a new slot, after everything in slot _parent_ if given.
+    fn open(&mut self, parent: Option<usize>) -> usize {
+        let id = self.next;
+        self.next += 1;
+        self.slots.insert(
+            id,
+            Slot {
+                items: VecDeque::new(),
+                open: true,
+            },
+        );
+        if let Some(slot) = parent.and_then(|parent| self.slots.get_mut(&parent)) {
+            slot.items.push_back(Item::Slot(id));
+        }
+        id
+    }
+
+    fn push(&mut self, id: usize, item: (Query, serde_json::Value)) {
+        if let Some(slot) = self.slots.get_mut(&id) {
+            slot.items.push_back(Item::Match(item));
+        }
+    }
+
+    fn close(&mut self, id: usize) {
+        if let Some(slot) = self.slots.get_mut(&id) {
+            slot.open = false;
+        }
+    }
+
+    // Moves matches that nothing can come before from slot _id_ to _out_.
+    // Returns `true` if slot _id_ is closed and empty, and has been removed.
+    fn drain(&mut self, id: usize, out: &mut VecDeque<(Query, serde_json::Value)>) -> bool {
+        loop {
+            let Some(slot) = self.slots.get_mut(&id) else {
+                return true;
+            };
+
+            match slot.items.pop_front() {
+                Some(Item::Match(item)) => out.push_back(item),
+                Some(Item::Slot(child)) => {
+                    if !self.drain(child, out) {
+                        if let Some(slot) = self.slots.get_mut(&id) {
+                            slot.items.push_front(Item::Slot(child));
+                        }
+                        return false;
+                    }
+                }
+                None if slot.open => return false,
+                None => {
+                    self.slots.remove(&id);
+                    return true;
+                }
+            }
+        }
+    }
+}
+
+// A pull parser for JSON text.
+struct Reader<R> {
+    inner: BufReader<R>,
+    offset: usize,
+}
+
+impl<R: Read> Reader<R> {
+    fn peek(&mut self) -> Result<Option<u8>, LiquidError> {
+        Ok(self.inner.fill_buf()?.first().copied())
+    }
+
+    fn bump(&mut self) {
+        self.inner.consume(1);
+        self.offset += 1;
+    }
+
+    fn next(&mut self) -> Result<u8, LiquidError> {
+        match self.peek()? {
+            Some(byte) => {
+                self.bump();
+                Ok(byte)
+            }
+            None => Err(self.error("unexpected end of input")),
+        }
+    }
+
+    fn peek_non_whitespace(&mut self) -> Result<Option<u8>, LiquidError> {
+        while let Some(byte) = self.peek()? {
+            if !matches!(byte, b' ' | b'\t' | b'\n' | b'\r') {
+                return Ok(Some(byte));
+            }
+            self.bump();
+        }
+        Ok(None)
+    }
+
+    fn expect(&mut self, expected: u8) -> Result<(), LiquidError> {
+        if self.peek_non_whitespace()? != Some(expected) {
+            return Err(self.error(&format!("expected `{}`", expected as char)));
+        }
+        self.bump();
+        Ok(())
+    }
+
+    // Reads a value, returning it if _keep_ is `true`, or `Null` otherwise.
+    fn value(&mut self, keep: bool, depth: usize) -> Result<serde_json::Value, LiquidError> {
+        if depth > MAX_DEPTH {
+            return Err(self.error("arrays and objects are nested too deeply"));
+        }
+
+        match self.peek_non_whitespace()? {
+            Some(b'{') => {
+                self.bump();
+                let mut members = serde_json::Map::new();
+                if self.peek_non_whitespace()? == Some(b'}') {
+                    self.bump();
+                } else {
+                    loop {
+                        if self.peek_non_whitespace()? != Some(b'"') {
+                            return Err(self.error("expected a member name"));
+                        }
+                        let name = self.string(keep)?;
+                        self.expect(b':')?;
+                        let value = self.value(keep, depth + 1)?;
+                        if keep {
+                            members.insert(name, value);
+                        }
+                        match self.peek_non_whitespace()? {
+                            Some(b',') => self.bump(),
+                            Some(b'}') => {
+                                self.bump();
+                                break;
+                            }
+                            _ => return Err(self.error("expected `,` or `}`")),
+                        }
+                    }
+                }
+                Ok(serde_json::Value::Object(members))
+            }
+            Some(b'[') => {
+                self.bump();
+                let mut items = Vec::new();
+                if self.peek_non_whitespace()? == Some(b']') {
+                    self.bump();
+                } else {
+                    loop {
+                        let item = self.value(keep, depth + 1)?;
+                        if keep {
+                            items.push(item);
+                        }
+                        match self.peek_non_whitespace()? {
+                            Some(b',') => self.bump(),
+                            Some(b']') => {
+                                self.bump();
+                                break;
+                            }
+                            _ => return Err(self.error("expected `,` or `]`")),
+                        }
+                    }
+                }
+                Ok(serde_json::Value::Array(items))
+            }
+            Some(b'"') => Ok(serde_json::Value::String(self.string(keep)?)),
+            Some(b't') => self.literal("true", serde_json::Value::Bool(true)),
+            Some(b'f') => self.literal("false", serde_json::Value::Bool(false)),
+            Some(b'n') => self.literal("null", serde_json::Value::Null),
+            Some(b'-' | b'0'..=b'9') => self.number(keep),
+            Some(_) => Err(self.error("expected a value")),
+            None => Err(self.error("unexpected end of input")),
+        }
+    }
+
+    fn literal(
+        &mut self,
+        text: &str,
+        value: serde_json::Value,
+    ) -> Result<serde_json::Value, LiquidError> {
+        for expected in text.bytes() {
+            if self.next()? != expected {
+                return Err(self.error(&format!("expected `{text}`")));
+            }
+        }
+        Ok(value)
+    }
+
+    // Reads a number, returning it if _keep_ is `true`, or `Null` otherwise.
+    // Integers that don't fit in an i64 would be read as floats, so they are
+    // an error if _keep_ is `true`.
+    fn number(&mut self, keep: bool) -> Result<serde_json::Value, LiquidError> {
+        let start = self.offset;
+        let mut text = String::new();
+        while let Some(byte @ (b'-' | b'+' | b'.' | b'e' | b'E' | b'0'..=b'9')) = self.peek()? {
+            text.push(byte as char);
+            self.bump();
+        }
+
+        let bad_number =
+            || LiquidError::syntax(format!("invalid JSON at byte {start}, bad number `{text}`"));
+
+        // A skipped number only has to be valid JSON, even if it's too big
+        // to read.
+        if !keep {
+            return match NUMBER.is_match(&text) {
+                true => Ok(serde_json::Value::Null),
+                false => Err(bad_number()),
+            };
+        }
+
+        let number = text
+            .parse::<serde_json::Number>()
+            .map_err(|_| bad_number())?;
+
+        if number.as_i64().is_none() && !text.contains(['.', 'e', 'E']) {
+            return Err(LiquidError::typ(format!(
+                "can't stream integer `{text}` at byte {start}, \
+                 it doesn't fit in a signed 64 bit integer"
+            )));
+        }
+
+        Ok(serde_json::Value::Number(number))
+    }
+
+    // Reads a string, including its quotes, returning an empty string unless
+    // _keep_ is `true`.
+    fn string(&mut self, keep: bool) -> Result<String, LiquidError> {
+        self.bump();
+        let mut bytes = Vec::new();
+
+        loop {
+            match self.next()? {
+                b'"' => break,
+                b'\\' => {
+                    let c = match self.next()? {
+                        b'"' => '"',
+                        b'\\' => '\\',
+                        b'/' => '/',
+                        b'b' => '\u{8}',
+                        b'f' => '\u{c}',
+                        b'n' => '\n',
+                        b'r' => '\r',
+                        b't' => '\t',
+                        b'u' => self.unicode_escape()?,
+                        _ => return Err(self.error("invalid escape")),
+                    };
+                    if keep {
+                        bytes.extend_from_slice(c.encode_utf8(&mut [0; 4]).as_bytes());
+                    }
+                }
+                byte if byte < 0x20 => return Err(self.error("unescaped control character")),
+                byte => {
+                    if keep {
+                        bytes.push(byte);
+                    }
+                }
+            }
+        }
+
+        String::from_utf8(bytes).map_err(|_| self.error("invalid UTF-8"))
+    }
+
+    fn unicode_escape(&mut self) -> Result<char, LiquidError> {
+        let high = self.hex()?;
+        let code_point = if (0xD800..0xDC00).contains(&high) {
+            if self.next()? != b'\\' || self.next()? != b'u' {
+                return Err(self.error("unpaired surrogate"));
+            }
+            let low = self.hex()?;
+            if !(0xDC00..0xE000).contains(&low) {
+                return Err(self.error("unpaired surrogate"));
+            }
+            0x10000 + ((high - 0xD800) << 10) + (low - 0xDC00)
+        } else {
+            high
+        };
+
+        char::from_u32(code_point).ok_or_else(|| self.error("unpaired surrogate"))
+    }
+
+    fn hex(&mut self) -> Result<u32, LiquidError> {
+        let mut value = 0;
+        for _ in 0..4 {
+            let digit = (self.next()? as char)
+                .to_digit(16)
+                .ok_or_else(|| self.error("invalid `\\u` escape"))?;
+            value = value * 16 + digit;
+        }
+        Ok(value)
+    }
+
+    fn error(&self, message: &str) -> LiquidError {
+        LiquidError::syntax(format!("invalid JSON at byte {}, {}", self.offset, message))
+    }
+}
@@ -1,10 +1,11 @@
 use core::str;
 
 use crate::errors::LiquidError;
+use crate::model::Span;
 
 // TODO: pass span or line/col to errors
 
-pub fn unescape(value: &str, span: &(usize, usize)) -> Result<String, LiquidError> {
+pub fn unescape(value: &str, span: &Option<Span>) -> Result<String, LiquidError> {
     let bytes = value.as_bytes();
     let length = bytes.len();
     let mut rv: Vec<u8> = Vec::new();
@@ -37,13 +38,13 @@ pub fn unescape(value: &str, span: &(usize, usize)) -> Result<String, LiquidErro
         index += 1;
     }
 
-    return Ok(String::from_utf8(rv).unwrap());
+    Ok(String::from_utf8(rv).unwrap())
 }
 
 fn decode_hex_char(
     bytes: &[u8],
     index: usize,
-    span: &(usize, usize),
+    span: &Option<Span>,
 ) -> Result<(u32, usize), LiquidError> {
     let length = bytes.len();
     let mut index = index;
@@ -52,7 +53,7 @@ fn decode_hex_char(
         return Err(LiquidError::syntax("incomplete escape sequence".to_owned()));
     }
 
-    index = index + 1; // move past 'u'
+    index += 1; // move past 'u'
     let mut code_point = parse_hex_digits(&bytes[index..index + 4], span)?;
 
     if is_low_surrogate(code_point) {
@@ -79,13 +80,13 @@ fn decode_hex_char(
     Ok((code_point, index + 3))
 }
 
-fn parse_hex_digits(digits: &[u8], span: &(usize, usize)) -> Result<u32, LiquidError> {
+fn parse_hex_digits(digits: &[u8], _span: &Option<Span>) -> Result<u32, LiquidError> {
     let s = str::from_utf8(digits).unwrap();
     u32::from_str_radix(s, 16)
         .map_err(|_| LiquidError::syntax("invalid escape sequence".to_owned()))
 }
 
-fn encode_code_point(code_point: u32, span: &(usize, usize)) -> Result<Vec<u8>, LiquidError> {
+fn encode_code_point(code_point: u32, _span: &Option<Span>) -> Result<Vec<u8>, LiquidError> {
     if code_point < 0x1F {
         Err(LiquidError::syntax("invalid character".to_owned()))
     } else {
@@ -97,9 +98,31 @@ fn encode_code_point(code_point: u32, span: &(usize, usize)) -> Result<Vec<u8>,
 }
 
 fn is_high_surrogate(code_point: u32) -> bool {
-    code_point >= 0xD800 && code_point <= 0xDBFF
+    (0xD800..=0xDBFF).contains(&code_point)
 }
 
 fn is_low_surrogate(code_point: u32) -> bool {
-    code_point >= 0xDC00 && code_point <= 0xDFFF
+    (0xDC00..=0xDFFF).contains(&code_point)
+}
+
+/// Return _value_ as a single-quoted string literal that reads back as
+/// _value_ when unescaped.
+pub fn quote(value: &str) -> String {
+    let mut rv = String::with_capacity(value.len() + 2);
+    rv.push('\'');
+    for c in value.chars() {
+        match c {
+            '\\' => rv.push_str("\\\\"),
+            '\'' => rv.push_str("\\'"),
+            '\x08' => rv.push_str("\\b"),
+            '\x0C' => rv.push_str("\\f"),
+            '\n' => rv.push_str("\\n"),
+            '\r' => rv.push_str("\\r"),
+            '\t' => rv.push_str("\\t"),
+            c if c < '\x20' => rv.push_str(&format!("\\u{:04x}", c as u32)),
+            c => rv.push(c),
+        }
+    }
+    rv.push('\'');
+    rv
 }
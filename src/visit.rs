@@ -0,0 +1,493 @@
+//! Traversal of template and query syntax trees
+//!
+//! `Visitor` walks a tree by reference and `Fold` rebuilds it by value. Every
+//! method has a default implementation that recurses into child nodes, so
+//! implementations only override methods for the nodes they care about,
+//! calling the matching `walk_*` or `fold_*` function to keep descending.
+
+use pyo3::exceptions::PyTypeError;
+use pyo3::prelude::*;
+use pyo3::types::{PyList, PyTuple};
+
+use crate::markup::{Markup, RangeArgument, Token};
+use crate::model::Span;
+use crate::query::{FilterExpression, Query, Segment, Selector};
+
+/// Visit syntax tree nodes in depth-first, pre-order.
+pub trait Visitor {
+    fn visit_markup(&mut self, markup: &Markup) {
+        walk_markup(self, markup);
+    }
+
+    fn visit_token(&mut self, token: &Token) {
+        walk_token(self, token);
+    }
+
+    fn visit_range_argument(&mut self, argument: &RangeArgument) {
+        walk_range_argument(self, argument);
+    }
+
+    fn visit_query(&mut self, query: &Query) {
+        walk_query(self, query);
+    }
+
+    fn visit_segment(&mut self, segment: &Segment) {
+        walk_segment(self, segment);
+    }
+
+    fn visit_selector(&mut self, selector: &Selector) {
+        walk_selector(self, selector);
+    }
+
+    fn visit_filter_expression(&mut self, expression: &FilterExpression) {
+        walk_filter_expression(self, expression);
+    }
+}
+
+pub fn walk_markup<V: Visitor + ?Sized>(visitor: &mut V, markup: &Markup) {
+    match markup {
+        Markup::Output { expression, .. }
+        | Markup::Tag {
+            expression: Some(expression),
+            ..
+        } => {
+            for token in expression {
+                visitor.visit_token(token);
+            }
+        }
+        Markup::Lines { statements, .. } => {
+            for statement in statements {
+                visitor.visit_markup(statement);
+            }
+        }
+        _ => (),
+    }
+}
+
+pub fn walk_token<V: Visitor + ?Sized>(visitor: &mut V, token: &Token) {
+    match token {
+        Token::RangeLiteral { start, stop, .. } => {
+            visitor.visit_range_argument(start);
+            visitor.visit_range_argument(stop);
+        }
+        Token::Query { path, .. } => visitor.visit_query(path),
+        _ => (),
+    }
+}
+
+pub fn walk_range_argument<V: Visitor + ?Sized>(visitor: &mut V, argument: &RangeArgument) {
+    if let RangeArgument::Query { path, .. } = argument {
+        visitor.visit_query(path);
+    }
+}
+
+pub fn walk_query<V: Visitor + ?Sized>(visitor: &mut V, query: &Query) {
+    for segment in &query.segments {
+        visitor.visit_segment(segment);
+    }
+}
+
+pub fn walk_segment<V: Visitor + ?Sized>(visitor: &mut V, segment: &Segment) {
+    match segment {
+        Segment::Child { selectors, .. } | Segment::Recursive { selectors, .. } => {
+            for selector in selectors {
+                visitor.visit_selector(selector);
+            }
+        }
+        Segment::Eoi {} => (),
+    }
+}
+
+pub fn walk_selector<V: Visitor + ?Sized>(visitor: &mut V, selector: &Selector) {
+    match selector {
+        Selector::Filter { expression, .. } => visitor.visit_filter_expression(expression),
+        Selector::SingularQuery { query, .. } => visitor.visit_query(query),
+        _ => (),
+    }
+}
+
+pub fn walk_filter_expression<V: Visitor + ?Sized>(visitor: &mut V, expression: &FilterExpression) {
+    match expression {
+        FilterExpression::Not { expression, .. } => visitor.visit_filter_expression(expression),
+        FilterExpression::Logical { left, right, .. }
+        | FilterExpression::Comparison { left, right, .. } => {
+            visitor.visit_filter_expression(left);
+            visitor.visit_filter_expression(right);
+        }
+        FilterExpression::RelativeQuery { query, .. }
+        | FilterExpression::RootQuery { query, .. } => visitor.visit_query(query),
+        FilterExpression::Function { args, .. } => {
+            for arg in args {
+                visitor.visit_filter_expression(arg);
+            }
+        }
+        _ => (),
+    }
+}
+
+/// Rebuild syntax trees bottom-up, replacing nodes.
+pub trait Fold {
+    fn fold_markup(&mut self, markup: Markup) -> Markup {
+        fold_markup(self, markup)
+    }
+
+    fn fold_token(&mut self, token: Token) -> Token {
+        fold_token(self, token)
+    }
+
+    fn fold_range_argument(&mut self, argument: RangeArgument) -> RangeArgument {
+        fold_range_argument(self, argument)
+    }
+
+    fn fold_query(&mut self, query: Query) -> Query {
+        fold_query(self, query)
+    }
+
+    fn fold_segment(&mut self, segment: Segment) -> Segment {
+        fold_segment(self, segment)
+    }
+
+    fn fold_selector(&mut self, selector: Selector) -> Selector {
+        fold_selector(self, selector)
+    }
+
+    fn fold_filter_expression(&mut self, expression: FilterExpression) -> FilterExpression {
+        fold_filter_expression(self, expression)
+    }
+}
+
+pub fn fold_markup<F: Fold + ?Sized>(folder: &mut F, markup: Markup) -> Markup {
+    match markup {
+        Markup::Output {
+            wc,
+            expression,
+            span,
+        } => Markup::Output {
+            wc,
+            expression: fold_tokens(folder, expression),
+            span,
+        },
+        Markup::Tag {
+            wc,
+            name,
+            expression,
+            span,
+        } => Markup::Tag {
+            wc,
+            name,
+            expression: expression.map(|e| fold_tokens(folder, e)),
+            span,
+        },
+        Markup::Lines {
+            wc,
+            name,
+            statements,
+            span,
+        } => Markup::Lines {
+            wc,
+            name,
+            statements: statements
+                .into_iter()
+                .map(|s| folder.fold_markup(s))
+                .collect(),
+            span,
+        },
+        markup => markup,
+    }
+}
+
+fn fold_tokens<F: Fold + ?Sized>(folder: &mut F, tokens: Vec<Token>) -> Vec<Token> {
+    tokens.into_iter().map(|t| folder.fold_token(t)).collect()
+}
+
+pub fn fold_token<F: Fold + ?Sized>(folder: &mut F, token: Token) -> Token {
+    match token {
+        Token::RangeLiteral { start, stop, span } => Token::RangeLiteral {
+            start: folder.fold_range_argument(start),
+            stop: folder.fold_range_argument(stop),
+            span,
+        },
+        Token::Query { path, span } => Token::Query {
+            path: folder.fold_query(path),
+            span,
+        },
+        token => token,
+    }
+}
+
+pub fn fold_range_argument<F: Fold + ?Sized>(
+    folder: &mut F,
+    argument: RangeArgument,
+) -> RangeArgument {
+    match argument {
+        RangeArgument::Query { path, span } => RangeArgument::Query {
+            path: folder.fold_query(path),
+            span,
+        },
+        argument => argument,
+    }
+}
+
+pub fn fold_query<F: Fold + ?Sized>(folder: &mut F, query: Query) -> Query {
+    Query {
+        segments: query
+            .segments
+            .into_iter()
+            .map(|s| folder.fold_segment(s))
+            .collect(),
+    }
+}
+
+pub fn fold_segment<F: Fold + ?Sized>(folder: &mut F, segment: Segment) -> Segment {
+    match segment {
+        Segment::Child { selectors, span } => Segment::Child {
+            selectors: fold_selectors(folder, selectors),
+            span,
+        },
+        Segment::Recursive { selectors, span } => Segment::Recursive {
+            selectors: fold_selectors(folder, selectors),
+            span,
+        },
+        Segment::Eoi {} => Segment::Eoi {},
+    }
+}
+
+fn fold_selectors<F: Fold + ?Sized>(folder: &mut F, selectors: Vec<Selector>) -> Vec<Selector> {
+    selectors
+        .into_iter()
+        .map(|s| folder.fold_selector(s))
+        .collect()
+}
+
+pub fn fold_selector<F: Fold + ?Sized>(folder: &mut F, selector: Selector) -> Selector {
+    match selector {
+        Selector::Filter { expression, span } => Selector::Filter {
+            expression: Box::new(folder.fold_filter_expression(*expression)),
+            span,
+        },
+        Selector::SingularQuery { query, span } => Selector::SingularQuery {
+            query: Box::new(folder.fold_query(*query)),
+            span,
+        },
+        selector => selector,
+    }
+}
+
+pub fn fold_filter_expression<F: Fold + ?Sized>(
+    folder: &mut F,
+    expression: FilterExpression,
+) -> FilterExpression {
+    let mut fold = |e: Box<FilterExpression>| Box::new(folder.fold_filter_expression(*e));
+    match expression {
+        FilterExpression::Not { expression, span } => FilterExpression::Not {
+            expression: fold(expression),
+            span,
+        },
+        FilterExpression::Logical {
+            left,
+            operator,
+            right,
+            span,
+        } => FilterExpression::Logical {
+            left: fold(left),
+            operator,
+            right: fold(right),
+            span,
+        },
+        FilterExpression::Comparison {
+            left,
+            operator,
+            right,
+            span,
+        } => FilterExpression::Comparison {
+            left: fold(left),
+            operator,
+            right: fold(right),
+            span,
+        },
+        FilterExpression::RelativeQuery { query, span } => FilterExpression::RelativeQuery {
+            query: Box::new(folder.fold_query(*query)),
+            span,
+        },
+        FilterExpression::RootQuery { query, span } => FilterExpression::RootQuery {
+            query: Box::new(folder.fold_query(*query)),
+            span,
+        },
+        FilterExpression::Function { name, args, span } => FilterExpression::Function {
+            name,
+            args: args
+                .into_iter()
+                .map(|a| folder.fold_filter_expression(a))
+                .collect(),
+            span,
+        },
+        expression => expression,
+    }
+}
+
+type WalkItem = (PyObject, Option<PyObject>, Option<Span>);
+
+/// Collects `(node, parent, span)` for every node in a syntax tree.
+struct PyWalker<'py> {
+    py: Python<'py>,
+    /// The Python object to use for the next node entered, so top level
+    /// nodes are yielded as the objects that were passed in.
+    root: Option<PyObject>,
+    parents: Vec<PyObject>,
+    items: Vec<WalkItem>,
+}
+
+impl<'py> PyWalker<'py> {
+    fn enter<T: Clone + IntoPy<PyObject>>(&mut self, node: &T, span: Option<Span>) {
+        let py = self.py;
+        let node = self.root.take().unwrap_or_else(|| node.clone().into_py(py));
+        let parent = self.parents.last().map(|p| p.clone_ref(py));
+        self.items.push((node.clone_ref(py), parent, span));
+        self.parents.push(node);
+    }
+
+    fn exit(&mut self) {
+        self.parents.pop();
+    }
+
+    // Walks _obj_, a node or a list or tuple of nodes.
+    fn walk_object(&mut self, obj: &Bound<'py, PyAny>) -> PyResult<()> {
+        if self.walk_node(obj) {
+            return Ok(());
+        }
+
+        let items = match obj.downcast::<PyList>() {
+            Ok(items) => items.to_tuple(),
+            Err(_) => obj
+                .downcast::<PyTuple>()
+                .map_err(|_| not_a_node(obj))?
+                .clone(),
+        };
+
+        for item in items.iter() {
+            if !self.walk_node(&item) {
+                return Err(not_a_node(&item));
+            }
+        }
+
+        Ok(())
+    }
+
+    // Walks _obj_ and returns `true` if it is a syntax tree node.
+    fn walk_node(&mut self, obj: &Bound<'py, PyAny>) -> bool {
+        self.root = Some(obj.clone().unbind());
+
+        if let Ok(node) = obj.downcast::<Markup>() {
+            self.visit_markup(node.get());
+        } else if let Ok(node) = obj.downcast::<Token>() {
+            self.visit_token(node.get());
+        } else if let Ok(node) = obj.downcast::<RangeArgument>() {
+            self.visit_range_argument(node.get());
+        } else if let Ok(node) = obj.downcast::<Query>() {
+            self.visit_query(node.get());
+        } else if let Ok(node) = obj.downcast::<Segment>() {
+            self.visit_segment(node.get());
+        } else if let Ok(node) = obj.downcast::<Selector>() {
+            self.visit_selector(node.get());
+        } else if let Ok(node) = obj.downcast::<FilterExpression>() {
+            self.visit_filter_expression(node.get());
+        } else {
+            self.root = None;
+            return false;
+        }
+
+        true
+    }
+}
+
+fn not_a_node(obj: &Bound<'_, PyAny>) -> PyErr {
+    let name = obj
+        .get_type()
+        .name()
+        .map_or_else(|_| "object".to_owned(), |name| name.to_string());
+    PyTypeError::new_err(format!(
+        "expected a syntax tree node or a list of nodes, found {name}"
+    ))
+}
+
+impl Visitor for PyWalker<'_> {
+    fn visit_markup(&mut self, markup: &Markup) {
+        self.enter(markup, markup.span());
+        walk_markup(self, markup);
+        self.exit();
+    }
+
+    fn visit_token(&mut self, token: &Token) {
+        self.enter(token, token.span());
+        walk_token(self, token);
+        self.exit();
+    }
+
+    fn visit_range_argument(&mut self, argument: &RangeArgument) {
+        self.enter(argument, argument.span());
+        walk_range_argument(self, argument);
+        self.exit();
+    }
+
+    fn visit_query(&mut self, query: &Query) {
+        self.enter(query, query.span());
+        walk_query(self, query);
+        self.exit();
+    }
+
+    fn visit_segment(&mut self, segment: &Segment) {
+        self.enter(segment, segment.span());
+        walk_segment(self, segment);
+        self.exit();
+    }
+
+    fn visit_selector(&mut self, selector: &Selector) {
+        self.enter(selector, selector.span());
+        walk_selector(self, selector);
+        self.exit();
+    }
+
+    fn visit_filter_expression(&mut self, expression: &FilterExpression) {
+        self.enter(expression, expression.span());
+        walk_filter_expression(self, expression);
+        self.exit();
+    }
+}
+
+/// An iterator over `(node, parent, span)` tuples collected by `walk`.
+#[pyclass]
+pub struct NodeIterator {
+    items: std::vec::IntoIter<WalkItem>,
+}
+
+#[pymethods]
+impl NodeIterator {
+    fn __iter__(slf: PyRef<'_, Self>) -> PyRef<'_, Self> {
+        slf
+    }
+
+    fn __next__(&mut self) -> Option<WalkItem> {
+        self.items.next()
+    }
+}
+
+/// Return an iterator over every node in _nodes_, a syntax tree node or a
+/// list or tuple of nodes, in depth-first, pre-order. Each item is a tuple of
+/// the node, its parent node or `None` for top level nodes, and its span.
+///
+/// The whole tree is walked before the first item is returned, so the
+/// iterator is eager, not a generator.
+#[pyfunction]
+#[pyo3(name = "walk")]
+pub fn py_walk(nodes: &Bound<'_, PyAny>) -> PyResult<NodeIterator> {
+    let mut walker = PyWalker {
+        py: nodes.py(),
+        root: None,
+        parents: Vec::new(),
+        items: Vec::new(),
+    };
+    walker.walk_object(nodes)?;
+    Ok(NodeIterator {
+        items: walker.items.into_iter(),
+    })
+}
@@ -0,0 +1,230 @@
+//! Load YAML documents, keeping mapping order and timestamps
+//!
+//! Plain scalars are resolved with the YAML 1.2 core schema, except that
+//! plain scalars that look like YAML 1.1 timestamps, like `2024-01-31` or
+//! `2024-01-31 12:30:00Z`, are timestamps. Quoted scalars are strings unless
+//! they have an explicit core tag like `!!int` or `!!float`.
+
+use std::collections::HashMap;
+
+use lazy_static::lazy_static;
+use regex::Regex;
+use yaml_rust2::parser::{Event, EventReceiver, Parser, Tag};
+use yaml_rust2::scanner::TScalarStyle;
+
+use crate::errors::LiquidError;
+
+/// The most values that aliases can add to a document, so that a few
+/// nested anchors can't expand to billions of values.
+const MAX_ALIAS_VALUES: usize = 1_000_000;
+
+lazy_static! {
+    static ref TIMESTAMP: Regex = Regex::new(
+        r"^([0-9]{4})-([0-9]{1,2})-([0-9]{1,2})(?:(?:[Tt]|[ \t]+)([0-9]{1,2}):([0-9]{2}):([0-9]{2})(\.[0-9]*)?(?:[ \t]*(Z|[-+][0-9]{1,2}(?::[0-9]{2})?))?)?$"
+    )
+    .unwrap();
+    static ref INTEGER: Regex = Regex::new(r"^(?:[-+]?[0-9]+|0o[0-7]+|0x[0-9a-fA-F]+)$").unwrap();
+}
+
+/// A YAML value.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Yaml {
+    Null,
+    Bool(bool),
+    Int(i64),
+    Float(f64),
+    String(String),
+    /// A date or date-time as RFC 3339 text.
+    Timestamp(String),
+    Sequence(Vec<Yaml>),
+    /// Mapping entries in document order. Keys are not always strings.
+    Mapping(Vec<(Yaml, Yaml)>),
+}
+
+/// Parse the first document in YAML _source_. An empty stream is `null`.
+pub fn parse(source: &str) -> Result<Yaml, LiquidError> {
+    let mut loader = Loader::default();
+    Parser::new_from_str(source)
+        .load(&mut loader, false)
+        .map_err(|err| LiquidError::syntax(format!("invalid YAML, {err}")))?;
+    match loader.error {
+        Some(err) => Err(err),
+        None => Ok(loader.document.unwrap_or(Yaml::Null)),
+    }
+}
+
+#[derive(Default)]
+struct Loader {
+    document: Option<Yaml>,
+    // Open collections with their anchor IDs, and for each open mapping, a
+    // key waiting for its value.
+    stack: Vec<(Yaml, usize)>,
+    keys: Vec<Option<Yaml>>,
+    // Anchored values with the number of values in each.
+    anchors: HashMap<usize, (Yaml, usize)>,
+    // The number of values added by aliases so far.
+    aliased: usize,
+    error: Option<LiquidError>,
+}
+
+impl EventReceiver for Loader {
+    fn on_event(&mut self, event: Event) {
+        if self.error.is_some() {
+            return;
+        }
+
+        match event {
+            Event::SequenceStart(anchor, _) => {
+                self.stack.push((Yaml::Sequence(Vec::new()), anchor))
+            }
+            Event::MappingStart(anchor, _) => {
+                self.stack.push((Yaml::Mapping(Vec::new()), anchor));
+                self.keys.push(None);
+            }
+            Event::SequenceEnd | Event::MappingEnd => {
+                if matches!(event, Event::MappingEnd) {
+                    self.keys.pop();
+                }
+                if let Some((node, anchor)) = self.stack.pop() {
+                    self.insert(node, anchor);
+                }
+            }
+            Event::Scalar(value, style, anchor, tag) => match scalar(value, style, tag.as_ref()) {
+                Ok(node) => self.insert(node, anchor),
+                Err(err) => self.error = Some(err),
+            },
+            Event::Alias(anchor) => {
+                // Check the size of an anchored value before copying it.
+                self.aliased += self.anchors.get(&anchor).map_or(1, |(_, size)| *size);
+                if self.aliased > MAX_ALIAS_VALUES {
+                    self.error = Some(LiquidError::syntax(format!(
+                        "invalid YAML, aliases expand to more than {MAX_ALIAS_VALUES} values"
+                    )));
+                    return;
+                }
+                let node = self
+                    .anchors
+                    .get(&anchor)
+                    .map_or(Yaml::Null, |(node, _)| node.clone());
+                self.insert(node, 0);
+            }
+            _ => (),
+        }
+    }
+}
+
+impl Loader {
+    fn insert(&mut self, node: Yaml, anchor: usize) {
+        if anchor > 0 {
+            self.anchors.insert(anchor, (node.clone(), size(&node)));
+        }
+
+        match self.stack.last_mut() {
+            Some((Yaml::Sequence(items), _)) => items.push(node),
+            Some((Yaml::Mapping(entries), _)) => {
+                // Every open mapping has a slot in `keys`.
+                let key = self.keys.last_mut().expect("a key for an open mapping");
+                match key.take() {
+                    Some(key) => entries.push((key, node)),
+                    None => *key = Some(node),
+                }
+            }
+            _ => {
+                self.document.get_or_insert(node);
+            }
+        }
+    }
+}
+
+// Returns the number of values in _node_, including _node_.
+fn size(node: &Yaml) -> usize {
+    match node {
+        Yaml::Sequence(items) => 1 + items.iter().map(size).sum::<usize>(),
+        Yaml::Mapping(entries) => {
+            1 + entries
+                .iter()
+                .map(|(key, value)| size(key) + size(value))
+                .sum::<usize>()
+        }
+        _ => 1,
+    }
+}
+
+fn scalar(value: String, style: TScalarStyle, tag: Option<&Tag>) -> Result<Yaml, LiquidError> {
+    let core = tag
+        .filter(|tag| tag.handle == "tag:yaml.org,2002:")
+        .map(|tag| tag.suffix.as_str());
+
+    let resolved = match core {
+        Some("str") => return Ok(Yaml::String(value)),
+        Some("null" | "bool" | "int" | "float") => resolve(&value)?,
+        Some("timestamp") => timestamp(&value).map_or(Yaml::String(value.clone()), Yaml::Timestamp),
+        _ if style != TScalarStyle::Plain => return Ok(Yaml::String(value)),
+        _ => match timestamp(&value) {
+            Some(timestamp) => Yaml::Timestamp(timestamp),
+            None => resolve(&value)?,
+        },
+    };
+
+    // An explicit core tag must agree with the value it tags, except that
+    // an integer can be tagged as a float.
+    match (core, resolved) {
+        (Some("null"), resolved @ Yaml::Null)
+        | (Some("bool"), resolved @ Yaml::Bool(_))
+        | (Some("int"), resolved @ Yaml::Int(_))
+        | (Some("float"), resolved @ Yaml::Float(_)) => Ok(resolved),
+        (Some("float"), Yaml::Int(int)) => Ok(Yaml::Float(int as f64)),
+        (Some(tag @ ("null" | "bool" | "int" | "float")), _) => Err(LiquidError::syntax(format!(
+            "invalid YAML, `{value}` is not a !!{tag}"
+        ))),
+        (_, resolved) => Ok(resolved),
+    }
+}
+
+// Resolves _value_ with the YAML 1.2 core schema.
+fn resolve(value: &str) -> Result<Yaml, LiquidError> {
+    match yaml_rust2::Yaml::from_str(value) {
+        yaml_rust2::Yaml::Null => Ok(Yaml::Null),
+        yaml_rust2::Yaml::Boolean(value) => Ok(Yaml::Bool(value)),
+        yaml_rust2::Yaml::Integer(value) => Ok(Yaml::Int(value)),
+        // Integers too big for `i64` would otherwise read as floats.
+        _ if INTEGER.is_match(value) => Err(LiquidError::syntax(format!(
+            "invalid YAML, `{value}` doesn't fit in a signed 64 bit integer"
+        ))),
+        real @ yaml_rust2::Yaml::Real(_) => Ok(Yaml::Float(real.as_f64().unwrap_or(f64::NAN))),
+        _ => Ok(Yaml::String(value.to_owned())),
+    }
+}
+
+/// Return YAML 1.1 timestamp _value_ as RFC 3339 text, or `None` if _value_
+/// is not a timestamp.
+fn timestamp(value: &str) -> Option<String> {
+    let captures = TIMESTAMP.captures(value)?;
+    let number = |index: usize| captures.get(index).map(|m| m.as_str().parse::<u32>().ok());
+    let (month, day) = (number(2)??, number(3)??);
+
+    let Some(hour) = number(4) else {
+        // A date on its own must use two digits for the month and day.
+        return (value.len() == 10).then(|| value.to_owned());
+    };
+
+    let mut text = format!(
+        "{}-{month:02}-{day:02}T{:02}:{}:{}",
+        &captures[1], hour?, &captures[5], &captures[6]
+    );
+
+    if let Some(fraction) = captures.get(7).filter(|m| m.len() > 1) {
+        text.push_str(fraction.as_str());
+    }
+
+    match captures.get(8).map(|m| m.as_str()) {
+        Some("Z") => text.push('Z'),
+        Some(offset) => {
+            let (hours, minutes) = offset[1..].split_once(':').unwrap_or((&offset[1..], "00"));
+            text.push_str(&format!("{}{:0>2}:{minutes}", &offset[..1], hours));
+        }
+        None => (),
+    }
+
+    Some(text)
+}